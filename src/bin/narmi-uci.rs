@@ -0,0 +1,37 @@
+//! The narmi engine speaking the Universal Chess Interface over stdin and stdout, for chess GUIs and
+//! engine-versus-engine matches.
+
+extern crate narmi_chess;
+
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::channel;
+use std::thread;
+
+use narmi_chess::engine::uci::Uci;
+
+fn main() {
+    let (sender, receiver) = channel::<String>();
+    // replies come from the search thread as well as this one, so a single writer keeps lines whole
+    let writer = thread::spawn(move || {
+        let stdout = io::stdout();
+        for line in receiver {
+            let mut out = stdout.lock();
+            if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut uci = Uci::new(sender);
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) if uci.handle(&line) => {}
+            _ => break,
+        }
+    }
+    // the end of input is as good as quit
+    uci.handle("quit");
+    drop(uci);
+    let _ = writer.join();
+}
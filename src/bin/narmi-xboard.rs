@@ -0,0 +1,37 @@
+//! The narmi engine speaking the Chess Engine Communication Protocol over stdin and stdout, for XBoard,
+//! WinBoard and other GUIs which speak it.
+
+extern crate narmi_chess;
+
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::channel;
+use std::thread;
+
+use narmi_chess::engine::xboard::Xboard;
+
+fn main() {
+    let (sender, receiver) = channel::<String>();
+    // replies come from the search thread as well as this one, so a single writer keeps lines whole
+    let writer = thread::spawn(move || {
+        let stdout = io::stdout();
+        for line in receiver {
+            let mut out = stdout.lock();
+            if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut xboard = Xboard::new(sender);
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) if xboard.handle(&line) => {}
+            _ => break,
+        }
+    }
+    // the end of input is as good as quit
+    xboard.handle("quit");
+    drop(xboard);
+    let _ = writer.join();
+}
@@ -0,0 +1,172 @@
+//! Command line tools for the narmi-chess library.
+//!
+//!     narmi perft <depth> [fen]     count the leaf nodes of the move tree to the given depth
+//!     narmi divide <depth> [fen]    the same, broken down by root move
+//!     narmi bench [depth]           search node counts with the engine's options switched on in turn
+//!     narmi book <games.pgn> <book.bin> [depth] [min-games]
+//!                                   build a Polyglot opening book from the games' first moves
+//!     narmi probe <syzygy-dir> [fen]
+//!                                   what the Syzygy tablebases say about the position and each move
+//!     narmi endgame <KRK|KBNK|...> [table.bin]
+//!                                   generate an ending's table, check it against the move generator and
+//!                                   optionally save it
+//!     narmi mate <moves> [fen]      solve a mate in n problem: every key and the play after it
+//!     narmi selfmate <moves> [fen]  the same for a selfmate, the side to move forcing the other to mate it
+//!     narmi helpmate <moves> [fen]  every line of a helpmate, the side to move helping the other to mate it
+
+extern crate narmi_chess;
+
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Instant;
+
+use narmi_chess::engine::bench::{bench, configurations, BENCH_POSITIONS};
+use narmi_chess::engine::book::{Book, BookOptions};
+use narmi_chess::engine::problem::{DirectMate, HelpMate, SelfMate};
+use narmi_chess::engine::retrograde::EndgameTable;
+use narmi_chess::engine::syzygy::Tablebases;
+use narmi_chess::game::fen;
+use narmi_chess::game::notation::encode;
+use narmi_chess::game::perft::{divide_fen, perft_fen};
+
+const USAGE: &str = "usage: narmi perft <depth> [fen]
+       narmi divide <depth> [fen]
+       narmi bench [depth]
+       narmi book <games.pgn> <book.bin> [depth] [min-games]
+       narmi probe <syzygy-dir> [fen]
+       narmi endgame <KRK|KBNK|...> [table.bin]
+       narmi mate <moves> [fen]
+       narmi selfmate <moves> [fen]
+       narmi helpmate <moves> [fen]";
+
+fn fail(reason: &str) -> ! {
+    eprintln!("{}", reason);
+    process::exit(1);
+}
+
+fn depth_and_fen(args: &[String]) -> (u32, String) {
+    let depth = match args.first().map(|depth| depth.parse()) {
+        Some(Ok(depth)) => depth,
+        _ => fail(USAGE),
+    };
+    let fen = if args.len() > 1 {
+        args[1..].join(" ")
+    } else {
+        fen::INITIAL_FEN.to_string()
+    };
+    (depth, fen)
+}
+
+fn perft_command(args: &[String]) {
+    let (depth, fen) = depth_and_fen(args);
+    let started = Instant::now();
+    let nodes = perft_fen(&fen, depth).unwrap_or_else(|err| fail(&err.reason));
+    let elapsed = started.elapsed().as_secs_f64();
+    println!("{}", nodes);
+    eprintln!("{:.3}s, {:.0} nodes/s", elapsed, nodes as f64 / elapsed.max(1e-9));
+}
+
+fn divide_command(args: &[String]) {
+    let (depth, fen) = depth_and_fen(args);
+    let divided = divide_fen(&fen, depth).unwrap_or_else(|err| fail(&err.reason));
+    for (chess_move, nodes) in divided.iter() {
+        println!("{}: {}", chess_move, nodes);
+    }
+    println!();
+    println!("moves: {}", divided.len());
+    println!("nodes: {}", divided.iter().map(|(_, nodes)| nodes).sum::<u64>());
+}
+
+fn bench_command(args: &[String]) {
+    let depth = match args.first().map(|depth| depth.parse()) {
+        None => 6,
+        Some(Ok(depth)) => depth,
+        Some(Err(_)) => fail(USAGE),
+    };
+    for (name, options) in configurations() {
+        let result = bench(options, depth, &BENCH_POSITIONS);
+        println!(
+            "{:<24} {:>12} nodes {:>8} ms {:>10.0} nodes/s",
+            name,
+            result.nodes,
+            result.time_ms,
+            result.nodes as f64 * 1000.0 / (result.time_ms.max(1) as f64)
+        );
+    }
+}
+
+fn book_command(args: &[String]) {
+    let (pgn, out) = match args {
+        [pgn, out, ..] => (pgn, out),
+        _ => fail(USAGE),
+    };
+    let defaults = BookOptions::default();
+    let options = match (args.get(2).map(|n| n.parse()), args.get(3).map(|n| n.parse())) {
+        (None, None) => defaults,
+        (Some(Ok(depth)), None) => BookOptions { depth, ..defaults },
+        (Some(Ok(depth)), Some(Ok(min_games))) => BookOptions { depth, min_games },
+        _ => fail(USAGE),
+    };
+    let text = fs::read_to_string(pgn).unwrap_or_else(|err| fail(&format!("Couldn't read {}: {}", pgn, err)));
+    let (book, skipped) = Book::build_from_pgn(&text, &options);
+    for game in skipped {
+        eprintln!("Skipped game {}: {}", game.number, game.reason);
+    }
+    fs::write(out, book.to_bytes()).unwrap_or_else(|err| fail(&format!("Couldn't write {}: {}", out, err)));
+    println!("{} entries", book.len());
+}
+
+fn probe_command(args: &[String]) {
+    let directory = args.first().unwrap_or_else(|| fail(USAGE));
+    let fen = if args.len() > 1 { args[1..].join(" ") } else { fen::INITIAL_FEN.to_string() };
+    let board = fen::decode(&fen).unwrap_or_else(|err| fail(&err.reason));
+    let tablebases = Tablebases::open(directory).unwrap_or_else(|err| fail(&err.reason));
+    let verdict = tablebases.verdict(&board).unwrap_or_else(|err| fail(&err.reason));
+    println!("{}", verdict);
+    for root_move in tablebases.root_moves(&board).unwrap_or_else(|err| fail(&err.reason)) {
+        println!("{:<8} {:<12} DTZ {}", encode(&board, &root_move.chess_move), root_move.wdl, root_move.dtz);
+    }
+}
+
+fn endgame_command(args: &[String]) {
+    let name = args.first().unwrap_or_else(|| fail(USAGE));
+    let started = Instant::now();
+    let table = EndgameTable::generate(name).unwrap_or_else(|err| fail(&err.reason));
+    let (wins, draws, losses) = table.counts();
+    println!("{}: {} positions, {} wins, {} draws, {} losses", table.name(), table.len(), wins, draws, losses);
+    println!("longest mate: {} plies", table.longest_win());
+    table.check().unwrap_or_else(|err| fail(&err.reason));
+    eprintln!("{:.3}s", started.elapsed().as_secs_f64());
+    if let Some(out) = args.get(1) {
+        let bytes = table.to_bytes();
+        fs::write(out, &bytes).unwrap_or_else(|err| fail(&format!("Couldn't write {}: {}", out, err)));
+        println!("{} bytes", bytes.len());
+    }
+}
+
+fn problem_command(command: &str, args: &[String]) {
+    let (moves, fen) = depth_and_fen(args);
+    let started = Instant::now();
+    let solution = match command {
+        "mate" => DirectMate::from_fen(&fen, moves).map(|problem| problem.to_string()),
+        "selfmate" => SelfMate::from_fen(&fen, moves).map(|problem| problem.to_string()),
+        _ => HelpMate::from_fen(&fen, moves).map(|problem| problem.to_string()),
+    };
+    print!("{}", solution.unwrap_or_else(|err| fail(&err.reason)));
+    eprintln!("{:.3}s", started.elapsed().as_secs_f64());
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|command| command.as_str()) {
+        Some("perft") => perft_command(&args[1..]),
+        Some("divide") => divide_command(&args[1..]),
+        Some("bench") => bench_command(&args[1..]),
+        Some("book") => book_command(&args[1..]),
+        Some("probe") => probe_command(&args[1..]),
+        Some("endgame") => endgame_command(&args[1..]),
+        Some(command @ ("mate" | "selfmate" | "helpmate")) => problem_command(command, &args[1..]),
+        _ => fail(USAGE),
+    }
+}
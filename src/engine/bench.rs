@@ -0,0 +1,88 @@
+// Node counts for fixed-depth searches of a set of positions. Node counts don't depend on the machine, so
+// they show exactly what each of the search options saves (and, run with the same options, that a change
+// to the search hasn't changed its behaviour).
+
+use super::search::*;
+use crate::game::fen;
+
+pub const BENCH_POSITIONS: [&str; 6] = [
+    fen::INITIAL_FEN,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r1bq1rk1/pp2bppp/2n2n2/2pp4/3P4/2PBPN2/PP1N1PPP/R2QK2R w KQ - 0 9",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BenchResult {
+    pub nodes: u64,
+    pub time_ms: u64,
+}
+
+// each position is searched by a new engine, so that results don't depend on the order they're searched in
+pub fn bench(options: SearchOptions, depth: u32, fens: &[&str]) -> BenchResult {
+    fens.iter().fold(BenchResult { nodes: 0, time_ms: 0 }, |total, fen| {
+        let board = fen::decode(fen).unwrap_or_else(|err| panic!("{}", err.reason));
+        let result = Engine::with_options(options).search(&board, &SearchLimits::depth(depth));
+        BenchResult {
+            nodes: total.nodes + result.nodes,
+            time_ms: total.time_ms + result.time_ms,
+        }
+    })
+}
+
+// the options switched on one at a time, starting from a plain alpha-beta search
+pub fn configurations() -> Vec<(&'static str, SearchOptions)> {
+    let plain = SearchOptions::plain();
+    let table = SearchOptions {
+        transposition_table: true,
+        hash_mb: SearchOptions::default().hash_mb,
+        ..plain
+    };
+    let mvv_lva = SearchOptions { mvv_lva: true, ..table };
+    let killers = SearchOptions {
+        killer_moves: true,
+        history_heuristic: true,
+        ..mvv_lva
+    };
+    let null_move = SearchOptions {
+        null_move_pruning: true,
+        ..killers
+    };
+    vec![
+        ("plain alpha-beta", plain),
+        ("+ transposition table", table),
+        ("+ MVV-LVA", mvv_lva),
+        ("+ killers and history", killers),
+        ("+ null move pruning", null_move),
+        ("+ late move reductions", SearchOptions::default()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn the_options_save_nodes() {
+        let fens = &BENCH_POSITIONS[..3];
+        let plain = bench(SearchOptions::plain(), 3, fens);
+        let all = bench(SearchOptions::default(), 3, fens);
+        assert!(all.nodes * 2 < plain.nodes, "{} vs {}", all.nodes, plain.nodes);
+    }
+
+    #[test]
+    fn node_counts_are_repeatable() {
+        let fens = &BENCH_POSITIONS[1..2];
+        assert_eq!(bench(SearchOptions::default(), 3, fens).nodes, bench(SearchOptions::default(), 3, fens).nodes);
+    }
+
+    #[test]
+    fn the_configurations_end_with_every_option() {
+        let configurations = configurations();
+        assert_eq!(configurations.first().unwrap().1, SearchOptions::plain());
+        assert_eq!(configurations.last().unwrap().1, SearchOptions::default());
+    }
+}
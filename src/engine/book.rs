@@ -0,0 +1,378 @@
+// Opening books in Polyglot's .bin format: 16 byte entries of position key, move, weight and a learning
+// value (all big-endian), sorted by key so a position's moves can be found by binary search.
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use super::polyglot::*;
+use crate::game::board::*;
+use crate::game::chess_move::*;
+use crate::game::color::Color;
+use crate::game::game::Game;
+use crate::game::moving::MoveError;
+use crate::game::notation::*;
+use crate::game::pgn::*;
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub struct BookError {
+    pub reason: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    // packed as described in polyglot.rs
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn read(bytes: &[u8]) -> BookEntry {
+        let mut key = [0; 8];
+        key.copy_from_slice(&bytes[0..8]);
+        BookEntry {
+            key: u64::from_be_bytes(key),
+            raw_move: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.key.to_be_bytes());
+        bytes.extend_from_slice(&self.raw_move.to_be_bytes());
+        bytes.extend_from_slice(&self.weight.to_be_bytes());
+        bytes.extend_from_slice(&self.learn.to_be_bytes());
+    }
+}
+
+// a book move for a particular position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookMove {
+    pub chess_move: Move,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+// how a book is built from games: only their first so many plies are kept, and only moves played in at
+// least so many of the games
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookOptions {
+    pub depth: usize,
+    pub min_games: u32,
+}
+
+impl Default for BookOptions {
+    fn default() -> BookOptions {
+        BookOptions { depth: 20, min_games: 1 }
+    }
+}
+
+#[derive(Default)]
+struct MoveTally {
+    games: u32,
+    // half points for the side making the move
+    score: u64,
+}
+
+// a game left out of a book because it couldn't be read or replayed, numbered from 1 in the order given
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkippedGame {
+    pub number: usize,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Book {
+        // the format only requires the keys in order; heavier moves first is the usual convention
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        Book { entries }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError {
+                reason: format!("A book is a whole number of {} byte entries, not {} bytes", ENTRY_SIZE, bytes.len()),
+            });
+        }
+        let entries: Vec<BookEntry> = bytes.chunks(ENTRY_SIZE).map(BookEntry::read).collect();
+        if entries.windows(2).any(|pair| pair[0].key > pair[1].key) {
+            return Err(BookError { reason: "The book's entries are not sorted by key".to_string() });
+        }
+        Ok(Book { entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in self.entries.iter() {
+            entry.write(&mut bytes);
+        }
+        bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[BookEntry] {
+        &self.entries
+    }
+
+    // the entries for the position with the key
+    pub fn entries_for(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    // the book's legal moves for the position, heaviest first
+    pub fn moves(&self, board: &Board) -> Vec<BookMove> {
+        let mut moves: Vec<BookMove> = self
+            .entries_for(polyglot_key(board))
+            .iter()
+            .filter_map(|entry| {
+                decode_move(board, entry.raw_move).map(|chess_move| BookMove {
+                    chess_move,
+                    weight: entry.weight,
+                    learn: entry.learn,
+                })
+            })
+            .collect();
+        moves.sort_by_key(|book_move| std::cmp::Reverse(book_move.weight));
+        moves
+    }
+
+    // A move chosen at random with probability in proportion to its weight, the roll being any random
+    // number. Moves of weight zero are never chosen; none is when the position isn't in the book.
+    pub fn choose(&self, board: &Board, roll: u64) -> Option<Move> {
+        let moves = self.moves(board);
+        let total: u64 = moves.iter().map(|book_move| u64::from(book_move.weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut remaining = roll % total;
+        for book_move in moves {
+            let weight = u64::from(book_move.weight);
+            if remaining < weight {
+                return Some(book_move.chess_move);
+            }
+            remaining -= weight;
+        }
+        None
+    }
+
+    // A book of the moves played in the games' openings, weighted two for each win and one for each draw
+    // by the side making them. Moves which never scored are left out, as are games without a decisive or
+    // drawn result. Games with illegal moves are skipped, and returned with the reason.
+    pub fn build(games: &[PgnGame], options: &BookOptions) -> (Book, Vec<SkippedGame>) {
+        Book::build_each(games.iter().map(Ok), options)
+    }
+
+    // a book of the games in the PGN text; those which can't be read are skipped like illegal ones
+    pub fn build_from_pgn(text: &str, options: &BookOptions) -> (Book, Vec<SkippedGame>) {
+        let games = parse_each_game(text);
+        Book::build_each(games.iter().map(|game| game.as_ref().map_err(|err| err.reason.clone())), options)
+    }
+
+    fn build_each<'a>(
+        games: impl Iterator<Item = Result<&'a PgnGame, String>>,
+        options: &BookOptions,
+    ) -> (Book, Vec<SkippedGame>) {
+        let mut tallies: HashMap<(u64, u16), MoveTally> = HashMap::new();
+        let mut skipped = vec![];
+        for (index, game) in games.enumerate() {
+            let replayed = game.and_then(|game| game.replay().map(|replay| (game, replay)).map_err(|err| err.reason));
+            let (game, (mut board, moves)) = match replayed {
+                Ok(replayed) => replayed,
+                Err(reason) => {
+                    skipped.push(SkippedGame { number: index + 1, reason });
+                    continue;
+                }
+            };
+            let white_score = match game.outcome() {
+                Some("1-0") => 2,
+                Some("1/2-1/2") => 1,
+                Some("0-1") => 0,
+                _ => continue,
+            };
+            for chess_move in moves.into_iter().take(options.depth) {
+                let score = if board.side_to_move() == Color::White {
+                    white_score
+                } else {
+                    2 - white_score
+                };
+                let tally = tallies.entry((polyglot_key(&board), encode_move(&board, &chess_move))).or_default();
+                tally.games += 1;
+                tally.score += score;
+                board.make_move(chess_move);
+            }
+        }
+        tallies.retain(|_, tally| tally.games >= options.min_games && tally.score > 0);
+        // weights are scaled down, never below one, when the most played move would overflow them
+        let heaviest = tallies.values().map(|tally| tally.score).max().unwrap_or(0);
+        let scale = |score: u64| {
+            if heaviest <= u64::from(u16::MAX) {
+                score as u16
+            } else {
+                (score * u64::from(u16::MAX) / heaviest).max(1) as u16
+            }
+        };
+        let entries = tallies
+            .into_iter()
+            .map(|((key, raw_move), tally)| BookEntry { key, raw_move, weight: scale(tally.score), learn: 0 })
+            .collect();
+        (Book::from_entries(entries), skipped)
+    }
+}
+
+// A book move as offered to JavaScript
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningMove {
+    pub san: String,
+    pub uci: String,
+    pub weight: u16,
+}
+
+// An opening book for the web, read from the bytes of a .bin file
+#[wasm_bindgen(js_name = OpeningBook)]
+pub struct OpeningBook {
+    book: Book,
+}
+
+#[wasm_bindgen(js_class = OpeningBook)]
+impl OpeningBook {
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<OpeningBook, MoveError> {
+        let book = Book::from_bytes(bytes).map_err(|err| MoveError { reason: err.reason })?;
+        Ok(OpeningBook { book })
+    }
+
+    // the book's moves for the game's current position, heaviest first
+    pub fn moves(&self, game: &Game) -> Result<Vec<OpeningMove>, MoveError> {
+        let board = game.board()?;
+        Ok(self
+            .book
+            .moves(&board)
+            .iter()
+            .map(|book_move| OpeningMove {
+                san: encode(&board, &book_move.chess_move),
+                uci: encode_uci(&board, &book_move.chess_move, UciCastling::KingMove),
+                weight: book_move.weight,
+            })
+            .collect())
+    }
+
+    // a book move in SAN chosen in proportion to the weights, given a random number in [0, 1)
+    pub fn choose(&self, game: &Game, random: f64) -> Result<Option<String>, MoveError> {
+        let board = game.board()?;
+        let roll = (random.clamp(0.0, 1.0) * f64::from(u32::MAX)) as u64;
+        Ok(self.book.choose(&board, roll).map(|chess_move| encode(&board, &chess_move)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn entry(board: &Board, uci: &str, weight: u16) -> BookEntry {
+        let chess_move = resolve_uci(board, uci).unwrap();
+        BookEntry { key: polyglot_key(board), raw_move: encode_move(board, &chess_move), weight, learn: 0 }
+    }
+
+    fn start_book() -> Book {
+        let board = Board::new();
+        Book::from_entries(vec![entry(&board, "d2d4", 1), entry(&board, "e2e4", 3), entry(&board, "g1f3", 0)])
+    }
+
+    #[test]
+    fn entries_are_written_big_endian() {
+        let entry = BookEntry { key: 0x0102030405060708, raw_move: 0x090a, weight: 0x0b0c, learn: 0x0d0e0f10 };
+        let book = Book::from_entries(vec![entry]);
+        let bytes = book.to_bytes();
+        assert_eq!(bytes, (1..=16).collect::<Vec<u8>>());
+        assert_eq!(Book::from_bytes(&bytes).unwrap(), book);
+    }
+
+    #[test]
+    fn malformed_books_are_rejected() {
+        assert!(Book::from_bytes(&[0; 15]).is_err());
+        let mut bytes = vec![0; 32];
+        bytes[0] = 1;
+        assert!(Book::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn moves_are_looked_up_by_position() {
+        let book = start_book();
+        let moves: Vec<(String, u16)> =
+            book.moves(&Board::new()).iter().map(|m| (m.chess_move.to_string(), m.weight)).collect();
+        assert_eq!(moves, [("e2-e4".to_string(), 3), ("d2-d4".to_string(), 1), ("g1-f3".to_string(), 0)]);
+        let mut board = Board::new();
+        board.make_move(resolve_uci(&board, "e2e4").unwrap());
+        assert!(book.moves(&board).is_empty());
+    }
+
+    #[test]
+    fn choices_follow_the_weights() {
+        let book = start_book();
+        let board = Board::new();
+        let chosen: Vec<String> = (0..4).map(|roll| book.choose(&board, roll).unwrap().to_string()).collect();
+        assert_eq!(chosen, ["e2-e4", "e2-e4", "e2-e4", "d2-d4"]);
+        let mut board = Board::new();
+        board.make_move(resolve_uci(&board, "e2e4").unwrap());
+        assert_eq!(book.choose(&board, 0), None);
+    }
+
+    #[test]
+    fn books_are_built_from_games() {
+        let pgn = "1. e4 e5 2. Nf3 1-0\n1. e4 c5 1/2-1/2\n1. d4 d5 0-1\n1. c4 *";
+        let (book, skipped) = Book::build_from_pgn(pgn, &BookOptions { depth: 2, min_games: 1 });
+        assert!(skipped.is_empty());
+        let weights = |board: &Board| -> Vec<(String, u16)> {
+            book.moves(board).iter().map(|m| (encode(board, &m.chess_move), m.weight)).collect()
+        };
+        // d4 lost and c4 has no result
+        let mut board = Board::new();
+        assert_eq!(weights(&board), [("e4".to_string(), 3)]);
+        board.make_move(resolve_uci(&board, "e2e4").unwrap());
+        assert_eq!(weights(&board), [("c5".to_string(), 1)]);
+        // Nf3 is beyond the depth
+        board.make_move(resolve_uci(&board, "e7e5").unwrap());
+        assert!(weights(&board).is_empty());
+        let (book, _) = Book::build_from_pgn(pgn, &BookOptions { depth: 2, min_games: 2 });
+        assert_eq!(book.len(), 1);
+    }
+
+    #[test]
+    fn games_which_cant_be_replayed_are_skipped() {
+        let pgn = "1. e4 e5 1-0\n1. e4 e5 2. Ke3 1-0\n[Event \"Broken]\n1. d4 1-0\n[Event \"Fine\"]\n1. e4 c5 1-0";
+        let (book, skipped) = Book::build_from_pgn(pgn, &BookOptions { depth: 2, min_games: 1 });
+        assert_eq!(skipped.iter().map(|game| game.number).collect::<Vec<_>>(), [2, 3]);
+        assert!(skipped[0].reason.ends_with("at ply 3: Ke3"), "{}", skipped[0].reason);
+        assert_eq!(skipped[1].reason, "Invalid tag: [Event \"Broken]");
+        assert_eq!(book.moves(&Board::new())[0].weight, 4);
+    }
+
+    #[test]
+    fn the_web_book_offers_san() {
+        let book = OpeningBook::new(&start_book().to_bytes()).unwrap();
+        let moves = book.moves(&Game::new()).unwrap();
+        assert_eq!(moves[0], OpeningMove { san: "e4".to_string(), uci: "e2e4".to_string(), weight: 3 });
+        assert_eq!(book.choose(&Game::new(), 0.0).unwrap(), Some("e4".to_string()));
+    }
+}
@@ -0,0 +1,37 @@
+// std::time::Instant panics on wasm32-unknown-unknown, so in the browser the time is read from JavaScript's
+// Date instead.
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Stopwatch {
+    started: f64,
+}
+
+impl Default for Stopwatch {
+    fn default() -> Stopwatch {
+        Stopwatch::start()
+    }
+}
+
+impl Stopwatch {
+    pub fn start() -> Stopwatch {
+        Stopwatch { started: now_ms() }
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        (now_ms() - self.started).max(0.0) as u64
+    }
+}
@@ -0,0 +1,556 @@
+// How good a position is, in centipawns, without searching any further. Each term is scored separately for
+// the middlegame and the endgame and the two are blended by how much material is left (the game phase), so
+// that, say, the king is kept safe while the queens are on but marched to the centre once they're off.
+//
+// Both sides are scored by the same code looking at the board from their own side, so a position and its
+// colour-reversed mirror image always evaluate to opposite scores.
+
+use std::ops::{Add, AddAssign, Neg, Sub};
+
+use wasm_bindgen::prelude::*;
+
+use crate::game::attacks::*;
+use crate::game::bitboard::*;
+use crate::game::board::*;
+use crate::game::color::*;
+use crate::game::piece_type::*;
+use crate::game::square::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TaperedScore {
+    pub middlegame: i32,
+    pub endgame: i32,
+}
+
+const fn tapered(middlegame: i32, endgame: i32) -> TaperedScore {
+    TaperedScore { middlegame, endgame }
+}
+
+impl TaperedScore {
+    // phase runs from 0 (bare kings and pawns) to MAX_PHASE (all the pieces on)
+    pub fn blend(self, phase: i32) -> i32 {
+        (self.middlegame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+
+    fn times(self, count: i32) -> TaperedScore {
+        tapered(self.middlegame * count, self.endgame * count)
+    }
+}
+
+impl Add for TaperedScore {
+    type Output = TaperedScore;
+
+    fn add(self, other: TaperedScore) -> TaperedScore {
+        tapered(self.middlegame + other.middlegame, self.endgame + other.endgame)
+    }
+}
+
+impl AddAssign for TaperedScore {
+    fn add_assign(&mut self, other: TaperedScore) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = TaperedScore;
+
+    fn sub(self, other: TaperedScore) -> TaperedScore {
+        tapered(self.middlegame - other.middlegame, self.endgame - other.endgame)
+    }
+}
+
+impl Neg for TaperedScore {
+    type Output = TaperedScore;
+
+    fn neg(self) -> TaperedScore {
+        tapered(-self.middlegame, -self.endgame)
+    }
+}
+
+pub const MAX_PHASE: i32 = 24;
+
+fn phase_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Knight | PieceType::Bishop => 1,
+        PieceType::Rook => 2,
+        PieceType::Queen => 4,
+        _ => 0,
+    }
+}
+
+// the plain material value, as used for ordering captures
+pub fn material_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King | PieceType::Empty => 0,
+    }
+}
+
+fn tapered_material(piece_type: PieceType) -> TaperedScore {
+    match piece_type {
+        PieceType::Pawn => tapered(82, 94),
+        PieceType::Knight => tapered(337, 281),
+        PieceType::Bishop => tapered(365, 297),
+        PieceType::Rook => tapered(477, 512),
+        PieceType::Queen => tapered(1025, 936),
+        PieceType::King | PieceType::Empty => tapered(0, 0),
+    }
+}
+
+// Piece-square tables are laid out as a board is printed, from White's side: the first row is the eighth
+// rank. The same table serves Black by reading it upside down.
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const PAWN_ENDGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    60, 60, 60, 60, 60, 60, 60, 60,
+    35, 35, 35, 35, 35, 35, 35, 35,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50,-40,-30,-30,-30,-30,-40,-50,
+   -40,-20,  0,  0,  0,  0,-20,-40,
+   -30,  0, 10, 15, 15, 10,  0,-30,
+   -30,  5, 15, 20, 20, 15,  5,-30,
+   -30,  0, 15, 20, 20, 15,  0,-30,
+   -30,  5, 10, 15, 15, 10,  5,-30,
+   -40,-20,  0,  5,  5,  0,-20,-40,
+   -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20,-10,-10,-10,-10,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  5,  5, 10, 10,  5,  5,-10,
+   -10,  0, 10, 10, 10, 10,  0,-10,
+   -10, 10, 10, 10, 10, 10, 10,-10,
+   -10,  5,  0,  0,  0,  0,  5,-10,
+   -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20,-10,-10, -5, -5,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+    -5,  0,  5,  5,  5,  5,  0, -5,
+     0,  0,  5,  5,  5,  5,  0, -5,
+   -10,  5,  5,  5,  5,  5,  0,-10,
+   -10,  0,  5,  0,  0,  0,  0,-10,
+   -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -20,-30,-30,-40,-40,-30,-30,-20,
+   -10,-20,-20,-20,-20,-20,-20,-10,
+    20, 20,  0,  0,  0,  0, 20, 20,
+    20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+   -50,-40,-30,-20,-20,-30,-40,-50,
+   -30,-20,-10,  0,  0,-10,-20,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-30,  0,  0,  0,  0,-30,-30,
+   -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+fn piece_square(piece_type: PieceType, color: Color, square: Square) -> TaperedScore {
+    let index = match color {
+        Color::White => square.flip().index(),
+        Color::Black => square.index(),
+    } as usize;
+    let (middlegame, endgame) = match piece_type {
+        PieceType::Pawn => (&PAWN_MIDDLEGAME, &PAWN_ENDGAME),
+        PieceType::Knight => (&KNIGHT, &KNIGHT),
+        PieceType::Bishop => (&BISHOP, &BISHOP),
+        PieceType::Rook => (&ROOK, &ROOK),
+        PieceType::Queen => (&QUEEN, &QUEEN),
+        PieceType::King => (&KING_MIDDLEGAME, &KING_ENDGAME),
+        PieceType::Empty => return TaperedScore::default(),
+    };
+    tapered(middlegame[index], endgame[index])
+}
+
+// per square a piece can move to, other than those held by its own side or attacked by enemy pawns
+fn mobility_weight(piece_type: PieceType) -> TaperedScore {
+    match piece_type {
+        PieceType::Knight => tapered(4, 4),
+        PieceType::Bishop => tapered(5, 5),
+        PieceType::Rook => tapered(2, 4),
+        PieceType::Queen => tapered(1, 2),
+        _ => TaperedScore::default(),
+    }
+}
+
+// per own pawn sheltering the king, one and two ranks in front of it
+const PAWN_SHIELD: [TaperedScore; 2] = [tapered(12, 0), tapered(6, 0)];
+// per square next to the king attacked by an enemy piece of the type
+fn king_attack_weight(piece_type: PieceType) -> TaperedScore {
+    match piece_type {
+        PieceType::Knight | PieceType::Bishop => tapered(-10, 0),
+        PieceType::Rook => tapered(-15, -5),
+        PieceType::Queen => tapered(-25, -5),
+        _ => TaperedScore::default(),
+    }
+}
+
+// by rank, from the pawn's own side
+const PASSED_PAWN: [TaperedScore; 8] = [
+    tapered(0, 0),
+    tapered(5, 10),
+    tapered(10, 20),
+    tapered(15, 35),
+    tapered(25, 60),
+    tapered(40, 100),
+    tapered(60, 150),
+    tapered(0, 0),
+];
+const ISOLATED_PAWN: TaperedScore = tapered(-10, -15);
+const DOUBLED_PAWN: TaperedScore = tapered(-10, -20);
+const BISHOP_PAIR: TaperedScore = tapered(30, 50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Term {
+    Material = 0,
+    PieceSquares = 1,
+    Mobility = 2,
+    KingSafety = 3,
+    PassedPawns = 4,
+    IsolatedPawns = 5,
+    DoubledPawns = 6,
+    BishopPair = 7,
+}
+
+pub const TERMS: [Term; 8] = [
+    Term::Material,
+    Term::PieceSquares,
+    Term::Mobility,
+    Term::KingSafety,
+    Term::PassedPawns,
+    Term::IsolatedPawns,
+    Term::DoubledPawns,
+    Term::BishopPair,
+];
+
+impl Term {
+    pub fn name(self) -> &'static str {
+        match self {
+            Term::Material => "material",
+            Term::PieceSquares => "piece-square tables",
+            Term::Mobility => "mobility",
+            Term::KingSafety => "king safety",
+            Term::PassedPawns => "passed pawns",
+            Term::IsolatedPawns => "isolated pawns",
+            Term::DoubledPawns => "doubled pawns",
+            Term::BishopPair => "bishop pair",
+        }
+    }
+}
+
+const MAJOR_AND_MINOR: [PieceType; 4] = [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen];
+
+fn adjacent_files(file: File) -> Bitboard {
+    [file.offset(-1), file.offset(1)]
+        .iter()
+        .flatten()
+        .fold(Bitboard::EMPTY, |files, file| files | Bitboard::file(*file))
+}
+
+// the squares in front of the square on its own and the adjacent files, from the colour's side
+fn front_span(color: Color, square: Square) -> Bitboard {
+    let files = Bitboard::file(square.file()) | adjacent_files(square.file());
+    let ahead = Rank::all()
+        .filter(|rank| rank.relative_to(color).index() > square.rank().relative_to(color).index())
+        .fold(Bitboard::EMPTY, |ranks, rank| ranks | Bitboard::rank(rank));
+    files & ahead
+}
+
+fn pawn_attacks_of(board: &Board, color: Color) -> Bitboard {
+    board
+        .pieces_of(color, PieceType::Pawn)
+        .squares()
+        .fold(Bitboard::EMPTY, |attacked, square| attacked | pawn_attacks(color, square))
+}
+
+// the terms for one side, scored from its own point of view
+fn evaluate_side(board: &Board, color: Color) -> [TaperedScore; 8] {
+    let mut terms = [TaperedScore::default(); 8];
+    let them = !color;
+    let occupied = board.occupied();
+    let own_pawns = board.pieces_of(color, PieceType::Pawn);
+    let their_pawns = board.pieces_of(them, PieceType::Pawn);
+
+    for square in board.color(color).squares() {
+        let piece_type = board.piece_type_at(square);
+        terms[Term::Material as usize] += tapered_material(piece_type);
+        terms[Term::PieceSquares as usize] += piece_square(piece_type, color, square);
+    }
+
+    let safe = !board.color(color) & !pawn_attacks_of(board, them);
+    for piece_type in MAJOR_AND_MINOR {
+        for square in board.pieces_of(color, piece_type).squares() {
+            let moves = (piece_attacks(piece_type, color, square, occupied) & safe).count() as i32;
+            terms[Term::Mobility as usize] += mobility_weight(piece_type).times(moves);
+        }
+    }
+
+    if let Some(king) = board.king_square(color) {
+        let forward = if color.is_white() { 1 } else { -1 };
+        for (distance, weight) in PAWN_SHIELD.iter().enumerate() {
+            let shelter = [-1, 0, 1]
+                .iter()
+                .filter_map(|file_delta| king.offset(*file_delta, forward * (distance as i8 + 1)))
+                .filter(|square| own_pawns.contains(*square))
+                .count() as i32;
+            terms[Term::KingSafety as usize] += weight.times(shelter);
+        }
+        let zone = king_attacks(king).with(king);
+        for piece_type in MAJOR_AND_MINOR {
+            for square in board.pieces_of(them, piece_type).squares() {
+                let attacked = (piece_attacks(piece_type, them, square, occupied) & zone).count() as i32;
+                terms[Term::KingSafety as usize] += king_attack_weight(piece_type).times(attacked);
+            }
+        }
+    }
+
+    for square in own_pawns.squares() {
+        if (front_span(color, square) & their_pawns).is_empty() {
+            terms[Term::PassedPawns as usize] += PASSED_PAWN[square.rank().relative_to(color).index() as usize];
+        }
+        if (adjacent_files(square.file()) & own_pawns).is_empty() {
+            terms[Term::IsolatedPawns as usize] += ISOLATED_PAWN;
+        }
+    }
+    for file in File::all() {
+        let on_file = (Bitboard::file(file) & own_pawns).count() as i32;
+        if on_file > 1 {
+            terms[Term::DoubledPawns as usize] += DOUBLED_PAWN.times(on_file - 1);
+        }
+    }
+
+    if board.pieces_of(color, PieceType::Bishop).count() >= 2 {
+        terms[Term::BishopPair as usize] = BISHOP_PAIR;
+    }
+    terms
+}
+
+fn phase(board: &Board) -> i32 {
+    MAJOR_AND_MINOR
+        .iter()
+        .map(|piece_type| board.pieces(*piece_type).count() as i32 * phase_weight(*piece_type))
+        .sum::<i32>()
+        .min(MAX_PHASE)
+}
+
+// from the point of view of the side to move, as the search wants it
+pub fn evaluate(board: &Board) -> i32 {
+    let us = evaluate_side(board, board.side_to_move());
+    let them = evaluate_side(board, !board.side_to_move());
+    let total = us
+        .iter()
+        .zip(them.iter())
+        .fold(TaperedScore::default(), |total, (ours, theirs)| total + *ours - *theirs);
+    total.blend(phase(board))
+}
+
+// one term of an evaluation, blended for the game phase; positive favours White
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvaluationTerm {
+    pub name: String,
+    pub white: i32,
+    pub black: i32,
+    pub score: i32,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+    // positive favours White
+    pub score: i32,
+    // from 0 (pawn endgame) to 24 (all the pieces on the board)
+    pub phase: i32,
+    pub terms: Vec<EvaluationTerm>,
+}
+
+// the evaluation from White's point of view, broken down term by term
+pub fn explain(board: &Board) -> Evaluation {
+    let phase = phase(board);
+    let white = evaluate_side(board, Color::White);
+    let black = evaluate_side(board, Color::Black);
+    let terms: Vec<EvaluationTerm> = TERMS
+        .iter()
+        .map(|term| {
+            let index = *term as usize;
+            EvaluationTerm {
+                name: term.name().to_string(),
+                white: white[index].blend(phase),
+                black: black[index].blend(phase),
+                score: (white[index] - black[index]).blend(phase),
+            }
+        })
+        .collect();
+    let score = white
+        .iter()
+        .zip(black.iter())
+        .fold(TaperedScore::default(), |total, (white, black)| total + *white - *black)
+        .blend(phase);
+    Evaluation { score, phase, terms }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::fen::decode;
+    use crate::game::position::Position;
+
+    fn board(fen: &str) -> Board {
+        decode(fen).unwrap_or_else(|err| panic!("{}", err.reason))
+    }
+
+    // the same position with the colours swapped and the board turned round
+    fn mirror(board: &Board) -> Board {
+        let positions: Vec<Position> = board
+            .to_positions()
+            .into_iter()
+            .map(|position| Position {
+                square: position.square.flip(),
+                piece_type: position.piece_type,
+                color: !position.color,
+            })
+            .collect();
+        Board::from_parts(
+            &positions,
+            !board.side_to_move(),
+            CastlingRights::NONE,
+            board.en_passant().map(Square::flip),
+            board.halfmove_clock(),
+            board.fullmove_number(),
+        )
+    }
+
+    fn term(evaluation: &Evaluation, name: &str) -> i32 {
+        evaluation.terms.iter().find(|term| term.name == name).unwrap().score
+    }
+
+    #[test]
+    fn the_initial_position_is_level() {
+        assert_eq!(evaluate(&Board::new()), 0);
+        assert!(explain(&Board::new()).terms.iter().all(|term| term.score == 0));
+        assert_eq!(explain(&Board::new()).phase, MAX_PHASE);
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "6k1/5ppp/8/3P4/8/2B5/PPP5/1K6 b - - 0 1",
+        ] {
+            let original = board(fen);
+            let mirrored = mirror(&original);
+            assert_eq!(evaluate(&original), evaluate(&mirrored), "{}", fen);
+            assert_eq!(explain(&original).score, -explain(&mirrored).score, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn the_side_to_move_sees_its_own_advantage() {
+        let white = board("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        let black = board("4k3/8/8/8/8/8/8/R3K3 b - - 0 1");
+        assert!(evaluate(&white) > 400);
+        assert_eq!(evaluate(&black), -evaluate(&white));
+    }
+
+    #[test]
+    fn the_terms_add_up_to_the_score() {
+        let evaluation = explain(&board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"));
+        let sum: i32 = evaluation.terms.iter().map(|term| term.score).sum();
+        // each term is rounded on its own
+        assert!((sum - evaluation.score).abs() <= evaluation.terms.len() as i32);
+    }
+
+    #[test]
+    fn pawn_structure_is_scored() {
+        // White's doubled, isolated c-pawns against Black's connected passed pawns
+        let evaluation = explain(&board("4k3/8/8/8/8/2P5/2P2pp1/4K3 w - - 0 1"));
+        assert!(term(&evaluation, "doubled pawns") < 0);
+        assert!(term(&evaluation, "isolated pawns") < 0);
+        assert!(term(&evaluation, "passed pawns") < 0);
+    }
+
+    #[test]
+    fn a_passed_pawn_is_worth_more_further_up_the_board() {
+        let near = explain(&board("4k3/8/8/8/8/P7/8/4K3 w - - 0 1"));
+        let far = explain(&board("4k3/P7/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(term(&far, "passed pawns") > term(&near, "passed pawns"));
+        assert!(term(&near, "passed pawns") > 0);
+    }
+
+    #[test]
+    fn the_bishop_pair_is_rewarded() {
+        let evaluation = explain(&board("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"));
+        assert!(term(&evaluation, "bishop pair") > 0);
+    }
+
+    #[test]
+    fn an_exposed_king_is_less_safe() {
+        let sheltered = explain(&board("rn1qk2r/ppp2ppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w kq - 0 1"));
+        let exposed = explain(&board("rn1qk2r/ppp2ppp/8/8/8/8/PPPPP3/RNBQ1RK1 w kq - 0 1"));
+        assert!(term(&sheltered, "king safety") > term(&exposed, "king safety"));
+    }
+
+    #[test]
+    fn pieces_with_more_moves_score_higher_mobility() {
+        let centre = explain(&board("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1"));
+        let corner = explain(&board("4k3/8/8/8/8/8/8/N3K3 w - - 0 1"));
+        assert!(term(&centre, "mobility") > term(&corner, "mobility"));
+    }
+}
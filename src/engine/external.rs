@@ -0,0 +1,457 @@
+// Drives another engine over UCI, so that positions can be analysed by any engine installed locally
+// rather than only by narmi's own search. The engine runs as a child process; its output is read on a
+// thread of its own so that waiting on a hung engine can time out.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::search::SearchLimits;
+use crate::game::board::*;
+use crate::game::chess_move::*;
+use crate::game::fen;
+use crate::game::game::Game;
+use crate::game::moving::replay_moves;
+use crate::game::notation::*;
+
+// how long past any time limit an engine is given to answer before it's told to stop, and then again
+// before giving up on it
+const DEFAULT_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct ExternalEngineError {
+    pub reason: String,
+}
+
+fn failed(reason: String) -> ExternalEngineError {
+    ExternalEngineError { reason }
+}
+
+// a score from the point of view of the side to move, as UCI gives it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    // mate in so many moves, negative when the side to move is the one being mated
+    Mate(i32),
+}
+
+// one `info` line, with whatever the engine chose to say
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UciInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    // the score is only a bound on the real one when the search failed high or low
+    pub bound: bool,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time_ms: Option<u64>,
+    pub hashfull: Option<u32>,
+    pub pv: Vec<String>,
+    pub string: Option<String>,
+}
+
+// Parses an `info` line. Fields are read by name and anything unknown is skipped, as engines add their own;
+// `string` takes the rest of the line and `pv` every move after it.
+pub fn parse_info(line: &str) -> Option<UciInfo> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("info") {
+        return None;
+    }
+    let mut info = UciInfo::default();
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => info.depth = words.next().and_then(|value| value.parse().ok()),
+            "seldepth" => info.seldepth = words.next().and_then(|value| value.parse().ok()),
+            "multipv" => info.multipv = words.next().and_then(|value| value.parse().ok()),
+            "nodes" => info.nodes = words.next().and_then(|value| value.parse().ok()),
+            "nps" => info.nps = words.next().and_then(|value| value.parse().ok()),
+            "time" => info.time_ms = words.next().and_then(|value| value.parse().ok()),
+            "hashfull" => info.hashfull = words.next().and_then(|value| value.parse().ok()),
+            "score" => {
+                let kind = words.next();
+                let value = words.next().and_then(|value| value.parse().ok());
+                info.score = match (kind, value) {
+                    (Some("cp"), Some(value)) => Some(Score::Centipawns(value)),
+                    (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                    _ => None,
+                };
+            }
+            "lowerbound" | "upperbound" => info.bound = true,
+            "pv" => {
+                info.pv = words.by_ref().map(|text| text.to_string()).collect();
+            }
+            "string" => {
+                info.string = Some(words.by_ref().collect::<Vec<&str>>().join(" "));
+            }
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+// parses `bestmove <move> [ponder <move>]` into the moves as written
+pub fn parse_bestmove(line: &str) -> Option<(String, Option<String>)> {
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("bestmove"), Some(best), Some("ponder"), Some(ponder)) => {
+            Some((best.to_string(), Some(ponder.to_string())))
+        }
+        (Some("bestmove"), Some(best), _, _) => Some((best.to_string(), None)),
+        _ => None,
+    }
+}
+
+// The `position` command for the board, giving the moves played to reach it (as far as the board
+// remembers them) so that the engine knows about repetitions.
+pub fn position_command(board: &Board) -> String {
+    let mut root = board.clone();
+    let mut moves = vec![];
+    while let Some(chess_move) = root.unmake_move() {
+        moves.push(chess_move);
+    }
+    moves.reverse();
+    let root_fen = fen::encode(&root);
+    let mut command = if root_fen == fen::INITIAL_FEN {
+        "position startpos".to_string()
+    } else {
+        format!("position fen {}", root_fen)
+    };
+    if !moves.is_empty() {
+        command.push_str(" moves ");
+        command.push_str(&encode_uci_line(&root, &moves, UciCastling::KingMove).join(" "));
+    }
+    command
+}
+
+fn go_command(limits: &SearchLimits) -> String {
+    let mut command = "go".to_string();
+    if let Some(depth) = limits.depth {
+        command.push_str(&format!(" depth {}", depth));
+    }
+    if let Some(nodes) = limits.nodes {
+        command.push_str(&format!(" nodes {}", nodes));
+    }
+    if let Some(movetime) = limits.movetime_ms {
+        command.push_str(&format!(" movetime {}", movetime));
+    }
+    command
+}
+
+// The engine's verdict on a position. The score, depth and moves are those of the last `info` line for the
+// main line (the first, when the engine gives several).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    // None when the side to move has no moves
+    pub best_move: Option<Move>,
+    pub ponder: Option<Move>,
+    pub score: Option<Score>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    // the legal moves of the principal variation, which stops early should the engine give an illegal one
+    pub pv: Vec<Move>,
+    pub san_pv: Vec<String>,
+    pub infos: Vec<UciInfo>,
+}
+
+impl Analysis {
+    pub fn mate_in(&self) -> Option<i32> {
+        match self.score {
+            Some(Score::Mate(moves)) => Some(moves),
+            _ => None,
+        }
+    }
+
+    // messages the engine sent along the way with `info string`
+    pub fn strings(&self) -> Vec<&str> {
+        self.infos.iter().filter_map(|info| info.string.as_deref()).collect()
+    }
+}
+
+fn legal_prefix(board: &Board, texts: &[String]) -> Vec<Move> {
+    let mut board = board.clone();
+    let mut moves = vec![];
+    for text in texts {
+        match resolve_uci(&board, text) {
+            Ok(chess_move) => {
+                board.make_move(chess_move);
+                moves.push(chess_move);
+            }
+            Err(_) => break,
+        }
+    }
+    moves
+}
+
+pub struct ExternalEngine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    name: Option<String>,
+    author: Option<String>,
+    options: Vec<String>,
+    grace: Duration,
+}
+
+impl ExternalEngine {
+    // starts the engine and waits for it to finish the UCI handshake
+    pub fn spawn(program: &str, args: &[&str]) -> Result<ExternalEngine, ExternalEngineError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| failed(format!("Couldn't start {}: {}", program, err)))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        let mut engine = ExternalEngine {
+            child,
+            stdin,
+            lines,
+            name: None,
+            author: None,
+            options: vec![],
+            grace: DEFAULT_GRACE,
+        };
+        engine.send("uci")?;
+        let deadline = Instant::now() + engine.grace;
+        loop {
+            let line = engine.receive(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = Some(name.to_string());
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                engine.author = Some(author.to_string());
+            } else if let Some(option) = line.strip_prefix("option name ") {
+                engine.options.push(option.to_string());
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.is_ready()?;
+        Ok(engine)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    // the options the engine offered, as declared after "option name", e.g. "Hash type spin default 16 min 1 max 1024"
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    // how much longer than asked the engine may take over a search, and then to answer `stop`
+    pub fn set_grace(&mut self, grace: Duration) {
+        self.grace = grace;
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), ExternalEngineError> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| failed(format!("Couldn't write to the engine: {}", err)))
+    }
+
+    fn receive(&self, deadline: Instant) -> Result<String, ExternalEngineError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.lines.recv_timeout(timeout).map_err(|err| match err {
+            RecvTimeoutError::Timeout => failed("The engine didn't answer in time".to_string()),
+            RecvTimeoutError::Disconnected => failed("The engine has exited".to_string()),
+        })
+    }
+
+    pub fn is_ready(&mut self) -> Result<(), ExternalEngineError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + self.grace;
+        while self.receive(deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), ExternalEngineError> {
+        match value {
+            Some(value) => self.send(&format!("setoption name {} value {}", name, value))?,
+            None => self.send(&format!("setoption name {}", name))?,
+        }
+        self.is_ready()
+    }
+
+    pub fn new_game(&mut self) -> Result<(), ExternalEngineError> {
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    // Searches the position within the limits, at least one of which must be given. An engine which takes
+    // too long is told to stop, and its best move so far taken.
+    pub fn analyse(&mut self, board: &Board, limits: &SearchLimits) -> Result<Analysis, ExternalEngineError> {
+        if limits.depth.is_none() && limits.nodes.is_none() && limits.movetime_ms.is_none() {
+            return Err(failed("A search needs a depth, node or time limit".to_string()));
+        }
+        // anything left over from before belongs to an earlier command
+        while self.lines.try_recv().is_ok() {}
+        self.send(&position_command(board))?;
+        self.send(&go_command(limits))?;
+
+        let mut deadline = Instant::now() + Duration::from_millis(limits.movetime_ms.unwrap_or(0)) + self.grace;
+        let mut stopped = false;
+        let mut infos = vec![];
+        let (best, ponder) = loop {
+            let line = match self.receive(deadline) {
+                Err(_) if !stopped => {
+                    self.send("stop")?;
+                    stopped = true;
+                    deadline = Instant::now() + self.grace;
+                    continue;
+                }
+                result => result?,
+            };
+            if let Some(info) = parse_info(&line) {
+                infos.push(info);
+            } else if let Some(best) = parse_bestmove(&line) {
+                break best;
+            }
+        };
+
+        let best_move = match best.as_str() {
+            "0000" | "(none)" => None,
+            text => Some(
+                resolve_uci(board, text).map_err(|_| failed(format!("The engine chose an illegal move: {}", text)))?,
+            ),
+        };
+        let main_line = infos
+            .iter()
+            .rev()
+            .find(|info| info.multipv.unwrap_or(1) == 1 && (info.score.is_some() || !info.pv.is_empty()));
+        let pv = main_line.map_or(vec![], |info| legal_prefix(board, &info.pv));
+        let ponder = match (best_move, ponder) {
+            (Some(best_move), Some(ponder)) => {
+                let mut after = board.clone();
+                after.make_move(best_move);
+                resolve_uci(&after, &ponder).ok()
+            }
+            _ => None,
+        };
+        Ok(Analysis {
+            best_move,
+            ponder,
+            score: main_line.and_then(|info| info.score),
+            depth: main_line.and_then(|info| info.depth),
+            nodes: main_line.and_then(|info| info.nodes),
+            san_pv: encode_line(board, &pv),
+            pv,
+            infos,
+        })
+    }
+
+    pub fn analyse_game(&mut self, game: &Game, limits: &SearchLimits) -> Result<Analysis, ExternalEngineError> {
+        // replayed rather than the game's board, which only remembers the moves since the last capture or pawn
+        // move, so the engine is given the game from the start
+        let board = replay_moves(&game.moves()).map_err(|err| failed(err.reason))?;
+        self.analyse(&board, limits)
+    }
+
+    // asks the engine to quit, ending it if it doesn't
+    pub fn quit(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            self.shut_down();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parses_info() {
+        let info = parse_info(
+            "info depth 12 seldepth 18 multipv 1 score cp -35 upperbound nodes 123456 nps 987654 hashfull 42 \
+             tbhits 0 time 125 pv e7e5 g1f3 b8c6",
+        )
+        .unwrap();
+        assert_eq!(info.depth, Some(12));
+        assert_eq!(info.seldepth, Some(18));
+        assert_eq!(info.multipv, Some(1));
+        assert_eq!(info.score, Some(Score::Centipawns(-35)));
+        assert!(info.bound);
+        assert_eq!(info.nodes, Some(123456));
+        assert_eq!(info.nps, Some(987654));
+        assert_eq!(info.hashfull, Some(42));
+        assert_eq!(info.time_ms, Some(125));
+        assert_eq!(info.pv, vec!["e7e5", "g1f3", "b8c6"]);
+    }
+
+    #[test]
+    fn parses_mates_and_strings() {
+        assert_eq!(parse_info("info depth 3 score mate -2 pv e8f8").unwrap().score, Some(Score::Mate(-2)));
+        let info = parse_info("info string NNUE evaluation enabled").unwrap();
+        assert_eq!(info.string.unwrap(), "NNUE evaluation enabled");
+        assert_eq!(parse_info("info currmove e2e4 currmovenumber 1").unwrap(), UciInfo::default());
+        assert!(parse_info("bestmove e2e4").is_none());
+    }
+
+    #[test]
+    fn parses_bestmove() {
+        assert_eq!(parse_bestmove("bestmove e2e4 ponder e7e5"), Some(("e2e4".to_string(), Some("e7e5".to_string()))));
+        assert_eq!(parse_bestmove("bestmove e7e8q"), Some(("e7e8q".to_string(), None)));
+        assert_eq!(parse_bestmove("bestmove (none)"), Some(("(none)".to_string(), None)));
+        assert!(parse_bestmove("info depth 1").is_none());
+    }
+
+    #[test]
+    fn positions_are_sent_with_their_moves() {
+        let mut board = Board::new();
+        assert_eq!(position_command(&board), "position startpos");
+        board.make_move(resolve_uci(&board, "e2e4").unwrap());
+        board.make_move(resolve_uci(&board, "e7e5").unwrap());
+        assert_eq!(position_command(&board), "position startpos moves e2e4 e7e5");
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        let mut board = fen::decode(fen).unwrap();
+        assert_eq!(position_command(&board), format!("position fen {}", fen));
+        board.make_move(resolve_uci(&board, "e1g1").unwrap());
+        assert_eq!(position_command(&board), format!("position fen {} moves e1g1", fen));
+    }
+
+    #[test]
+    fn search_limits() {
+        assert_eq!(go_command(&SearchLimits::depth(8)), "go depth 8");
+        assert_eq!(
+            go_command(&SearchLimits { nodes: Some(5000), ..SearchLimits::movetime(250) }),
+            "go nodes 5000 movetime 250"
+        );
+    }
+}
@@ -0,0 +1,16 @@
+// The engine plays chess rather than refereeing it: it searches the tree of legal moves from a position
+// and picks the move it thinks best.
+
+pub mod bench;
+pub mod book;
+pub mod clock;
+pub mod evaluation;
+pub mod external;
+pub mod polyglot;
+pub mod problem;
+pub mod retrograde;
+pub mod search;
+pub mod syzygy;
+pub mod transposition;
+pub mod uci;
+pub mod xboard;
@@ -0,0 +1,568 @@
+// Chess problems: positions composed so that a stated result comes about in a stated number of moves. In a
+// direct mate the side to move forces mate against any defence; in a selfmate it forces the defender to mate
+// it, the defender doing everything to avoid that; in a helpmate both sides play together for the side to
+// move to be mated.
+//
+// Solving finds every solution. For direct mates and selfmates that's every key (the first move), so a problem
+// with more than one is cooked, and the tree of play after each: every defence, and against it the attacker's
+// quickest ways on, more than one being a dual. For helpmates it's every line of play, lines starting the same
+// way being duals of each other and lines starting differently cooks.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::game::board::Board;
+use crate::game::chess_move::Move;
+use crate::game::color::Color;
+use crate::game::fen;
+use crate::game::notation::{encode, encode_line};
+use crate::game::piece_move::generate_moves;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProblemError {
+    pub reason: String,
+}
+
+// A move in the solution tree with the play after it. After the attacker's moves the replies are every legal
+// defence, none if the move mates; after a defence they're the attacker's quickest ways on, none if the defence
+// mates (as the last move of a selfmate does).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub chess_move: Move,
+    pub san: String,
+    pub replies: Vec<Line>,
+}
+
+impl Line {
+    fn collect_duals<'a>(&'a self, path: &mut Vec<&'a str>, duals: &mut Vec<Vec<&'a str>>) {
+        path.push(&self.san);
+        for defence in self.replies.iter() {
+            path.push(&defence.san);
+            if defence.replies.len() > 1 {
+                duals.push(path.clone());
+            }
+            for answer in defence.replies.iter() {
+                answer.collect_duals(path, duals);
+            }
+            path.pop();
+        }
+        path.pop();
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Key {
+    // fewer than the problem's moves for a short solution
+    pub mate_in: u32,
+    pub play: Line,
+}
+
+impl Key {
+    // the play, from the key, up to each defence the attacker has more than one way of meeting
+    pub fn duals(&self) -> Vec<Vec<&str>> {
+        let mut duals = Vec::new();
+        self.play.collect_duals(&mut Vec::new(), &mut duals);
+        duals
+    }
+}
+
+// a mate in n problem with its solutions
+#[derive(Clone, Debug)]
+pub struct DirectMate {
+    pub board: Board,
+    pub moves: u32,
+    pub keys: Vec<Key>,
+}
+
+impl DirectMate {
+    pub fn solve(board: &Board, moves: u32) -> Result<DirectMate, ProblemError> {
+        let keys = solve_keys(board, moves, Goal::Mate)?;
+        Ok(DirectMate { board: board.clone(), moves, keys })
+    }
+
+    pub fn from_fen(text: &str, moves: u32) -> Result<DirectMate, ProblemError> {
+        DirectMate::solve(&decode(text)?, moves)
+    }
+
+    // a single key
+    pub fn is_sound(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    // more than one key, all of them then being cooks of the intended solution
+    pub fn is_cooked(&self) -> bool {
+        self.keys.len() > 1
+    }
+}
+
+// a selfmate in n problem with its solutions, the side to move forcing the other to mate it
+#[derive(Clone, Debug)]
+pub struct SelfMate {
+    pub board: Board,
+    pub moves: u32,
+    pub keys: Vec<Key>,
+}
+
+impl SelfMate {
+    pub fn solve(board: &Board, moves: u32) -> Result<SelfMate, ProblemError> {
+        let keys = solve_keys(board, moves, Goal::SelfMate)?;
+        Ok(SelfMate { board: board.clone(), moves, keys })
+    }
+
+    pub fn from_fen(text: &str, moves: u32) -> Result<SelfMate, ProblemError> {
+        SelfMate::solve(&decode(text)?, moves)
+    }
+
+    pub fn is_sound(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    pub fn is_cooked(&self) -> bool {
+        self.keys.len() > 1
+    }
+}
+
+// a line of cooperative play ending in mate
+#[derive(Clone, Debug, PartialEq)]
+pub struct HelpLine {
+    pub moves: Vec<Move>,
+    pub san: Vec<String>,
+}
+
+// a helpmate in n problem with its solutions, the side to move helping the other to mate it
+#[derive(Clone, Debug)]
+pub struct HelpMate {
+    pub board: Board,
+    pub moves: u32,
+    pub solutions: Vec<HelpLine>,
+}
+
+impl HelpMate {
+    pub fn solve(board: &Board, moves: u32) -> Result<HelpMate, ProblemError> {
+        check_moves(moves)?;
+        let mut solver = HelpSolver::default();
+        let mut solutions = Vec::new();
+        solver.collect(&mut board.clone(), 2 * moves, &mut Vec::new(), &mut solutions);
+        let solutions = solutions
+            .into_iter()
+            .map(|moves| {
+                let san = encode_line(board, &moves);
+                HelpLine { moves, san }
+            })
+            .collect();
+        Ok(HelpMate { board: board.clone(), moves, solutions })
+    }
+
+    pub fn from_fen(text: &str, moves: u32) -> Result<HelpMate, ProblemError> {
+        HelpMate::solve(&decode(text)?, moves)
+    }
+
+    pub fn is_sound(&self) -> bool {
+        self.solutions.len() == 1
+    }
+
+    // solutions starting with different moves
+    pub fn is_cooked(&self) -> bool {
+        self.solutions.iter().any(|solution| solution.moves[0] != self.solutions[0].moves[0])
+    }
+
+    // solutions starting with the same move
+    pub fn has_duals(&self) -> bool {
+        self.solutions
+            .iter()
+            .enumerate()
+            .any(|(i, solution)| self.solutions[..i].iter().any(|other| other.moves[0] == solution.moves[0]))
+    }
+}
+
+fn decode(text: &str) -> Result<Board, ProblemError> {
+    fen::decode(text).map_err(|err| ProblemError { reason: err.reason })
+}
+
+fn check_moves(moves: u32) -> Result<(), ProblemError> {
+    if moves == 0 {
+        return Err(ProblemError { reason: "A problem needs at least one move".to_string() });
+    }
+    Ok(())
+}
+
+fn solve_keys(board: &Board, moves: u32, goal: Goal) -> Result<Vec<Key>, ProblemError> {
+    check_moves(moves)?;
+    let mut solver = Solver { goal, known: HashMap::new() };
+    let mut board = board.clone();
+    let mut keys = Vec::new();
+    for chess_move in generate_moves(&board) {
+        if let Some(mate_in) = (1..=moves).find(|&n| solver.forces(&mut board, chess_move, n)) {
+            let play = solver.attack(&mut board, chess_move, mate_in);
+            keys.push(Key { mate_in, play });
+        }
+    }
+    Ok(keys)
+}
+
+// Problem notation counts moves from 1 whatever the position's move number, so the attacker's first move is
+// "1.Qg7" or, with Black to move, "1...Qg2".
+fn numbered(ply: usize, attacker: Color, san: &str) -> String {
+    let ply = if attacker == Color::White { ply } else { ply + 1 };
+    if ply % 2 == 0 {
+        format!("{}.{}", ply / 2 + 1, san)
+    } else {
+        format!("{}...{}", ply / 2 + 1, san)
+    }
+}
+
+// each defence on a line of its own with the attacker's answer, and the play after that indented beneath
+fn write_defences(f: &mut fmt::Formatter, attack: &Line, ply: usize, attacker: Color) -> fmt::Result {
+    let indent = "    ".repeat(ply.div_ceil(2));
+    for defence in attack.replies.iter() {
+        if defence.replies.is_empty() {
+            writeln!(f, "{}{}", indent, numbered(ply, attacker, &defence.san))?;
+        }
+        for answer in defence.replies.iter() {
+            writeln!(
+                f,
+                "{}{} {}",
+                indent,
+                numbered(ply, attacker, &defence.san),
+                numbered(ply + 1, attacker, &answer.san)
+            )?;
+            write_defences(f, answer, ply + 2, attacker)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_keys(f: &mut fmt::Formatter, name: &str, board: &Board, moves: u32, keys: &[Key]) -> fmt::Result {
+    let attacker = board.side_to_move();
+    match keys.len() {
+        0 => return writeln!(f, "No {} in {}", name.to_lowercase(), moves),
+        1 => writeln!(f, "{} in {}", name, moves)?,
+        count => writeln!(f, "{} in {}, cooked: {} keys", name, moves, count)?,
+    }
+    for key in keys.iter() {
+        if key.mate_in < moves {
+            writeln!(f, "{}! ({} in {})", numbered(0, attacker, &key.play.san), name.to_lowercase(), key.mate_in)?;
+        } else {
+            writeln!(f, "{}!", numbered(0, attacker, &key.play.san))?;
+        }
+        write_defences(f, &key.play, 1, attacker)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for DirectMate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_keys(f, "Mate", &self.board, self.moves, &self.keys)
+    }
+}
+
+impl fmt::Display for SelfMate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_keys(f, "Selfmate", &self.board, self.moves, &self.keys)
+    }
+}
+
+// Helpmates are written a full move to a line, the helping side's move first: "1.Kd4 Kb2 2.Kc3 Qd2#".
+impl fmt::Display for HelpMate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.solutions.len() {
+            0 => return writeln!(f, "No helpmate in {}", self.moves),
+            1 => writeln!(f, "Helpmate in {}", self.moves)?,
+            count => {
+                write!(f, "Helpmate in {}, {} solutions", self.moves, count)?;
+                if self.is_cooked() {
+                    write!(f, ", cooked")?;
+                }
+                if self.has_duals() {
+                    write!(f, ", with duals")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        for solution in self.solutions.iter() {
+            let moves: Vec<String> = solution
+                .san
+                .chunks(2)
+                .enumerate()
+                .map(|(number, pair)| format!("{}.{}", number + 1, pair.join(" ")))
+                .collect();
+            writeln!(f, "{}", moves.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    // the attacker mates
+    Mate,
+    // the defender mates
+    SelfMate,
+}
+
+fn is_mate(board: &Board) -> bool {
+    board.in_check() && generate_moves(board).is_empty()
+}
+
+struct Solver {
+    goal: Goal,
+    // whether the side to move reaches the goal within so many moves, by position
+    known: HashMap<(u64, u32), bool>,
+}
+
+impl Solver {
+    fn reaches_within(&mut self, board: &mut Board, moves: u32) -> bool {
+        if let Some(&reaches) = self.known.get(&(board.hash(), moves)) {
+            return reaches;
+        }
+        let mut reaches = false;
+        for chess_move in generate_moves(board) {
+            if self.forces(board, chess_move, moves) {
+                reaches = true;
+                break;
+            }
+        }
+        self.known.insert((board.hash(), moves), reaches);
+        reaches
+    }
+
+    // whether the move reaches the goal whatever the defence, in the moves given counting this one
+    fn forces(&mut self, board: &mut Board, chess_move: Move, moves: u32) -> bool {
+        board.make_move(chess_move);
+        let forced = match self.goal {
+            Goal::Mate => self.mates_after(board, moves),
+            Goal::SelfMate => self.is_mated_after(board, moves),
+        };
+        board.unmake_move();
+        forced
+    }
+
+    // whether the attacker, having moved, has mated or mates against every defence in the moves left
+    fn mates_after(&mut self, board: &mut Board, moves: u32) -> bool {
+        if moves == 1 && !board.in_check() {
+            return false;
+        }
+        let defences = generate_moves(board);
+        if defences.is_empty() {
+            return board.in_check();
+        }
+        if moves == 1 {
+            return false;
+        }
+        for defence in defences {
+            board.make_move(defence);
+            let forced = self.reaches_within(board, moves - 1);
+            board.unmake_move();
+            if !forced {
+                return false;
+            }
+        }
+        true
+    }
+
+    // whether every defence mates the attacker or, with moves left, lets the attacker force mate again
+    fn is_mated_after(&mut self, board: &mut Board, moves: u32) -> bool {
+        // mate or stalemate of the defender
+        let defences = generate_moves(board);
+        if defences.is_empty() {
+            return false;
+        }
+        for defence in defences {
+            board.make_move(defence);
+            let forced = is_mate(board) || (moves > 1 && self.reaches_within(board, moves - 1));
+            board.unmake_move();
+            if !forced {
+                return false;
+            }
+        }
+        true
+    }
+
+    // the attacker's move, which must reach the goal in the given moves, with every defence to it
+    fn attack(&mut self, board: &mut Board, chess_move: Move, moves: u32) -> Line {
+        let san = encode(board, &chess_move);
+        board.make_move(chess_move);
+        let mut replies = Vec::new();
+        for defence in generate_moves(board) {
+            replies.push(self.defend(board, defence, moves - 1));
+        }
+        board.unmake_move();
+        Line { chess_move, san, replies }
+    }
+
+    fn defend(&mut self, board: &mut Board, defence: Move, moves: u32) -> Line {
+        let san = encode(board, &defence);
+        board.make_move(defence);
+        let mut replies = Vec::new();
+        if !is_mate(board) {
+            let quickest = (1..=moves).find(|&n| self.reaches_within(board, n)).unwrap_or(moves);
+            for chess_move in generate_moves(board) {
+                if self.forces(board, chess_move, quickest) {
+                    replies.push(self.attack(board, chess_move, quickest));
+                }
+            }
+        }
+        board.unmake_move();
+        Line { chess_move: defence, san, replies }
+    }
+}
+
+#[derive(Default)]
+struct HelpSolver {
+    // whether the side to move can be mated with so many plies played, by position
+    known: HashMap<(u64, u32), bool>,
+}
+
+impl HelpSolver {
+    fn mates_in(&mut self, board: &mut Board, plies: u32) -> bool {
+        if plies == 0 {
+            return is_mate(board);
+        }
+        if let Some(&mates) = self.known.get(&(board.hash(), plies)) {
+            return mates;
+        }
+        let mut mates = false;
+        for chess_move in generate_moves(board) {
+            board.make_move(chess_move);
+            mates = self.mates_in(board, plies - 1);
+            board.unmake_move();
+            if mates {
+                break;
+            }
+        }
+        self.known.insert((board.hash(), plies), mates);
+        mates
+    }
+
+    fn collect(&mut self, board: &mut Board, plies: u32, line: &mut Vec<Move>, solutions: &mut Vec<Vec<Move>>) {
+        if plies == 0 {
+            solutions.push(line.clone());
+            return;
+        }
+        for chess_move in generate_moves(board) {
+            board.make_move(chess_move);
+            if self.mates_in(board, plies - 1) {
+                line.push(chess_move);
+                self.collect(board, plies - 1, line, solutions);
+                line.pop();
+            }
+            board.unmake_move();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(problem: &DirectMate) -> Vec<&str> {
+        problem.keys.iter().map(|key| key.play.san.as_str()).collect()
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let problem = DirectMate::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1).unwrap();
+        assert!(problem.is_sound());
+        assert_eq!(keys(&problem), vec!["Ra8#"]);
+        assert!(problem.keys[0].play.replies.is_empty());
+        assert_eq!(problem.to_string(), "Mate in 1\n1.Ra8#!\n");
+    }
+
+    #[test]
+    fn cooks_are_every_key() {
+        let problem = DirectMate::from_fen("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1", 1).unwrap();
+        assert!(problem.is_cooked());
+        assert_eq!(keys(&problem), vec!["Ra8#", "Rb8#"]);
+        assert!(problem.to_string().starts_with("Mate in 1, cooked: 2 keys\n"));
+    }
+
+    #[test]
+    fn mate_in_two() {
+        let problem = DirectMate::from_fen("5k2/8/5K2/8/8/8/8/4R3 w - - 0 1", 2).unwrap();
+        assert!(problem.is_sound());
+        assert_eq!(problem.keys[0].mate_in, 2);
+        // the king's only flight is g8, as the rook holds the e-file
+        let defences = &problem.keys[0].play.replies;
+        assert_eq!(defences.len(), 1);
+        assert_eq!(defences[0].san, "Kg8");
+        assert_eq!(defences[0].replies[0].san, "Re8#");
+        assert_eq!(problem.to_string(), "Mate in 2\n1.Kg6!\n    1...Kg8 2.Re8#\n");
+    }
+
+    #[test]
+    fn mate_in_three() {
+        let problem = DirectMate::from_fen("6k1/8/4K3/8/8/8/8/4R3 w - - 0 1", 3).unwrap();
+        assert!(problem.is_sound());
+        assert_eq!(
+            problem.to_string(),
+            "Mate in 3
+1.Kf6!
+    1...Kh7 2.Re8
+        2...Kh6 3.Rh8#
+    1...Kf8 2.Kg6
+        2...Kg8 3.Re8#
+    1...Kh8 2.Kg6
+        2...Kg8 3.Re8#
+    1...Kh8 2.Kf7
+        2...Kh7 3.Rh1#
+"
+        );
+        // the last being a dual
+        let defences = &problem.keys[0].play.replies;
+        assert_eq!(defences[2].replies.len(), 2);
+        assert_eq!(problem.keys[0].duals(), vec![vec!["Kf6", "Kh8"]]);
+    }
+
+    #[test]
+    fn black_to_play_and_short_solutions() {
+        let problem = DirectMate::from_fen("4r3/8/8/8/8/5k2/8/5K2 b - - 0 1", 3).unwrap();
+        assert!(problem.is_cooked());
+        let text = problem.to_string();
+        assert!(text.contains("1...Kg3! (mate in 2)\n    2.Kg1 2...Re1#\n"));
+        assert!(text.contains("1...Re4!\n    2.Kg1 2...Rh4\n        3.Kf1 3...Rh1#\n"));
+    }
+
+    #[test]
+    fn unsolvable() {
+        let problem = DirectMate::from_fen("k7/8/8/8/8/8/8/K6N w - - 0 1", 2).unwrap();
+        assert!(problem.keys.is_empty());
+        assert_eq!(problem.to_string(), "No mate in 2\n");
+        assert!(DirectMate::from_fen("k7/8/8/8/8/8/8/K6N w - - 0 1", 0).is_err());
+    }
+
+    #[test]
+    fn selfmates() {
+        let problem = SelfMate::from_fen("5k2/3Q4/4K3/8/8/5q2/7b/8 w - - 0 1", 1).unwrap();
+        assert_eq!(problem.to_string(), "Selfmate in 1\n1.Qf7+!\n    1...Qxf7#\n");
+        // Black's king can't take, so must go to f8 where the queen is offered again
+        let problem = SelfMate::from_fen("4k3/7Q/4K3/8/8/5q2/7b/8 w - - 0 1", 2).unwrap();
+        assert!(problem.is_sound());
+        assert!(problem.keys[0].duals().is_empty());
+        assert_eq!(problem.to_string(), "Selfmate in 2\n1.Qd7+!\n    1...Kf8 2.Qf7+\n        2...Qxf7#\n");
+        // the direct mate isn't one
+        let problem = SelfMate::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2).unwrap();
+        assert_eq!(problem.to_string(), "No selfmate in 2\n");
+    }
+
+    #[test]
+    fn helpmates() {
+        let problem = HelpMate::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1", 1).unwrap();
+        assert!(problem.is_sound());
+        assert_eq!(problem.solutions[0].san, vec!["Kb8", "Rh8#"]);
+        assert_eq!(problem.to_string(), "Helpmate in 1\n1.Kb8 Rh8#\n");
+
+        // Black has to come back to h8, and any white move passes in between
+        let problem = HelpMate::from_fen("7k/6pp/8/8/8/8/8/R5K1 b - - 0 1", 2).unwrap();
+        assert_eq!(problem.solutions.len(), 16);
+        assert!(problem.has_duals());
+        assert!(!problem.is_cooked());
+        let text = problem.to_string();
+        assert!(text.starts_with("Helpmate in 2, 16 solutions, with duals\n"));
+        assert!(text.contains("\n1.Kg8 Rb1 2.Kh8 Rb8#\n"));
+
+        let problem = HelpMate::from_fen("5rk1/8/6K1/8/8/8/8/R7 b - - 0 1", 2).unwrap();
+        assert!(problem.is_cooked());
+        assert!(problem.to_string().contains("\n1.Kh8 Kh6 2.Ra8 Rxa8#\n"));
+        assert!(HelpMate::from_fen("5rk1/8/6K1/8/8/8/8/R7 b - - 0 1", 0).is_err());
+    }
+}
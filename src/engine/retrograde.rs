@@ -0,0 +1,867 @@
+// Endgame tables generated here rather than read from files: for every position of a small ending (e.g. KRK,
+// a king and rook against a lone king) whether the side to move wins, draws or loses, and in how many plies
+// mate comes with best play.
+//
+// Generation is by retrograde analysis. Mates are found first; every position with a move into one is a win in
+// one ply; every position whose moves all lead to such wins is a loss in two, and so on back until nothing
+// changes, leaving the rest as draws. Moves which leave the ending (captures and promotions) are looked up in
+// the smaller endings' tables, generated first. Positions are stored once for each of the board's symmetries,
+// a byte each, and run-length encoded when saved.
+
+use std::collections::HashMap;
+
+use super::search::MATE;
+use crate::game::attacks::*;
+use crate::game::bitboard::*;
+use crate::game::board::*;
+use crate::game::chess_move::*;
+use crate::game::color::Color;
+use crate::game::piece_move::generate_moves;
+use crate::game::piece_type::PieceType;
+use crate::game::position::Position;
+use crate::game::square::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndgameError {
+    pub reason: String,
+}
+
+// the result for the side to move, in plies to mate with best play
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Win(u32),
+    Draw,
+    // Loss(0) is mate
+    Loss(u32),
+}
+
+impl Outcome {
+    // a score as the search would give it
+    pub fn score(self) -> i32 {
+        match self {
+            Outcome::Win(plies) => MATE - plies as i32,
+            Outcome::Draw => 0,
+            Outcome::Loss(plies) => -(MATE - plies as i32),
+        }
+    }
+
+    // the outcome of the position before a move leading to this one
+    fn before(self) -> Outcome {
+        match self {
+            Outcome::Win(plies) => Outcome::Loss(plies + 1),
+            Outcome::Draw => Outcome::Draw,
+            Outcome::Loss(plies) => Outcome::Win(plies + 1),
+        }
+    }
+}
+
+// Values as stored: 0 for a draw, then the plies to mate plus one (odd plies are wins for the side to move,
+// even plies losses) and positions which are illegal or are stored as one of their reflections.
+const DRAW: u8 = 0;
+const UNUSED: u8 = 255;
+// while generating
+const UNKNOWN: u8 = 254;
+const DRAWN: u8 = 253;
+const MAX_PLIES: u32 = 251;
+
+const MAGIC: &[u8; 4] = b"NRTB";
+// a run of values in a saved table
+const RUN: u8 = 255;
+
+fn decode_value(value: u8) -> Option<Outcome> {
+    match value {
+        UNUSED => None,
+        DRAW | UNKNOWN | DRAWN => Some(Outcome::Draw),
+        value if (value - 1) % 2 == 1 => Some(Outcome::Win(value as u32 - 1)),
+        value => Some(Outcome::Loss(value as u32 - 1)),
+    }
+}
+
+fn encode_plies(plies: u32) -> u8 {
+    plies as u8 + 1
+}
+
+// Applies one of the board's eight symmetries: bit 2 reflects in the a1-h8 diagonal, then bit 0 left to right
+// and bit 1 top to bottom. Endings with pawns only have the left-right reflection.
+fn reflect(symmetry: u8, square: u8) -> u8 {
+    let mut square = square;
+    if symmetry & 4 != 0 {
+        square = ((square >> 3) | (square << 3)) & 63;
+    }
+    if symmetry & 1 != 0 {
+        square ^= 7;
+    }
+    if symmetry & 2 != 0 {
+        square ^= 56;
+    }
+    square
+}
+
+fn square(index: u8) -> Square {
+    Square::from_index(index).expect("squares are numbered below 64")
+}
+
+// The pieces of an ending and how its positions are numbered: the side to move, the white king (on one of the
+// squares left after the reflections), the black king, then White's other pieces.
+#[derive(Clone, Debug, PartialEq)]
+struct Layout {
+    // White's pieces besides the king, queens first and pawns last
+    pieces: Vec<PieceType>,
+    has_pawns: bool,
+    king_squares: Vec<u8>,
+    king_slots: [Option<u32>; 64],
+}
+
+impl Layout {
+    fn new(pieces: Vec<PieceType>) -> Layout {
+        let has_pawns = pieces.contains(&PieceType::Pawn);
+        // a1-d1-d4 without pawns, the a to d files with them
+        let king_squares: Vec<u8> =
+            (0..64).filter(|&square| square & 7 < 4 && (has_pawns || square >> 3 <= square & 7)).collect();
+        let mut king_slots = [None; 64];
+        for (slot, &square) in king_squares.iter().enumerate() {
+            king_slots[square as usize] = Some(slot as u32);
+        }
+        Layout { pieces, has_pawns, king_squares, king_slots }
+    }
+
+    fn name(&self) -> String {
+        let pieces: String = self.pieces.iter().map(|piece| piece.symbol().unwrap_or('P')).collect();
+        format!("K{}K", pieces)
+    }
+
+    fn size(&self) -> usize {
+        2 * self.king_squares.len() * 64usize.pow(self.pieces.len() as u32 + 1)
+    }
+
+    fn index(&self, side_to_move: Color, squares: &[u8]) -> Option<usize> {
+        let mut idx = (side_to_move == Color::Black) as usize * self.king_squares.len();
+        idx += self.king_slots[squares[0] as usize]? as usize;
+        for &square in &squares[1..] {
+            idx = idx * 64 + square as usize;
+        }
+        Some(idx)
+    }
+
+    // the index of the position or of whichever of its reflections is numbered lowest
+    fn canonical(&self, side_to_move: Color, squares: &[u8]) -> Option<usize> {
+        let symmetries = if self.has_pawns { 2 } else { 8 };
+        let mut reflected = [0; 4];
+        let reflected = &mut reflected[..squares.len()];
+        (0..symmetries)
+            .filter_map(|symmetry| {
+                self.king_slots[reflect(symmetry, squares[0]) as usize]?;
+                for (to, &from) in reflected.iter_mut().zip(squares) {
+                    *to = reflect(symmetry, from);
+                }
+                // pieces of the same kind are interchangeable, so they're numbered in order of square
+                let mut start = 2;
+                while start < reflected.len() {
+                    let piece = self.pieces[start - 2];
+                    let end = start + self.pieces[start - 2..].iter().take_while(|&&other| other == piece).count();
+                    reflected[start..end].sort_unstable();
+                    start = end;
+                }
+                self.index(side_to_move, reflected)
+            })
+            .min()
+    }
+
+    // the index after a move within the ending
+    fn after(&self, side_to_move: Color, squares: &[u8], chess_move: &Move) -> Option<usize> {
+        let mut after = [0; 4];
+        let after = &mut after[..squares.len()];
+        after.copy_from_slice(squares);
+        let moved = after.iter().position(|&at| at == chess_move.from.index())?;
+        after[moved] = chess_move.to.index();
+        self.canonical(!side_to_move, after)
+    }
+
+    fn decode(&self, idx: usize) -> (Color, Vec<u8>) {
+        let mut idx = idx;
+        let mut squares = vec![0; self.pieces.len() + 2];
+        for square in squares[1..].iter_mut().rev() {
+            *square = (idx % 64) as u8;
+            idx /= 64;
+        }
+        let slots = self.king_squares.len();
+        squares[0] = self.king_squares[idx % slots];
+        let side_to_move = if idx / slots == 0 { Color::White } else { Color::Black };
+        (side_to_move, squares)
+    }
+
+    // No two pieces on a square, no pawns on the first or last rank, and the side which has just moved not in
+    // check. Black has only the king, which can't give check.
+    fn is_legal(&self, side_to_move: Color, squares: &[u8]) -> bool {
+        let occupied = squares.iter().fold(Bitboard::EMPTY, |occupied, &at| occupied.with(square(at)));
+        if occupied.count() as usize != squares.len() {
+            return false;
+        }
+        let pawn_out_of_place = self
+            .pieces
+            .iter()
+            .zip(&squares[2..])
+            .any(|(&piece, &square)| piece == PieceType::Pawn && !(8..56).contains(&square));
+        let black_king = square(squares[1]);
+        if pawn_out_of_place || king_attacks(square(squares[0])).contains(black_king) {
+            return false;
+        }
+        side_to_move == Color::Black
+            || !self.pieces.iter().zip(&squares[2..]).any(|(&piece_type, &at)| {
+                piece_attacks(piece_type, Color::White, square(at), occupied).contains(black_king)
+            })
+    }
+
+    // whether the index is of a legal position, and not of the reflection of one numbered lower
+    fn is_stored(&self, idx: usize) -> bool {
+        let (side_to_move, squares) = self.decode(idx);
+        self.is_legal(side_to_move, &squares) && self.canonical(side_to_move, &squares) == Some(idx)
+    }
+
+    fn board(&self, side_to_move: Color, squares: &[u8]) -> Option<Board> {
+        if !self.is_legal(side_to_move, squares) {
+            return None;
+        }
+        let mut positions = vec![
+            Position { square: square(squares[0]), piece_type: PieceType::King, color: Color::White },
+            Position { square: square(squares[1]), piece_type: PieceType::King, color: Color::Black },
+        ];
+        for (&piece_type, &at) in self.pieces.iter().zip(&squares[2..]) {
+            positions.push(Position { square: square(at), piece_type, color: Color::White });
+        }
+        Some(Board::from_parts(&positions, side_to_move, CastlingRights::NONE, None, 0, 1))
+    }
+
+    // the squares of the kings and White's other pieces, in the layout's order, if the board has them
+    fn squares(&self, board: &Board) -> Option<Vec<u8>> {
+        let white = board.color(Color::White);
+        let black = board.color(Color::Black);
+        if black.count() != 1 || white.count() as usize != self.pieces.len() + 1 {
+            return None;
+        }
+        let mut squares = vec![board.king_square(Color::White)?.index(), board.king_square(Color::Black)?.index()];
+        let mut start = 0;
+        while start < self.pieces.len() {
+            let piece = self.pieces[start];
+            let count = self.pieces[start..].iter().take_while(|&&other| other == piece).count();
+            let on = board.pieces_of(Color::White, piece);
+            if on.count() as usize != count {
+                return None;
+            }
+            squares.extend(on.squares().map(|square| square.index()));
+            start += count;
+        }
+        Some(squares)
+    }
+
+    // the positions one move before, by the side not to move, which didn't capture or promote
+    fn predecessors(&self, side_to_move: Color, squares: &[u8]) -> Vec<Vec<u8>> {
+        let occupied = squares.iter().fold(Bitboard::EMPTY, |occupied, &at| occupied.with(square(at)));
+        let mut predecessors = vec![];
+        let mut unmove = |piece: usize, from: Square| {
+            let mut before = squares.to_vec();
+            before[piece] = from.index();
+            predecessors.push(before);
+        };
+        if side_to_move == Color::White {
+            for from in king_attacks(square(squares[1])) & !occupied {
+                unmove(1, from);
+            }
+            return predecessors;
+        }
+        for from in king_attacks(square(squares[0])) & !occupied {
+            unmove(0, from);
+        }
+        for (i, &piece_type) in self.pieces.iter().enumerate() {
+            let at = square(squares[i + 2]);
+            if piece_type != PieceType::Pawn {
+                // the moves of pieces other than pawns can be made backwards
+                for from in piece_attacks(piece_type, Color::White, at, occupied) & !occupied {
+                    unmove(i + 2, from);
+                }
+                continue;
+            }
+            let rank = at.index() >> 3;
+            let one_back = square(at.index() - 8);
+            if rank >= 2 && !occupied.contains(one_back) {
+                unmove(i + 2, one_back);
+                let two_back = square(at.index() - 16);
+                if rank == 3 && !occupied.contains(two_back) {
+                    unmove(i + 2, two_back);
+                }
+            }
+        }
+        predecessors
+    }
+}
+
+// the material of a name like "KBNK": a king and the given pieces against a lone king
+fn parse_name(name: &str) -> Result<Vec<PieceType>, EndgameError> {
+    let invalid = || EndgameError {
+        reason: format!("{} isn't an ending of a king and up to two pieces against a lone king", name),
+    };
+    let inner = name.strip_prefix('K').and_then(|rest| rest.strip_suffix('K')).ok_or_else(invalid)?;
+    let mut pieces = inner
+        .chars()
+        .map(|c| match c {
+            'Q' => Ok(PieceType::Queen),
+            'R' => Ok(PieceType::Rook),
+            'B' => Ok(PieceType::Bishop),
+            'N' => Ok(PieceType::Knight),
+            'P' => Ok(PieceType::Pawn),
+            _ => Err(invalid()),
+        })
+        .collect::<Result<Vec<PieceType>, EndgameError>>()?;
+    if pieces.is_empty() || pieces.len() > 2 {
+        return Err(invalid());
+    }
+    pieces.sort();
+    Ok(pieces)
+}
+
+// the outcome after the move, which may leave the ending
+fn outcome_after(board: &mut Board, chess_move: Move, table: &EndgameTable) -> Option<Outcome> {
+    board.make_move(chess_move);
+    let outcome = table.probe(board).or_else(|| {
+        let pieces: Vec<PieceType> = (board.color(Color::White) & !board.pieces(PieceType::King))
+            .squares()
+            .map(|square| board.piece_type_at(square))
+            .collect();
+        if pieces.is_empty() {
+            return Some(Outcome::Draw);
+        }
+        table.subtables.iter().find_map(|subtable| subtable.probe(board))
+    });
+    board.unmake_move();
+    outcome
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndgameTable {
+    layout: Layout,
+    values: Vec<u8>,
+    // the endings reached by captures and promotions
+    subtables: Vec<EndgameTable>,
+}
+
+impl EndgameTable {
+    // generates the ending named as in "KRK" or "KBNK", with White the stronger side
+    pub fn generate(name: &str) -> Result<EndgameTable, EndgameError> {
+        Generator::default().generate(parse_name(name)?)
+    }
+
+    pub fn name(&self) -> String {
+        self.layout.name()
+    }
+
+    // the positions stored, counting reflections of one another once
+    pub fn len(&self) -> usize {
+        self.values.iter().filter(|&&value| value != UNUSED).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the result with best play, if the position is of this ending (either way round)
+    pub fn probe(&self, board: &Board) -> Option<Outcome> {
+        let (side_to_move, squares) = self.locate(board)?;
+        let idx = self.layout.canonical(side_to_move, &squares)?;
+        decode_value(self.values[idx])
+    }
+
+    // the side to move and pieces' squares, with the colours swapped if Black is the stronger side
+    fn locate(&self, board: &Board) -> Option<(Color, Vec<u8>)> {
+        if board.castling_rights() != CastlingRights::NONE {
+            return None;
+        }
+        if let Some(squares) = self.layout.squares(board) {
+            return Some((board.side_to_move(), squares));
+        }
+        let positions: Vec<Position> = board
+            .to_positions()
+            .into_iter()
+            .map(|position| Position { square: position.square.flip(), color: !position.color, ..position })
+            .collect();
+        let flipped = Board::from_parts(&positions, !board.side_to_move(), CastlingRights::NONE, None, 0, 1);
+        Some((flipped.side_to_move(), self.layout.squares(&flipped)?))
+    }
+
+    // the move reaching the outcome soonest when winning and putting it off longest when losing
+    pub fn best_move(&self, board: &Board) -> Option<Move> {
+        self.probe(board)?;
+        let mut board = board.clone();
+        generate_moves(&board)
+            .into_iter()
+            .filter_map(|chess_move| Some((chess_move, outcome_after(&mut board, chess_move, self)?.before())))
+            .max_by_key(|&(_, outcome)| outcome.score())
+            .map(|(chess_move, _)| chess_move)
+    }
+
+    // the wins, draws and losses stored for the side to move
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for &value in &self.values {
+            match decode_value(value) {
+                Some(Outcome::Win(_)) => counts.0 += 1,
+                Some(Outcome::Draw) => counts.1 += 1,
+                Some(Outcome::Loss(_)) => counts.2 += 1,
+                None => {}
+            }
+        }
+        counts
+    }
+
+    // the most plies to mate of any win
+    pub fn longest_win(&self) -> u32 {
+        self.values
+            .iter()
+            .filter_map(|&value| match decode_value(value) {
+                Some(Outcome::Win(plies)) => Some(plies),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // A drill: a position with White to move and mate in exactly so many plies, picked by the roll from all
+    // of them.
+    pub fn drill(&self, plies: u32, roll: u64) -> Option<Board> {
+        let wanted = encode_plies(plies);
+        let half = self.values.len() / 2;
+        let count = self.values[..half].iter().filter(|&&value| value == wanted).count();
+        if plies.is_multiple_of(2) || count == 0 {
+            return None;
+        }
+        let nth = (roll % count as u64) as usize;
+        let idx = (0..half).filter(|&idx| self.values[idx] == wanted).nth(nth)?;
+        let (side_to_move, squares) = self.layout.decode(idx);
+        self.layout.board(side_to_move, &squares)
+    }
+
+    // Checks every stored value against the values after each legal move, found with the crate's move
+    // generator rather than the backward moves the table was generated with, so that a mistake in either
+    // shows up as a disagreement.
+    pub fn check(&self) -> Result<(), EndgameError> {
+        for (idx, &value) in self.values.iter().enumerate() {
+            let (side_to_move, squares) = self.layout.decode(idx);
+            if value == UNUSED {
+                if self.layout.is_stored(idx) {
+                    return Err(EndgameError { reason: format!("{} at {} is missing", self.name(), idx) });
+                }
+                continue;
+            }
+            let mut board = self.layout.board(side_to_move, &squares)
+                .ok_or_else(|| EndgameError { reason: format!("{} at {} is illegal", self.name(), idx) })?;
+            let moves = generate_moves(&board);
+            let expected = if moves.is_empty() {
+                if board.in_check() { Outcome::Loss(0) } else { Outcome::Draw }
+            } else {
+                let mut best = None;
+                for chess_move in moves {
+                    let outcome = outcome_after(&mut board, chess_move, self)
+                        .ok_or_else(|| EndgameError { reason: format!("{} has no value", chess_move) })?
+                        .before();
+                    best = best.max_by_score(outcome);
+                }
+                best.unwrap_or(Outcome::Draw)
+            };
+            if decode_value(value) != Some(expected) {
+                return Err(EndgameError {
+                    reason: format!("{} at {} is {:?} but its moves give {:?}", self.name(), idx, value, expected),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // The table and the ones it relies on. Only the positions stored are written, in order, with runs of four
+    // or more of the same value as RUN, the count and the value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        self.write(&mut bytes);
+        bytes
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        let name = self.name();
+        bytes.push(name.len() as u8);
+        bytes.extend(name.as_bytes());
+        let values: Vec<u8> = self.values.iter().copied().filter(|&value| value != UNUSED).collect();
+        let mut i = 0;
+        while i < values.len() {
+            let value = values[i];
+            let run = values[i..].iter().take(255).take_while(|&&other| other == value).count();
+            if run >= 4 {
+                bytes.extend([RUN, run as u8, value]);
+            } else {
+                bytes.extend(std::iter::repeat_n(value, run));
+            }
+            i += run;
+        }
+        bytes.push(self.subtables.len() as u8);
+        for subtable in &self.subtables {
+            subtable.write(bytes);
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<EndgameTable, EndgameError> {
+        let rest = bytes
+            .strip_prefix(&MAGIC[..])
+            .ok_or_else(|| EndgameError { reason: "Not an endgame table".to_string() })?;
+        let mut reader = rest.iter().copied();
+        let table = EndgameTable::read(&mut reader)?;
+        if reader.next().is_some() {
+            return Err(EndgameError { reason: "Unexpected bytes after the endgame table".to_string() });
+        }
+        Ok(table)
+    }
+
+    fn read(reader: &mut impl Iterator<Item = u8>) -> Result<EndgameTable, EndgameError> {
+        let truncated = || EndgameError { reason: "Truncated endgame table".to_string() };
+        let name_len = reader.next().ok_or_else(truncated)? as usize;
+        let name: String = reader.by_ref().take(name_len).map(char::from).collect();
+        let layout = Layout::new(parse_name(&name)?);
+        let corrupt = || EndgameError { reason: format!("Corrupt endgame table for {}", name) };
+        let mut values = vec![UNUSED; layout.size()];
+        let mut run = (0, UNUSED);
+        for (idx, value) in values.iter_mut().enumerate() {
+            if !layout.is_stored(idx) {
+                continue;
+            }
+            if run.0 == 0 {
+                run = match reader.next().ok_or_else(truncated)? {
+                    RUN => (reader.next().ok_or_else(truncated)?, reader.next().ok_or_else(truncated)?),
+                    value => (1, value),
+                };
+                if run.0 == 0 || run.1 > MAX_PLIES as u8 + 1 {
+                    return Err(corrupt());
+                }
+            }
+            *value = run.1;
+            run.0 -= 1;
+        }
+        if run.0 != 0 {
+            return Err(corrupt());
+        }
+        let subtables = (0..reader.next().ok_or_else(truncated)?)
+            .map(|_| EndgameTable::read(reader))
+            .collect::<Result<_, _>>()?;
+        Ok(EndgameTable { layout, values, subtables })
+    }
+}
+
+trait BestOutcome {
+    fn max_by_score(self, outcome: Outcome) -> Self;
+}
+
+impl BestOutcome for Option<Outcome> {
+    fn max_by_score(self, outcome: Outcome) -> Option<Outcome> {
+        match self {
+            Some(best) if best.score() >= outcome.score() => Some(best),
+            _ => Some(outcome),
+        }
+    }
+}
+
+// generates endings and the smaller ones they lead to, each once
+#[derive(Default)]
+struct Generator {
+    generated: HashMap<Vec<PieceType>, EndgameTable>,
+}
+
+impl Generator {
+    fn generate(&mut self, pieces: Vec<PieceType>) -> Result<EndgameTable, EndgameError> {
+        if let Some(table) = self.generated.get(&pieces) {
+            return Ok(table.clone());
+        }
+        // captures leave one piece fewer, and promotions swap a pawn for another piece
+        let mut smaller = vec![];
+        for (i, &piece) in pieces.iter().enumerate() {
+            let mut rest = pieces.clone();
+            rest.remove(i);
+            if !rest.is_empty() {
+                smaller.push(rest.clone());
+            }
+            if piece == PieceType::Pawn {
+                for promotion in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                    let mut promoted = rest.clone();
+                    promoted.push(promotion);
+                    promoted.sort();
+                    smaller.push(promoted);
+                }
+            }
+        }
+        smaller.sort();
+        smaller.dedup();
+        let subtables = smaller.into_iter().map(|pieces| self.generate(pieces)).collect::<Result<Vec<_>, _>>()?;
+        let layout = Layout::new(pieces.clone());
+        let mut table = EndgameTable { values: vec![UNUSED; layout.size()], layout, subtables };
+        table.solve()?;
+        self.generated.insert(pieces, table.clone());
+        Ok(table)
+    }
+}
+
+impl EndgameTable {
+    fn solve(&mut self) -> Result<(), EndgameError> {
+        let size = self.values.len();
+        // how many of the positions reached by moves within the ending aren't yet known to be won by the other
+        // side, counted as stored rather than by move, as two moves can reach reflections of the same position
+        let mut moves_left = vec![0u8; size];
+        // positions to resolve at each ply: those known to be mate in so many, or wins by leaving the ending
+        let mut by_ply: Vec<Vec<u32>> = vec![vec![]];
+        let mut exit_wins: Vec<Vec<u32>> = vec![];
+        for (idx, moves_left) in moves_left.iter_mut().enumerate() {
+            if !self.layout.is_stored(idx) {
+                continue;
+            }
+            let (side_to_move, squares) = self.layout.decode(idx);
+            let mut board = match self.layout.board(side_to_move, &squares) {
+                Some(board) => board,
+                None => continue,
+            };
+            self.values[idx] = UNKNOWN;
+            let moves = generate_moves(&board);
+            if moves.is_empty() {
+                if board.in_check() {
+                    self.values[idx] = encode_plies(0);
+                    by_ply[0].push(idx as u32);
+                } else {
+                    self.values[idx] = DRAWN;
+                }
+                continue;
+            }
+            let mut staying = vec![];
+            let mut best_exit: Option<Outcome> = None;
+            for chess_move in moves {
+                let leaves = board.is_capture(&chess_move) || chess_move.promotion.is_some();
+                if !leaves {
+                    staying.extend(self.layout.after(side_to_move, &squares, &chess_move));
+                    continue;
+                }
+                let outcome = outcome_after(&mut board, chess_move, self).ok_or_else(|| EndgameError {
+                    reason: format!("No table for the ending after {} in {}", chess_move, self.name()),
+                })?;
+                best_exit = best_exit.max_by_score(outcome.before());
+            }
+            staying.sort_unstable();
+            staying.dedup();
+            let staying = staying.len();
+            *moves_left = staying as u8;
+            match best_exit {
+                Some(Outcome::Win(plies)) => {
+                    let plies = plies as usize;
+                    if exit_wins.len() <= plies {
+                        exit_wins.resize(plies + 1, vec![]);
+                    }
+                    exit_wins[plies].push(idx as u32);
+                }
+                Some(Outcome::Draw) if staying == 0 => self.values[idx] = DRAWN,
+                Some(Outcome::Loss(plies)) if staying == 0 => {
+                    self.values[idx] = encode_plies(plies);
+                    by_ply.resize(by_ply.len().max(plies as usize + 1), vec![]);
+                    by_ply[plies as usize].push(idx as u32);
+                }
+                _ => {}
+            }
+        }
+        let mut ply = 0;
+        loop {
+            let pending = |lists: &[Vec<u32>]| lists.iter().skip(ply).any(|list| !list.is_empty());
+            if !pending(&by_ply) && !pending(&exit_wins) {
+                break;
+            }
+            if ply as u32 > MAX_PLIES {
+                return Err(EndgameError { reason: format!("{} has mates too long to store", self.name()) });
+            }
+            by_ply.resize(by_ply.len().max(ply + 2), vec![]);
+            for &idx in exit_wins.get(ply).map(|wins| wins.as_slice()).unwrap_or(&[]) {
+                if self.values[idx as usize] == UNKNOWN {
+                    self.values[idx as usize] = encode_plies(ply as u32);
+                    by_ply[ply].push(idx);
+                }
+            }
+            for idx in std::mem::take(&mut by_ply[ply]) {
+                let (side_to_move, squares) = self.layout.decode(idx as usize);
+                let mut predecessors: Vec<usize> = self
+                    .layout
+                    .predecessors(side_to_move, &squares)
+                    .iter()
+                    .filter_map(|before| self.layout.canonical(!side_to_move, before))
+                    .collect();
+                predecessors.sort_unstable();
+                predecessors.dedup();
+                for previous in predecessors {
+                    if self.values[previous] != UNKNOWN {
+                        continue;
+                    }
+                    if ply % 2 == 0 {
+                        // a move to a lost position wins
+                        self.values[previous] = encode_plies(ply as u32 + 1);
+                        by_ply[ply + 1].push(previous as u32);
+                        continue;
+                    }
+                    moves_left[previous] = moves_left[previous].saturating_sub(1);
+                    if moves_left[previous] > 0 {
+                        continue;
+                    }
+                    if let Some(Outcome::Loss(plies)) = self.outcome_by_moves(previous)? {
+                        self.values[previous] = encode_plies(plies);
+                        by_ply.resize(by_ply.len().max(plies as usize + 1), vec![]);
+                        by_ply[plies as usize].push(previous as u32);
+                    }
+                }
+            }
+            ply += 1;
+        }
+        for value in self.values.iter_mut() {
+            if *value == UNKNOWN || *value == DRAWN {
+                *value = DRAW;
+            }
+        }
+        Ok(())
+    }
+
+    // the outcome from the moves' outcomes, if they are all known
+    fn outcome_by_moves(&self, idx: usize) -> Result<Option<Outcome>, EndgameError> {
+        let (side_to_move, squares) = self.layout.decode(idx);
+        let mut board = match self.layout.board(side_to_move, &squares) {
+            Some(board) => board,
+            None => return Ok(None),
+        };
+        let mut best = None;
+        for chess_move in generate_moves(&board) {
+            let leaves = board.is_capture(&chess_move) || chess_move.promotion.is_some();
+            let outcome = if leaves {
+                outcome_after(&mut board, chess_move, self)
+            } else {
+                match self.layout.after(side_to_move, &squares, &chess_move).map(|after| self.values[after]) {
+                    Some(UNKNOWN) => return Ok(None),
+                    value => value.and_then(decode_value),
+                }
+            };
+            let outcome = outcome.ok_or_else(|| EndgameError { reason: format!("{} has no value", chess_move) })?;
+            best = best.max_by_score(outcome.before());
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::OnceLock;
+
+    use crate::game::fen;
+    use crate::game::notation::encode;
+
+    // KPK, and through it KQK and KRK, are generated once for all the tests
+    fn kpk() -> &'static EndgameTable {
+        static KPK: OnceLock<EndgameTable> = OnceLock::new();
+        KPK.get_or_init(|| EndgameTable::generate("KPK").unwrap())
+    }
+
+    fn table(name: &str) -> &'static EndgameTable {
+        kpk().subtables.iter().find(|table| table.name() == name).unwrap()
+    }
+
+    #[test]
+    fn names_give_the_pieces() {
+        assert_eq!(parse_name("KBNK").unwrap(), [PieceType::Bishop, PieceType::Knight]);
+        assert_eq!(parse_name("KNBK").unwrap(), [PieceType::Bishop, PieceType::Knight]);
+        assert!(parse_name("KK").is_err());
+        assert!(parse_name("KQRBK").is_err());
+        assert!(parse_name("KQKR").is_err());
+        assert_eq!(Layout::new(vec![PieceType::Queen]).size(), 2 * 10 * 64 * 64);
+        assert_eq!(Layout::new(vec![PieceType::Pawn]).size(), 2 * 32 * 64 * 64);
+    }
+
+    #[test]
+    fn reflections_share_an_index() {
+        let layout = Layout::new(vec![PieceType::Rook]);
+        // e1, e8, h1 against d1, d8, a1 and e8, e1, h8
+        let idx = layout.canonical(Color::White, &[4, 60, 7]);
+        assert_eq!(layout.canonical(Color::White, &[3, 59, 0]), idx);
+        assert_eq!(layout.canonical(Color::White, &[60, 4, 63]), idx);
+        assert_ne!(layout.canonical(Color::Black, &[4, 60, 7]), idx);
+        let (side_to_move, squares) = layout.decode(idx.unwrap());
+        assert_eq!(layout.canonical(side_to_move, &squares), idx);
+    }
+
+    #[test]
+    fn king_and_queen_mate_within_ten_moves() {
+        let table = table("KQK");
+        assert_eq!(table.longest_win(), 19);
+        table.check().unwrap();
+        let board = fen::decode("8/8/8/8/8/8/8/kQ5K b - - 0 1").unwrap();
+        assert_eq!(table.probe(&board), Some(Outcome::Draw));
+        let board = fen::decode("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&board), Some(Outcome::Win(1)));
+    }
+
+    #[test]
+    fn king_and_rook_mate_within_sixteen_moves() {
+        let table = table("KRK");
+        assert_eq!(table.longest_win(), 31);
+        let board = fen::decode("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        assert_eq!(table.probe(&board), Some(Outcome::Win(1)));
+        assert_eq!(encode(&board, &table.best_move(&board).unwrap()), "Rh8#");
+        // the same with the colours reversed
+        let board = fen::decode("7r/8/8/8/8/1k6/8/K7 b - - 0 1").unwrap();
+        assert_eq!(table.probe(&board), Some(Outcome::Win(1)));
+        let board = fen::decode("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&board), None);
+    }
+
+    #[test]
+    fn king_and_pawn_results() {
+        let table = kpk();
+        let probe = |fen: &str| table.probe(&fen::decode(fen).unwrap()).unwrap();
+        // the defending king in front of the pawn holds
+        assert_eq!(probe("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1"), Outcome::Draw);
+        // the attacking king on the sixth in front of the pawn wins whoever is to move
+        assert!(matches!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Outcome::Win(_)));
+        assert!(matches!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Outcome::Loss(_)));
+        // but not with a rook's pawn
+        assert_eq!(probe("k7/8/K7/P7/8/8/8/8 w - - 0 1"), Outcome::Draw);
+        // a pawn which can't be caught queens and mates
+        assert!(matches!(probe("8/4P3/8/8/8/8/k7/4K3 w - - 0 1"), Outcome::Win(_)));
+    }
+
+    // these take a while in a debug build
+    #[test]
+    #[ignore]
+    fn king_and_pawn_agree_with_the_move_generator() {
+        kpk().check().unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn bishop_and_knight_mate_within_thirty_three_moves() {
+        let table = EndgameTable::generate("KBNK").unwrap();
+        assert_eq!(table.longest_win(), 65);
+        table.check().unwrap();
+    }
+
+    #[test]
+    fn drills_are_picked_by_length() {
+        let table = table("KQK");
+        let board = table.drill(5, 7).unwrap();
+        assert_eq!(board.side_to_move(), Color::White);
+        assert_eq!(table.probe(&board), Some(Outcome::Win(5)));
+        assert_eq!(table.drill(4, 0), None);
+        assert_eq!(table.drill(21, 0), None);
+    }
+
+    #[test]
+    fn tables_are_saved_compactly() {
+        let table = table("KRK");
+        let bytes = table.to_bytes();
+        assert!(bytes.len() < table.len(), "{} bytes", bytes.len());
+        assert_eq!(&EndgameTable::from_bytes(&bytes).unwrap(), table);
+        assert!(EndgameTable::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(EndgameTable::from_bytes(b"nonsense").is_err());
+    }
+}
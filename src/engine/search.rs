@@ -0,0 +1,670 @@
+// Iterative deepening principal variation search. Each iteration searches one ply deeper than the last,
+// trying the previous iteration's best line first, so that a search cut short by its limits still has the
+// best move found so far to return. Leaf positions are resolved by a quiescence search over captures so
+// that they aren't judged halfway through an exchange.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use wasm_bindgen::prelude::*;
+
+use super::clock::*;
+use super::evaluation::*;
+use super::transposition::*;
+use crate::game::board::*;
+use crate::game::chess_move::*;
+use crate::game::notation::encode_line;
+use crate::game::piece_move::generate_moves;
+use crate::game::piece_type::PieceType;
+
+// Scores are in centipawns from the point of view of the side to move. Being mated n plies from the root
+// scores -(MATE - n), so a quicker mate is always preferred to a slower one.
+pub const MATE: i32 = 32000;
+pub const INFINITY: i32 = MATE + 1;
+pub const MAX_PLY: usize = 128;
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+// mate in so many moves (not plies), negative when the side to move is the one being mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if !is_mate_score(score) {
+        None
+    } else if score > 0 {
+        Some((MATE - score + 1) / 2)
+    } else {
+        Some(-(MATE + score) / 2)
+    }
+}
+
+// A search stops at whichever limit it reaches first; with none it runs until stopped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime_ms: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits {
+            nodes: Some(nodes),
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn movetime(movetime_ms: u64) -> SearchLimits {
+        SearchLimits {
+            movetime_ms: Some(movetime_ms),
+            ..SearchLimits::default()
+        }
+    }
+}
+
+// reported as each iteration completes
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub score: i32,
+    pub nodes: u64,
+    pub time_ms: u64,
+    // how full the transposition table is, in parts per thousand
+    pub hashfull: u32,
+    pub pv: Vec<Move>,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    // in standard algebraic notation; none when there is no legal move
+    #[wasm_bindgen(js_name = bestMove)]
+    pub best_move: Option<String>,
+    pub pv: Vec<String>,
+    pub score: i32,
+    // mate in so many moves, when the score is a mate score
+    pub mate: Option<i32>,
+    // the deepest iteration completed
+    pub depth: u32,
+    pub nodes: u64,
+    #[wasm_bindgen(js_name = timeMs)]
+    pub time_ms: u64,
+    moves: Vec<Move>,
+}
+
+impl SearchResult {
+    pub fn best(&self) -> Option<Move> {
+        self.moves.first().copied()
+    }
+
+    pub fn principal_variation(&self) -> &[Move] {
+        &self.moves
+    }
+}
+
+// Which of the search's refinements are used. They all save nodes (see the benchmarks in bench.rs) and
+// are on by default; they can be switched off to compare them or to rule them out when something looks wrong.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    // the transposition table's size
+    pub hash_mb: usize,
+    pub transposition_table: bool,
+    // captures of the most valuable victim by the least valuable attacker first (the search of captures
+    // beyond the depth limit always orders them so)
+    pub mvv_lva: bool,
+    // quiet moves which caused a cutoff at the same ply elsewhere in the tree first
+    pub killer_moves: bool,
+    // quiet moves which have often caused cutoffs anywhere in the tree first
+    pub history_heuristic: bool,
+    // if passing the turn still leaves the side to move doing too well, its moves needn't be searched
+    pub null_move_pruning: bool,
+    // moves ordered late are searched less deeply unless they turn out to be good
+    pub late_move_reductions: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            hash_mb: 16,
+            transposition_table: true,
+            mvv_lva: true,
+            killer_moves: true,
+            history_heuristic: true,
+            null_move_pruning: true,
+            late_move_reductions: true,
+        }
+    }
+}
+
+impl SearchOptions {
+    // a plain alpha-beta search, with every refinement switched off
+    pub fn plain() -> SearchOptions {
+        SearchOptions {
+            hash_mb: 1,
+            transposition_table: false,
+            mvv_lva: false,
+            killer_moves: false,
+            history_heuristic: false,
+            null_move_pruning: false,
+            late_move_reductions: false,
+        }
+    }
+}
+
+pub struct Engine {
+    stop: Arc<AtomicBool>,
+    options: SearchOptions,
+    table: TranspositionTable,
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::with_options(SearchOptions::default())
+    }
+
+    pub fn with_options(options: SearchOptions) -> Engine {
+        Engine {
+            stop: Arc::new(AtomicBool::new(false)),
+            options,
+            table: TranspositionTable::new(options.hash_mb),
+        }
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    // the transposition table is only reallocated (and so emptied) when its size changes
+    pub fn set_options(&mut self, options: SearchOptions) {
+        if options.hash_mb != self.options.hash_mb {
+            self.table = TranspositionTable::new(options.hash_mb);
+        }
+        self.options = options;
+    }
+
+    // forgets everything learnt from earlier searches, e.g. when a new game starts
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    // setting the flag (from another thread, say) ends the current search as soon as possible; it is
+    // cleared when the next search starts
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn search(&mut self, board: &Board, limits: &SearchLimits) -> SearchResult {
+        self.search_with(board, limits, |_| {})
+    }
+
+    pub fn search_with(
+        &mut self,
+        board: &Board,
+        limits: &SearchLimits,
+        mut on_iteration: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        self.stop.store(false, Ordering::Relaxed);
+        self.table.new_search();
+        let mut search = Search {
+            board: board.clone(),
+            limits: *limits,
+            options: self.options,
+            table: &mut self.table,
+            stopwatch: Stopwatch::start(),
+            stop: &self.stop,
+            nodes: 0,
+            aborted: false,
+            previous_pv: vec![],
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![[0; 64]; 64],
+        };
+        let mut completed: Option<SearchInfo> = None;
+        let max_depth = limits.depth.unwrap_or(MAX_PLY as u32 - 1).clamp(1, MAX_PLY as u32 - 1);
+        for depth in 1..=max_depth {
+            let mut pv = vec![];
+            let score = search.negamax(depth as i32, 0, -INFINITY, INFINITY, &mut pv);
+            if search.aborted {
+                // moves searched completely before the search was cut short can still improve on the
+                // previous iteration, which always searches its best move first
+                if let (Some(info), false) = (completed.as_mut(), pv.is_empty()) {
+                    info.pv = pv;
+                }
+                break;
+            }
+            let info = SearchInfo {
+                depth,
+                score,
+                nodes: search.nodes,
+                time_ms: search.stopwatch.elapsed_ms(),
+                hashfull: if self.options.transposition_table { search.table.hashfull() } else { 0 },
+                pv: pv.clone(),
+            };
+            on_iteration(&info);
+            search.previous_pv = pv;
+            completed = Some(info);
+            // a forced mate found within the depth searched can't be improved on by searching deeper
+            if mate_in(score).is_some_and(|moves| moves.unsigned_abs() * 2 <= depth) || search.out_of_time(2) {
+                break;
+            }
+        }
+        let (score, depth, mut moves) = match completed {
+            Some(info) => (info.score, info.depth, info.pv),
+            None => (0, 0, vec![]),
+        };
+        // stopped before the first iteration finished, so any legal move will do
+        if moves.is_empty() {
+            moves.extend(generate_moves(board).first());
+        }
+        let pv = encode_line(board, &moves);
+        SearchResult {
+            best_move: pv.first().cloned(),
+            pv,
+            score,
+            mate: mate_in(score),
+            depth,
+            nodes: search.nodes,
+            time_ms: search.stopwatch.elapsed_ms(),
+            moves,
+        }
+    }
+}
+
+struct Search<'a> {
+    board: Board,
+    limits: SearchLimits,
+    options: SearchOptions,
+    table: &'a mut TranspositionTable,
+    stopwatch: Stopwatch,
+    stop: &'a AtomicBool,
+    nodes: u64,
+    aborted: bool,
+    previous_pv: Vec<Move>,
+    // two quiet moves per ply which last caused a cutoff there
+    killers: Vec<[Option<Move>; 2]>,
+    // by origin and destination square, how much quiet moves have caused cutoffs
+    history: Vec<[i32; 64]>,
+}
+
+// move ordering scores, highest first
+const HASH_MOVE: i32 = 1_000_000;
+const CAPTURE: i32 = 100_000;
+const KILLER: [i32; 2] = [90_000, 89_000];
+const HISTORY_LIMIT: i32 = 80_000;
+
+impl Search<'_> {
+    // has more than 1/fraction of the time allowed been used?
+    fn out_of_time(&self, fraction: u64) -> bool {
+        self.limits
+            .movetime_ms
+            .is_some_and(|movetime| self.stopwatch.elapsed_ms() * fraction >= movetime)
+    }
+
+    // the clock and stop flag are only looked at every so often as reading them costs more than a node
+    fn should_abort(&mut self) -> bool {
+        if !self.aborted {
+            self.aborted = self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes)
+                || (self.nodes.is_multiple_of(1024) && (self.stop.load(Ordering::Relaxed) || self.out_of_time(1)));
+        }
+        self.aborted
+    }
+
+    fn is_quiet(&self, chess_move: &Move) -> bool {
+        !self.board.is_capture(chess_move) && chess_move.promotion.is_none()
+    }
+
+    fn is_killer(&self, chess_move: &Move, ply: usize) -> bool {
+        self.options.killer_moves && self.killers[ply].contains(&Some(*chess_move))
+    }
+
+    fn mvv_lva(&self, chess_move: &Move) -> i32 {
+        let victim = self.board.captured_piece_type(chess_move).map_or(0, material_value)
+            + chess_move.promotion.map_or(0, material_value);
+        let attacker = material_value(self.board.piece_type_at(chess_move.from));
+        victim * 10 - attacker / 10
+    }
+
+    fn capture_score(&self, chess_move: &Move) -> i32 {
+        if self.options.mvv_lva {
+            CAPTURE + self.mvv_lva(chess_move)
+        } else {
+            CAPTURE
+        }
+    }
+
+    // the hash move goes first, then captures and promotions, then killers, then the other quiet moves by
+    // their history
+    fn order_moves(&self, moves: &mut [Move], ply: usize, hash_move: Option<Move>) {
+        moves.sort_by_cached_key(|chess_move| {
+            let score = if Some(*chess_move) == hash_move {
+                HASH_MOVE
+            } else if !self.is_quiet(chess_move) {
+                self.capture_score(chess_move)
+            } else if let Some(slot) = self.killers[ply].iter().position(|killer| *killer == Some(*chess_move)) {
+                if self.options.killer_moves { KILLER[slot] } else { 0 }
+            } else if self.options.history_heuristic {
+                self.history[chess_move.from.index() as usize][chess_move.to.index() as usize]
+            } else {
+                0
+            };
+            std::cmp::Reverse(score)
+        });
+    }
+
+    fn record_cutoff(&mut self, chess_move: Move, ply: usize, depth: i32) {
+        if self.options.killer_moves && self.killers[ply][0] != Some(chess_move) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(chess_move);
+        }
+        if self.options.history_heuristic {
+            let entry = &mut self.history[chess_move.from.index() as usize][chess_move.to.index() as usize];
+            *entry = (*entry + depth * depth).min(HISTORY_LIMIT);
+        }
+    }
+
+    // without pieces the side to move is often in zugzwang, where passing would be better than any move
+    fn has_pieces(&self) -> bool {
+        let color = self.board.side_to_move();
+        (self.board.color(color)
+            & !self.board.pieces(PieceType::Pawn)
+            & !self.board.pieces(PieceType::King))
+        .any()
+    }
+
+    fn negamax(&mut self, depth: i32, ply: usize, mut alpha: i32, mut beta: i32, pv: &mut Vec<Move>) -> i32 {
+        pv.clear();
+        if self.should_abort() {
+            return 0;
+        }
+        if ply > 0 {
+            if self.board.halfmove_clock() >= 100 || self.board.is_repetition() {
+                return 0;
+            }
+            // no line from here can beat a mate already found nearer the root
+            alpha = alpha.max(-MATE + ply as i32);
+            beta = beta.min(MATE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+        let in_check = self.board.in_check();
+        // looking one ply further when in check stops the horizon hiding a mate
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth <= 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(ply, alpha, beta);
+        }
+        self.nodes += 1;
+
+        // only nodes searched with a null window take their score from the table, so that the principal
+        // variation is always searched out in full
+        let pv_node = beta - alpha > 1;
+        let entry = if self.options.transposition_table {
+            self.table.probe(self.board.hash(), ply)
+        } else {
+            None
+        };
+        if let Some(entry) = entry.filter(|entry| !pv_node && ply > 0 && entry.depth >= depth) {
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if usable {
+                return entry.score;
+            }
+        }
+
+        if self.options.null_move_pruning
+            && !pv_node
+            && !in_check
+            && ply > 0
+            && depth >= 3
+            && !is_mate_score(beta)
+            && self.has_pieces()
+            && evaluate(&self.board) >= beta
+        {
+            let reduction = if depth > 6 { 3 } else { 2 };
+            let mut line = vec![];
+            self.board.make_move(Move::NULL);
+            let score = -self.negamax(depth - 1 - reduction, ply + 1, -beta, -beta + 1, &mut line);
+            self.board.unmake_move();
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                return if is_mate_score(score) { beta } else { score };
+            }
+        }
+
+        let mut moves = generate_moves(&self.board);
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        let hash_move = entry
+            .and_then(|entry| entry.best_move)
+            .or_else(|| self.previous_pv.get(ply).copied());
+        self.order_moves(&mut moves, ply, hash_move);
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut line = vec![];
+        for (index, chess_move) in moves.iter().enumerate() {
+            let quiet = self.is_quiet(chess_move);
+            let killer = self.is_killer(chess_move, ply);
+            self.board.make_move(*chess_move);
+            let score = if index == 0 {
+                -self.negamax(depth - 1, ply + 1, -beta, -alpha, &mut line)
+            } else {
+                // quiet moves late in the order are unlikely to be best, so are first searched less deeply
+                let reduction = if self.options.late_move_reductions
+                    && depth >= 3
+                    && index >= 3
+                    && quiet
+                    && !killer
+                    && !in_check
+                    && !self.board.in_check()
+                {
+                    if index >= 6 { 2 } else { 1 }
+                } else {
+                    0
+                };
+                // after the first move, the others are expected to be worse and searched with a null window
+                // to prove it, only being searched again in full if they turn out better
+                let mut score = -self.negamax(depth - 1 - reduction, ply + 1, -alpha - 1, -alpha, &mut line);
+                if reduction > 0 && score > alpha {
+                    score = -self.negamax(depth - 1, ply + 1, -alpha - 1, -alpha, &mut line);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, &mut line);
+                }
+                score
+            };
+            self.board.unmake_move();
+            if self.aborted {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                best_move = Some(*chess_move);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(*chess_move);
+                    pv.append(&mut line);
+                    if alpha >= beta {
+                        if quiet {
+                            self.record_cutoff(*chess_move, ply, depth);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        if self.options.transposition_table {
+            let bound = if best >= beta {
+                Bound::Lower
+            } else if best > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.table.store(self.board.hash(), depth, best, bound, best_move, ply);
+        }
+        best
+    }
+
+    // only captures and promotions are searched, unless in check, and the side to move can always decline
+    // them by "standing pat" on the static evaluation
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_abort() {
+            return 0;
+        }
+        self.nodes += 1;
+        let in_check = self.board.in_check();
+        let mut moves = generate_moves(&self.board);
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        let mut best = -INFINITY;
+        if !in_check {
+            best = evaluate(&self.board);
+            if best >= beta || ply >= MAX_PLY - 1 {
+                return best;
+            }
+            alpha = alpha.max(best);
+            moves.retain(|chess_move| !self.is_quiet(chess_move));
+        }
+        // always the most valuable victims first, as otherwise the search of captures can take longer than
+        // the rest of the search put together
+        moves.sort_by_cached_key(|chess_move| std::cmp::Reverse(self.mvv_lva(chess_move)));
+        for chess_move in moves {
+            self.board.make_move(chess_move);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.board.unmake_move();
+            if self.aborted {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::fen::decode;
+
+    fn search(fen: &str, limits: SearchLimits) -> SearchResult {
+        let board = decode(fen).unwrap_or_else(|err| panic!("{}", err.reason));
+        Engine::new().search(&board, &limits)
+    }
+
+    #[test]
+    fn mate_scores_count_moves_to_mate() {
+        assert_eq!(mate_in(MATE - 1), Some(1));
+        assert_eq!(mate_in(MATE - 3), Some(2));
+        assert_eq!(mate_in(-(MATE - 2)), Some(-1));
+        assert_eq!(mate_in(250), None);
+    }
+
+    #[test]
+    fn finds_a_mate_in_one() {
+        let result = search("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", SearchLimits::depth(3));
+        assert_eq!(result.best_move, Some("Ra8#".to_string()));
+        assert_eq!(result.mate, Some(1));
+    }
+
+    #[test]
+    fn finds_a_mate_in_two() {
+        let result = search("r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1", SearchLimits::depth(5));
+        assert_eq!(result.mate, Some(2));
+        assert_eq!(result.pv, vec!["Re8+", "Rxe8", "Rxe8#"]);
+    }
+
+    #[test]
+    fn wins_hanging_material() {
+        let result = search("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", SearchLimits::depth(2));
+        assert_eq!(result.best_move, Some("Rxd5".to_string()));
+        assert!(result.score > 400);
+    }
+
+    #[test]
+    fn quiescence_sees_the_recapture() {
+        // taking the defended pawn with the queen loses it to the pawn on e6
+        let result = search("4k3/8/4p3/3p4/8/8/3Q4/4K3 w - - 0 1", SearchLimits::depth(1));
+        assert_ne!(result.best_move, Some("Qxd5".to_string()));
+    }
+
+    #[test]
+    fn being_mated_scores_as_a_mate_against() {
+        let result = search("k7/8/1K6/8/8/8/8/7R b - - 0 1", SearchLimits::depth(3));
+        assert_eq!(result.mate, Some(-1));
+    }
+
+    #[test]
+    fn no_legal_move_means_no_best_move() {
+        let result = search("k7/8/1Q6/8/8/8/8/7K b - - 0 1", SearchLimits::depth(3));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn a_node_limit_is_respected() {
+        let result = search(crate::game::fen::INITIAL_FEN, SearchLimits::nodes(5000));
+        assert!(result.nodes <= 5000);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn a_time_limit_is_respected() {
+        let result = search(crate::game::fen::INITIAL_FEN, SearchLimits::movetime(50));
+        assert!(result.time_ms < 500);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn a_stopped_search_returns_a_legal_move() {
+        let mut engine = Engine::new();
+        let board = Board::new();
+        engine.stop_handle().store(true, Ordering::Relaxed);
+        // the flag is cleared as the search starts, so stopping has to happen during it
+        let stop = engine.stop_handle();
+        let result = engine.search_with(&board, &SearchLimits::default(), |info| {
+            if info.depth == 2 {
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+        assert!(result.depth >= 2);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn each_iteration_is_reported() {
+        let mut depths = vec![];
+        let board = Board::new();
+        Engine::new().search_with(&board, &SearchLimits::depth(3), |info| depths.push(info.depth));
+        assert_eq!(depths, vec![1, 2, 3]);
+    }
+}
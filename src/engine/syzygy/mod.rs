@@ -0,0 +1,588 @@
+// Syzygy endgame tablebases: for every position with few enough pieces, whether the side to move wins, draws
+// or loses with perfect play (WDL), and how many plies it takes to reach the next capture or pawn move while
+// keeping that result (DTZ). Playing the move with the best DTZ wins any won position, and the tables account
+// for the 50-move rule: a "cursed" win can't be forced in time and a "blessed" loss is saved by it.
+//
+// The tables are read from .rtbw (WDL) and .rtbz (DTZ) files as published at https://tablebase.lichess.ovh,
+// e.g. KQvK.rtbw for a king and queen against a king. Neither holds positions with castling rights, and WDL
+// tables may hold anything for positions where a capture is best, so probing always looks at the captures too.
+
+mod table;
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::ops::Neg;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use table::*;
+use wasm_bindgen::prelude::*;
+
+use crate::game::board::*;
+use crate::game::chess_move::*;
+use crate::game::color::Color;
+use crate::game::game::Game;
+use crate::game::moving::MoveError;
+use crate::game::notation::encode;
+use crate::game::piece_move::generate_moves;
+use crate::game::piece_type::PieceType;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyzygyError {
+    pub reason: String,
+}
+
+// the result for the side to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss,
+    // lost, but drawn by the 50-move rule
+    BlessedLoss,
+    Draw,
+    // won, but drawn by the 50-move rule
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    // -2 for a loss up to 2 for a win
+    pub fn value(self) -> i32 {
+        self as i32 - 2
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-self.value())
+    }
+}
+
+impl fmt::Display for Wdl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Wdl::Loss => "loss",
+            Wdl::BlessedLoss => "blessed loss",
+            Wdl::Draw => "draw",
+            Wdl::CursedWin => "cursed win",
+            Wdl::Win => "win",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// The DTZ of the position before a capture or pawn move with the given result afterwards for the side which
+// made it. DTZ tables don't hold these as the move itself resets the count.
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+// a legal move at the root, ranked by what it leads to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RootMove {
+    pub chess_move: Move,
+    // for the side making the move
+    pub wdl: Wdl,
+    // plies to a capture or pawn move, counted from before the move: positive when winning, negative when losing
+    pub dtz: i32,
+    // higher is better: certain wins 1000, wins which may run into the 50-move rule less, draws 0, losses below
+    pub rank: i32,
+}
+
+// what the tablebases say about a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Verdict {
+    pub wdl: Wdl,
+    pub dtz: i32,
+    pub best_move: Option<Move>,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.wdl {
+            Wdl::Draw => write!(f, "draw"),
+            wdl => {
+                let plies = self.dtz.abs();
+                let unit = if plies == 1 { "ply" } else { "plies" };
+                write!(f, "{} in {} {} to a capture or pawn move", wdl, plies, unit)
+            }
+        }
+    }
+}
+
+// the files for one material balance, opened when first probed
+struct Entry {
+    material: Material,
+    wdl_source: TableSource,
+    dtz_source: Option<TableSource>,
+    wdl: OnceLock<Result<Table, SyzygyError>>,
+    dtz: OnceLock<Result<Table, SyzygyError>>,
+}
+
+#[derive(Default)]
+pub struct Tablebases {
+    // by name, e.g. "KRPvKR"
+    entries: HashMap<String, Entry>,
+    max_pieces: usize,
+}
+
+// the pieces of one side in the order table names list them, e.g. "KRP"
+fn side_name(board: &Board, color: Color) -> String {
+    let pieces = [
+        (PieceType::King, 'K'),
+        (PieceType::Queen, 'Q'),
+        (PieceType::Rook, 'R'),
+        (PieceType::Bishop, 'B'),
+        (PieceType::Knight, 'N'),
+        (PieceType::Pawn, 'P'),
+    ];
+    pieces
+        .iter()
+        .flat_map(|&(piece_type, letter)| {
+            std::iter::repeat_n(letter, board.pieces_of(color, piece_type).count() as usize)
+        })
+        .collect()
+}
+
+fn piece_code(piece_type: PieceType, color: Color) -> u8 {
+    let code = match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+        PieceType::Empty => 0,
+    };
+    if color == Color::Black {
+        code | BLACK
+    } else {
+        code
+    }
+}
+
+fn is_zeroing(board: &Board, chess_move: &Move) -> bool {
+    board.is_capture(chess_move) || board.piece_type_at(chess_move.from) == PieceType::Pawn
+}
+
+fn is_mate(board: &Board) -> bool {
+    board.in_check() && generate_moves(board).is_empty()
+}
+
+impl Tablebases {
+    pub fn new() -> Tablebases {
+        Tablebases::default()
+    }
+
+    // the tables in the directories, which are separated as in PATH (e.g. "/tb/345:/tb/6" on Unix)
+    pub fn open(paths: &str) -> Result<Tablebases, SyzygyError> {
+        let mut tablebases = Tablebases::new();
+        for directory in env::split_paths(paths) {
+            tablebases.add_directory(&directory)?;
+        }
+        Ok(tablebases)
+    }
+
+    // adds the tables in the directory, returning how many were found
+    pub fn add_directory(&mut self, directory: &Path) -> Result<usize, SyzygyError> {
+        let listing = fs::read_dir(directory)
+            .map_err(|err| SyzygyError { reason: format!("Couldn't read {}: {}", directory.display(), err) })?;
+        let mut found = 0;
+        for path in listing.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.extension().and_then(|extension| extension.to_str()) != Some("rtbw") {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let material = match Material::from_name(&name) {
+                Some(material) => material,
+                None => continue,
+            };
+            let dtz_source = Some(path.with_extension("rtbz")).filter(|dtz| dtz.is_file()).map(TableSource::Path);
+            self.insert(name, material, TableSource::Path(path), dtz_source);
+            found += 1;
+        }
+        Ok(found)
+    }
+
+    // adds a table from the bytes of its files, named as they are (e.g. "KQvK")
+    pub fn add_table(&mut self, name: &str, wdl: Vec<u8>, dtz: Option<Vec<u8>>) -> Result<(), SyzygyError> {
+        let material = Material::from_name(name)
+            .ok_or_else(|| SyzygyError { reason: format!("{} isn't the name of a tablebase", name) })?;
+        let wdl_source = TableSource::Bytes(format!("{}.rtbw", name), wdl.into());
+        let dtz_source = dtz.map(|dtz| TableSource::Bytes(format!("{}.rtbz", name), dtz.into()));
+        self.insert(name.to_string(), material, wdl_source, dtz_source);
+        Ok(())
+    }
+
+    fn insert(&mut self, name: String, material: Material, wdl_source: TableSource, dtz_source: Option<TableSource>) {
+        self.max_pieces = self.max_pieces.max(material.piece_count);
+        let entry = Entry { material, wdl_source, dtz_source, wdl: OnceLock::new(), dtz: OnceLock::new() };
+        self.entries.insert(name, entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // the most pieces of any table found
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // the names of the tables found, e.g. "KQvK"
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.entries.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // can the position be probed at all (whether or not its table was found)?
+    pub fn covers(&self, board: &Board) -> bool {
+        board.castling_rights() == CastlingRights::NONE && board.occupied().count() as usize <= self.max_pieces
+    }
+
+    fn check(&self, board: &Board) -> Result<(), SyzygyError> {
+        if board.castling_rights() != CastlingRights::NONE {
+            return Err(SyzygyError { reason: "Positions with castling rights aren't in the tablebases".to_string() });
+        }
+        Ok(())
+    }
+
+    fn probe_table(&self, board: &Board, kind: Kind, wdl: Wdl) -> Result<Lookup, SyzygyError> {
+        // king against king needs no table
+        if board.occupied().count() == 2 {
+            return Ok(Lookup::Value(0));
+        }
+        let (white, black) = (side_name(board, Color::White), side_name(board, Color::Black));
+        let name = format!("{}v{}", white, black);
+        let (entry, mirrored) = match self.entries.get(&name) {
+            Some(entry) => (entry, false),
+            None => match self.entries.get(&format!("{}v{}", black, white)) {
+                Some(entry) => (entry, true),
+                None => return Err(SyzygyError { reason: format!("No tablebase for {}", name) }),
+            },
+        };
+        let table = match kind {
+            Kind::Wdl => entry.wdl.get_or_init(|| Table::open(&entry.wdl_source, kind, entry.material.clone())),
+            Kind::Dtz => match &entry.dtz_source {
+                Some(source) => entry.dtz.get_or_init(|| Table::open(source, kind, entry.material.clone())),
+                None => return Err(SyzygyError { reason: format!("No DTZ tablebase for {}", name) }),
+            },
+        };
+        let table = table.as_ref().map_err(|err| err.clone())?;
+        let pieces: Vec<(u8, u8)> = board
+            .occupied()
+            .squares()
+            .filter_map(|square| {
+                board.piece_at(square).map(|(piece_type, color)| (square.index(), piece_code(piece_type, color)))
+            })
+            .collect();
+        table.probe(&pieces, board.side_to_move() == Color::White, mirrored, wdl.value())
+    }
+
+    // The result with the captures (and with zeroing_moves, pawn moves) searched as well as the table probed,
+    // and whether the best of those moves is at least as good as what the table says.
+    fn search(&self, board: &mut Board, zeroing_moves: bool) -> Result<(Wdl, bool), SyzygyError> {
+        let moves = generate_moves(board);
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for chess_move in moves.iter() {
+            let pawn_move = board.piece_type_at(chess_move.from) == PieceType::Pawn;
+            if !(board.is_capture(chess_move) || zeroing_moves && pawn_move) {
+                continue;
+            }
+            searched += 1;
+            board.make_move(*chess_move);
+            let value = self.search(board, false).map(|(wdl, _)| -wdl);
+            board.unmake_move();
+            let value = value?;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+        // when every move has been searched the table needn't be (and may be wrong, e.g. with en passant)
+        let every_move = searched > 0 && searched == moves.len();
+        let value = if every_move {
+            best
+        } else {
+            match self.probe_table(board, Kind::Wdl, Wdl::Draw)? {
+                Lookup::Value(value) => Wdl::from_value(value),
+                Lookup::ChangeSideToMove => {
+                    return Err(SyzygyError { reason: "WDL tables hold both sides".to_string() })
+                }
+            }
+        };
+        if best >= value {
+            return Ok((best, best > Wdl::Draw || every_move));
+        }
+        Ok((value, false))
+    }
+
+    // the result for the side to move with perfect play
+    pub fn probe_wdl(&self, board: &Board) -> Result<Wdl, SyzygyError> {
+        self.check(board)?;
+        Ok(self.search(&mut board.clone(), false)?.0)
+    }
+
+    // The plies to the next capture or pawn move with perfect play, positive when the side to move wins and
+    // negative when it loses, beyond 100 when the 50-move rule gets in the way, and 0 for a draw. A mated side
+    // has -1.
+    pub fn probe_dtz(&self, board: &Board) -> Result<i32, SyzygyError> {
+        self.check(board)?;
+        self.dtz(&mut board.clone())
+    }
+
+    fn dtz(&self, board: &mut Board) -> Result<i32, SyzygyError> {
+        let (wdl, zeroing_is_best) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing_is_best {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+        let sign = wdl.value().signum();
+        if let Lookup::Value(dtz) = self.probe_table(board, Kind::Dtz, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Ok((dtz + if cursed { 100 } else { 0 }) * sign);
+        }
+        // the table holds the other side to move, so look one move ahead for the best DTZ
+        let mut best = i32::MAX;
+        for chess_move in generate_moves(board) {
+            let zeroing = is_zeroing(board, &chess_move);
+            board.make_move(chess_move);
+            let dtz = if zeroing {
+                self.search(board, false).map(|(wdl, _)| -dtz_before_zeroing(wdl))
+            } else {
+                self.dtz(board).map(|dtz| -dtz)
+            };
+            let mates = is_mate(board);
+            board.unmake_move();
+            let mut dtz = dtz?;
+            if dtz == 1 && mates {
+                best = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < best && dtz.signum() == sign {
+                best = dtz;
+            }
+        }
+        Ok(if best == i32::MAX { -1 } else { best })
+    }
+
+    // Every legal move ranked, best first: wins by the fewest plies to a capture or pawn move, then draws, then
+    // losses by the most plies. Wins and losses are only certain if the 50-move rule won't intervene, given how
+    // many plies the count has reached already.
+    pub fn root_moves(&self, board: &Board) -> Result<Vec<RootMove>, SyzygyError> {
+        self.check(board)?;
+        let mut board = board.clone();
+        let count = board.halfmove_clock() as i32;
+        let mut root_moves = vec![];
+        for chess_move in generate_moves(&board) {
+            board.make_move(chess_move);
+            let probed = self.search(&mut board, false).and_then(|(wdl, _)| {
+                let dtz = if board.halfmove_clock() == 0 {
+                    dtz_before_zeroing(-wdl)
+                } else {
+                    let dtz = -self.dtz(&mut board)?;
+                    dtz + dtz.signum()
+                };
+                Ok((-wdl, dtz))
+            });
+            let mates = is_mate(&board);
+            board.unmake_move();
+            let (wdl, mut dtz) = probed?;
+            if mates && dtz == 2 {
+                dtz = 1;
+            }
+            let rank = if dtz > 0 {
+                if dtz + count <= 99 {
+                    1000
+                } else {
+                    1000 - (dtz + count)
+                }
+            } else if dtz < 0 {
+                if -dtz * 2 + count < 100 {
+                    -1000
+                } else {
+                    -1000 + (-dtz + count)
+                }
+            } else {
+                0
+            };
+            root_moves.push(RootMove { chess_move, wdl, dtz, rank });
+        }
+        root_moves.sort_by_key(|root_move| (-root_move.rank, root_move.dtz));
+        Ok(root_moves)
+    }
+
+    // the moves which keep the best result, for the engine to choose between
+    pub fn best_moves(&self, board: &Board) -> Result<Vec<Move>, SyzygyError> {
+        let root_moves = self.root_moves(board)?;
+        let best = root_moves.first().map(|root_move| root_move.rank);
+        Ok(root_moves
+            .iter()
+            .filter(|root_move| Some(root_move.rank) == best)
+            .map(|root_move| root_move.chess_move)
+            .collect())
+    }
+
+    // the result, the plies to the next capture or pawn move, and the move to play to get there soonest
+    pub fn verdict(&self, board: &Board) -> Result<Verdict, SyzygyError> {
+        let wdl = self.probe_wdl(board)?;
+        let dtz = self.probe_dtz(board)?;
+        let best_move = self.root_moves(board)?.first().map(|root_move| root_move.chess_move);
+        Ok(Verdict { wdl, dtz, best_move })
+    }
+}
+
+// What the tablebases say as offered to JavaScript, e.g. "win in 13 plies to a capture or pawn move"
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TablebaseVerdict {
+    // "win", "cursed win", "draw", "blessed loss" or "loss" for the side to move
+    pub result: String,
+    pub dtz: i32,
+    // in SAN
+    pub best_move: Option<String>,
+    pub summary: String,
+}
+
+// Tablebases for the web, added from the bytes of their files
+#[wasm_bindgen(js_name = EndgameTablebases)]
+#[derive(Default)]
+pub struct EndgameTablebases {
+    tablebases: Tablebases,
+}
+
+#[wasm_bindgen(js_class = EndgameTablebases)]
+impl EndgameTablebases {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> EndgameTablebases {
+        EndgameTablebases::default()
+    }
+
+    // the .rtbw and (optionally) .rtbz files of the named table, e.g. "KQvK"
+    #[wasm_bindgen(js_name = addTable)]
+    pub fn add_table(&mut self, name: &str, wdl: Vec<u8>, dtz: Option<Vec<u8>>) -> Result<(), MoveError> {
+        self.tablebases.add_table(name, wdl, dtz).map_err(|err| MoveError { reason: err.reason })
+    }
+
+    // what the tables say about the game's current position, or nothing when they don't cover it
+    pub fn verdict(&self, game: &Game) -> Result<Option<TablebaseVerdict>, MoveError> {
+        let board = game.board()?;
+        if !self.tablebases.covers(&board) {
+            return Ok(None);
+        }
+        let verdict = self.tablebases.verdict(&board).map_err(|err| MoveError { reason: err.reason })?;
+        Ok(Some(TablebaseVerdict {
+            result: verdict.wdl.to_string(),
+            dtz: verdict.dtz,
+            best_move: verdict.best_move.map(|chess_move| encode(&board, &chess_move)),
+            summary: verdict.to_string(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::fen;
+
+    #[test]
+    fn results_are_negated() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::BlessedLoss, Wdl::CursedWin);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert_eq!(Wdl::CursedWin.value(), 1);
+        assert!(Wdl::Win > Wdl::CursedWin);
+        assert_eq!(Wdl::BlessedLoss.to_string(), "blessed loss");
+        let verdict = Verdict { wdl: Wdl::Loss, dtz: -13, best_move: None };
+        assert_eq!(verdict.to_string(), "loss in 13 plies to a capture or pawn move");
+    }
+
+    #[test]
+    fn bare_kings_need_no_tables() {
+        let tablebases = Tablebases::new();
+        let board = fen::decode("8/8/4k3/8/8/3K4/8/8 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&board), Ok(Wdl::Draw));
+        assert_eq!(tablebases.probe_dtz(&board), Ok(0));
+        // a queen which can only be taken
+        let board = fen::decode("k7/1Q6/8/8/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&board), Ok(Wdl::Draw));
+        let verdict = tablebases.verdict(&board).unwrap();
+        assert_eq!(verdict.to_string(), "draw");
+        assert_eq!(verdict.best_move.map(|chess_move| chess_move.to.to_string()), Some("b7".to_string()));
+    }
+
+    #[test]
+    fn positions_out_of_the_tables_are_errors() {
+        let tablebases = Tablebases::new();
+        let err = tablebases.probe_wdl(&Board::new()).unwrap_err();
+        assert_eq!(err.reason, "Positions with castling rights aren't in the tablebases");
+        let board = fen::decode("8/8/8/8/8/4k3/8/3QKR2 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&board).unwrap_err().reason, "No tablebase for KQRvK");
+        assert!(!tablebases.covers(&board));
+    }
+
+    #[test]
+    fn directories_are_scanned() {
+        let directory = env::temp_dir().join(format!("narmi-syzygy-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("KQvK.rtbw"), b"not a table").unwrap();
+        fs::write(directory.join("KQvK.rtbz"), b"not a table").unwrap();
+        fs::write(directory.join("README.txt"), b"").unwrap();
+        let tablebases = Tablebases::open(directory.to_str().unwrap()).unwrap();
+        assert_eq!(tablebases.names(), ["KQvK"]);
+        assert_eq!(tablebases.max_pieces(), 3);
+        // black's queen is found in the same table
+        let board = fen::decode("8/8/8/4k3/8/8/8/3qK3 w - - 0 1").unwrap();
+        assert!(tablebases.covers(&board));
+        // the files are only opened when probed
+        let err = tablebases.probe_wdl(&board).unwrap_err();
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(err.reason.ends_with("is not a Syzygy WDL table"), "{}", err.reason);
+        assert!(Tablebases::open("/no/such/directory").is_err());
+    }
+
+    #[test]
+    fn tables_are_added_from_bytes() {
+        let mut tablebases = EndgameTablebases::new();
+        let game = Game::new();
+        assert_eq!(tablebases.verdict(&game).unwrap(), None);
+        tablebases.add_table("KQvK", vec![], None).unwrap();
+        assert!(tablebases.add_table("KQ", vec![], None).is_err());
+        assert_eq!(tablebases.tablebases.names(), ["KQvK"]);
+    }
+}
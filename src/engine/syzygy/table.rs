@@ -0,0 +1,1002 @@
+// Reading Syzygy table files. Each file holds one or more tables of values (a WDL or DTZ value per position),
+// compressed by recursive pairing and canonical Huffman codes into blocks. A position is found in a table by
+// an index computed from where its pieces stand, after mirroring the board so that equivalent positions share
+// an index. Files are read a block at a time as they are probed, since the larger ones run to gigabytes.
+//
+// The layout follows the probing code published with the tables (see https://github.com/syzygy1/tb) and the
+// well documented port of it in Stockfish's tbprobe.cpp.
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use super::SyzygyError;
+
+pub const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+pub const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// flags in the first byte of a file
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// flags of each table
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+// pieces are coded as in the files: 1 to 6 for White's pawn, knight, bishop, rook, queen and king, and 9 to 14
+// for Black's
+pub const BLACK: u8 = 8;
+
+// the most pieces any published table has
+pub const MAX_PIECES: usize = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Wdl,
+    Dtz,
+}
+
+// the squares and combinations the position indices are built from
+struct Indexing {
+    // a2..h7 numbered so that the pawn nearest the edge and then the lowest rank has the highest number
+    map_pawns: [u64; 64],
+    // squares below the a1-h8 diagonal
+    map_b1h1h7: [u64; 64],
+    // squares of the a1-d1-d4 triangle, those on the diagonal last
+    map_a1d1d4: [u64; 64],
+    // the 462 placements of two kings with the first in the triangle
+    map_kk: [[u64; 64]; 10],
+    // binomial[k][n] ways of choosing k of n
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+// how far above the a1-h8 diagonal the square is (negative below it)
+fn off_diagonal(square: u8) -> i32 {
+    (square >> 3) as i32 - (square & 7) as i32
+}
+
+fn rank_of(square: u8) -> u64 {
+    (square >> 3) as u64
+}
+
+fn flip_file(square: u8) -> u8 {
+    square ^ 7
+}
+
+fn flip_rank(square: u8) -> u8 {
+    square ^ 56
+}
+
+fn flip_diagonal(square: u8) -> u8 {
+    ((square >> 3) | (square << 3)) & 63
+}
+
+fn are_adjacent(a: u8, b: u8) -> bool {
+    let files = ((a & 7) as i32 - (b & 7) as i32).abs();
+    let ranks = ((a >> 3) as i32 - (b >> 3) as i32).abs();
+    files <= 1 && ranks <= 1
+}
+
+impl Indexing {
+    fn new() -> Indexing {
+        let mut indexing = Indexing {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                indexing.map_b1h1h7[square as usize] = code;
+                code += 1;
+            }
+        }
+        let mut code = 0;
+        let mut diagonal = vec![];
+        for square in 0..=27u8 {
+            if square & 7 > 3 {
+                continue;
+            }
+            if off_diagonal(square) < 0 {
+                indexing.map_a1d1d4[square as usize] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            indexing.map_a1d1d4[square as usize] = code;
+            code += 1;
+        }
+        // placements with both kings on the diagonal come last
+        let mut both_on_diagonal = vec![];
+        let mut code = 0;
+        for idx in 0..10 {
+            for first in 0..=27u8 {
+                let in_triangle = first & 7 <= 3 && off_diagonal(first) <= 0;
+                if !in_triangle || indexing.map_a1d1d4[first as usize] != idx {
+                    continue;
+                }
+                for second in 0..64u8 {
+                    if are_adjacent(first, second) {
+                        continue;
+                    }
+                    let (first_on, second_off) = (off_diagonal(first) == 0, off_diagonal(second));
+                    if first_on && second_off > 0 {
+                        continue;
+                    }
+                    if first_on && second_off == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        indexing.map_kk[idx as usize][second as usize] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            indexing.map_kk[idx as usize][second as usize] = code;
+            code += 1;
+        }
+        indexing.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6 {
+                let with = if k > 0 { indexing.binomial[k - 1][n - 1] } else { 0 };
+                let without = if k < n { indexing.binomial[k][n - 1] } else { 0 };
+                indexing.binomial[k][n] = with + without;
+            }
+        }
+        let mut available = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4u8 {
+                let mut idx = 0;
+                for rank in 1..7u8 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        indexing.map_pawns[square as usize] = available;
+                        indexing.map_pawns[flip_file(square) as usize] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    indexing.lead_pawn_idx[lead_pawns][square as usize] = idx;
+                    idx += indexing.binomial[lead_pawns - 1][indexing.map_pawns[square as usize] as usize];
+                }
+                indexing.lead_pawns_size[lead_pawns][file as usize] = idx;
+            }
+        }
+        indexing
+    }
+}
+
+fn indexing() -> &'static Indexing {
+    static INDEXING: OnceLock<Indexing> = OnceLock::new();
+    INDEXING.get_or_init(Indexing::new)
+}
+
+fn corrupt(what: &str) -> SyzygyError {
+    SyzygyError { reason: format!("Corrupt table: {}", what) }
+}
+
+// where a table is read from: a file on disk, or its bytes where there's no file system (as on the web)
+#[derive(Clone, Debug)]
+pub enum TableSource {
+    Path(PathBuf),
+    Bytes(String, Arc<[u8]>),
+}
+
+enum Contents {
+    File(Mutex<fs::File>),
+    Bytes(Arc<[u8]>),
+}
+
+// a table file, read from as it is probed
+struct TableFile {
+    name: String,
+    contents: Contents,
+    len: u64,
+}
+
+impl TableFile {
+    fn bytes(&self, offset: u64, len: u64) -> Result<Vec<u8>, SyzygyError> {
+        if offset.checked_add(len).is_none_or(|end| end > self.len) {
+            return Err(corrupt(&format!("{} ends at {} bytes", self.name, self.len)));
+        }
+        let file = match &self.contents {
+            Contents::File(file) => file,
+            Contents::Bytes(bytes) => return Ok(bytes[offset as usize..(offset + len) as usize].to_vec()),
+        };
+        let mut bytes = vec![0; len as usize];
+        let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .map_err(|err| SyzygyError { reason: format!("Couldn't read {}: {}", self.name, err) })?;
+        Ok(bytes)
+    }
+
+    fn u16_le(&self, offset: u64) -> Result<u16, SyzygyError> {
+        let bytes = self.bytes(offset, 2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+// reads the header of a file in order
+struct Cursor<'a> {
+    file: &'a TableFile,
+    offset: u64,
+}
+
+impl Cursor<'_> {
+    fn u8(&mut self) -> Result<u8, SyzygyError> {
+        let byte = self.file.bytes(self.offset, 1)?[0];
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn u16_le(&mut self) -> Result<u16, SyzygyError> {
+        let value = self.file.u16_le(self.offset)?;
+        self.offset += 2;
+        Ok(value)
+    }
+
+    fn u32_le(&mut self) -> Result<u32, SyzygyError> {
+        let bytes = self.file.bytes(self.offset, 4)?;
+        self.offset += 4;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn bytes(&mut self, len: u64) -> Result<Vec<u8>, SyzygyError> {
+        let bytes = self.file.bytes(self.offset, len)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self, to: u64) {
+        self.offset = self.offset.div_ceil(to) * to;
+    }
+}
+
+// What a table is of, from its name: e.g. KRPvKR has a white king, rook and pawn against a black king and rook.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub piece_count: usize,
+    pub has_pawns: bool,
+    // a piece other than the kings which is the only one of its kind and colour
+    pub has_unique_pieces: bool,
+    // the pawns of the side whose pawns are encoded first (the side with fewer, if both have any) and the other's
+    pub pawn_count: [usize; 2],
+    // both sides have the same pieces
+    pub symmetric: bool,
+}
+
+impl Material {
+    pub fn from_name(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let valid = |side: &str| {
+            side.starts_with('K')
+                && side.chars().filter(|&c| c == 'K').count() == 1
+                && side.chars().all(|c| "KQRBNP".contains(c))
+        };
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+        let count = |side: &str, piece: char| side.chars().filter(|&c| c == piece).count();
+        let has_unique_pieces = "QRBNP".chars().any(|piece| count(white, piece) == 1 || count(black, piece) == 1);
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(Material {
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            symmetric: white == black,
+        })
+    }
+}
+
+// one of a file's tables: for one side to move and, with pawns, one file of the leading pawn
+#[derive(Clone, Debug, Default)]
+struct Pairs {
+    flags: u8,
+    // the pieces in the order they are encoded, grouped
+    pieces: Vec<u8>,
+    group_len: Vec<usize>,
+    // the multiplier of each group's index; the last is the table's size
+    group_idx: Vec<u64>,
+    min_sym_len: u8,
+    block_size: u64,
+    span: u64,
+    sparse_index_size: u64,
+    num_blocks: u64,
+    block_length_size: u64,
+    lowest_sym: Vec<u16>,
+    base64: Vec<u64>,
+    // how many values (less one) each symbol stands for
+    symlen: Vec<u32>,
+    // the pair of symbols each symbol stands for, or its value when it is a leaf
+    btree: Vec<(u16, u16)>,
+    // where the sparse index, block lengths and compressed blocks start in the file
+    sparse_index: u64,
+    block_lengths: u64,
+    data: u64,
+    // where the DTZ values of wins, losses, cursed wins and blessed losses are mapped from
+    map_idx: [u64; 4],
+}
+
+impl Pairs {
+    fn size(&self) -> u64 {
+        *self.group_idx.last().unwrap_or(&0)
+    }
+
+    // Groups the pieces encoded together: three unique pieces (or the kings, when there aren't three) or the
+    // leading pawns first, then each run of the same piece.
+    fn set_groups(&mut self, material: &Material, order: [u8; 2], file: usize) -> Result<(), SyzygyError> {
+        let indexing = indexing();
+        let mut first_len: i32 = if material.has_pawns {
+            0
+        } else if material.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        self.group_len = vec![1];
+        for i in 1..self.pieces.len() {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                *self.group_len.last_mut().unwrap() += 1;
+            } else {
+                self.group_len.push(1);
+            }
+        }
+        let groups = self.group_len.len();
+        self.group_idx = vec![0; groups + 1];
+        // the groups' indices are combined in the order the file gives, which needn't be the order of the pieces
+        let pawns_on_both_sides = material.has_pawns && material.pawn_count[1] > 0;
+        let mut next = if pawns_on_both_sides { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_len[0] - if pawns_on_both_sides { self.group_len[1] } else { 0 };
+        let mut idx = 1u64;
+        let mut k = 0;
+        while next < groups || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                self.group_idx[0] = idx;
+                idx *= if material.has_pawns {
+                    indexing.lead_pawns_size[self.group_len[0]][file]
+                } else if material.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                self.group_idx[1] = idx;
+                idx *= indexing.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                if next >= groups {
+                    return Err(corrupt("group order"));
+                }
+                self.group_idx[next] = idx;
+                idx *= indexing.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_idx[groups] = idx;
+        Ok(())
+    }
+
+    // reads the sizes and the Huffman code of the table
+    fn set_sizes(&mut self, cursor: &mut Cursor, kind: Kind) -> Result<(), SyzygyError> {
+        self.flags = cursor.u8()?;
+        if self.flags & SINGLE_VALUE != 0 {
+            // every position has the same value; DTZ tables store nothing useful in the byte
+            let value = cursor.u8()?;
+            self.min_sym_len = if kind == Kind::Wdl { value } else { 0 };
+            return Ok(());
+        }
+        let block_bits = cursor.u8()?;
+        let span_bits = cursor.u8()?;
+        if block_bits > 16 || span_bits > 32 {
+            return Err(corrupt("block size"));
+        }
+        self.block_size = 1 << block_bits;
+        self.span = 1 << span_bits;
+        self.sparse_index_size = self.size().div_ceil(self.span);
+        let padding = cursor.u8()? as u64;
+        self.num_blocks = cursor.u32_le()? as u64;
+        self.block_length_size = self.num_blocks + padding;
+        let max_sym_len = cursor.u8()?;
+        self.min_sym_len = cursor.u8()?;
+        if max_sym_len < self.min_sym_len || max_sym_len > 64 {
+            return Err(corrupt("symbol lengths"));
+        }
+        let lengths = (max_sym_len - self.min_sym_len + 1) as usize;
+        self.lowest_sym = (0..lengths).map(|_| cursor.u16_le()).collect::<Result<_, _>>()?;
+        // The canonical code gives longer symbols lower values, so the lowest code of each length padded to 64
+        // bits tells the length of the code at the front of the bits being decoded.
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            self.base64[i] = (self.base64[i + 1]
+                .wrapping_add(self.lowest_sym[i] as u64)
+                .wrapping_sub(self.lowest_sym[i + 1] as u64))
+                / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - i as u32 - self.min_sym_len as u32).unwrap_or(0);
+        }
+        let symbols = cursor.u16_le()? as usize;
+        let tree = cursor.bytes(3 * symbols as u64)?;
+        self.btree = tree
+            .chunks(3)
+            .map(|lr| {
+                let left = ((lr[1] as u16 & 0xf) << 8) | lr[0] as u16;
+                let right = ((lr[2] as u16) << 4) | (lr[1] as u16 >> 4);
+                (left, right)
+            })
+            .collect();
+        if symbols & 1 == 1 {
+            cursor.u8()?;
+        }
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                self.symlen[symbol] = self.set_symlen(symbol, &mut visited)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_symlen(&mut self, symbol: usize, visited: &mut [bool]) -> Result<u32, SyzygyError> {
+        visited[symbol] = true;
+        let (left, right) = self.btree[symbol];
+        if right == 0xfff {
+            return Ok(0);
+        }
+        let (left, right) = (left as usize, right as usize);
+        if left >= self.btree.len() || right >= self.btree.len() {
+            return Err(corrupt("symbol tree"));
+        }
+        for child in [left, right] {
+            if !visited[child] {
+                self.symlen[child] = self.set_symlen(child, visited)?;
+            }
+        }
+        Ok(self.symlen[left] + self.symlen[right] + 1)
+    }
+
+    // the value stored at the index
+    fn decompress(&self, file: &TableFile, idx: u64) -> Result<u16, SyzygyError> {
+        if self.flags & SINGLE_VALUE != 0 {
+            return Ok(self.min_sym_len as u16);
+        }
+        if idx >= self.size() {
+            return Err(corrupt("index out of range"));
+        }
+        // the sparse index gives the block and offset of every span'th value, from which the block holding
+        // the value is found by walking the block lengths
+        let k = idx / self.span;
+        let entry = file.bytes(self.sparse_index + 6 * k, 6)?;
+        let mut block = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64;
+        let mut offset = u16::from_le_bytes([entry[4], entry[5]]) as i64;
+        offset += (idx % self.span) as i64 - (self.span / 2) as i64;
+        let block_length = |block: u64| -> Result<i64, SyzygyError> {
+            if block >= self.block_length_size {
+                return Err(corrupt("block out of range"));
+            }
+            Ok(file.u16_le(self.block_lengths + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or_else(|| corrupt("block out of range"))?;
+            offset += block_length(block)? + 1;
+        }
+        loop {
+            let length = block_length(block)?;
+            if offset <= length {
+                break;
+            }
+            offset -= length + 1;
+            block += 1;
+        }
+        if block >= self.num_blocks {
+            return Err(corrupt("block out of range"));
+        }
+        let data = file.bytes(self.data + block * self.block_size, self.block_size)?;
+        // big-endian words of the block, reading past its end as zeros
+        let word = |i: usize| -> u64 {
+            data.get(4 * i..4 * i + 4)
+                .map_or(0, |bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64)
+        };
+        let mut bits = (word(0) << 32) | word(1);
+        let mut next_word = 2;
+        let mut bits_left = 64i32;
+        let min_len = self.min_sym_len as u32;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while len + 1 < self.base64.len() && bits < self.base64[len] {
+                len += 1;
+            }
+            let code = (bits - self.base64[len]).checked_shr(64 - len as u32 - min_len).unwrap_or(0);
+            symbol = (code as usize).wrapping_add(self.lowest_sym[len] as usize);
+            let values = *self.symlen.get(symbol).ok_or_else(|| corrupt("symbol out of range"))? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            let consumed = len as u32 + min_len;
+            bits = bits.checked_shl(consumed).unwrap_or(0);
+            bits_left -= consumed as i32;
+            if bits_left <= 32 {
+                bits_left += 32;
+                bits |= word(next_word) << (64 - bits_left);
+                next_word += 1;
+            }
+        }
+        // the symbol stands for a run of values: follow the pairs down to the one wanted
+        while self.symlen[symbol] != 0 {
+            let (left, right) = self.btree[symbol];
+            let left_values = self.symlen[left as usize] as i64 + 1;
+            if offset < left_values {
+                symbol = left as usize;
+            } else {
+                offset -= left_values;
+                symbol = right as usize;
+            }
+        }
+        Ok(self.btree[symbol].0)
+    }
+}
+
+// the outcome of looking a position up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookup {
+    Value(i32),
+    // DTZ tables only hold one side to move
+    ChangeSideToMove,
+}
+
+// a file's tables
+pub struct Table {
+    kind: Kind,
+    material: Material,
+    file: TableFile,
+    // [side to move][file of the leading pawn, or just 0 without pawns]
+    pairs: Vec<Vec<Pairs>>,
+}
+
+impl Table {
+    pub fn open(source: &TableSource, kind: Kind, material: Material) -> Result<Table, SyzygyError> {
+        let file = match source {
+            TableSource::Path(path) => {
+                let name = path.display().to_string();
+                let file = fs::File::open(path)
+                    .map_err(|err| SyzygyError { reason: format!("Couldn't open {}: {}", name, err) })?;
+                let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                TableFile { name, contents: Contents::File(Mutex::new(file)), len }
+            }
+            TableSource::Bytes(name, bytes) => {
+                TableFile { name: name.clone(), contents: Contents::Bytes(bytes.clone()), len: bytes.len() as u64 }
+            }
+        };
+        let magic = if kind == Kind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if file.bytes(0, 4).ok().as_deref() != Some(&magic[..]) {
+            let kind = if kind == Kind::Wdl { "WDL" } else { "DTZ" };
+            return Err(SyzygyError { reason: format!("{} is not a Syzygy {} table", file.name, kind) });
+        }
+        let mut table = Table { kind, material, file, pairs: vec![] };
+        table.read_header()?;
+        Ok(table)
+    }
+
+    fn read_header(&mut self) -> Result<(), SyzygyError> {
+        let material = &self.material;
+        let mut cursor = Cursor { file: &self.file, offset: 4 };
+        let flags = cursor.u8()?;
+        if (flags & HAS_PAWNS != 0) != material.has_pawns {
+            return Err(corrupt(&format!("{} doesn't match its name", self.file.name)));
+        }
+        let sides = if self.kind == Kind::Wdl && flags & SPLIT != 0 { 2 } else { 1 };
+        let files = if material.has_pawns { 4 } else { 1 };
+        let pawns_on_both_sides = material.has_pawns && material.pawn_count[1] > 0;
+        let mut pairs = vec![vec![Pairs::default(); files]; sides];
+        for file in 0..files {
+            let first = cursor.u8()?;
+            let second = if pawns_on_both_sides { cursor.u8()? } else { 0xff };
+            let orders = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            let pieces = cursor.bytes(material.piece_count as u64)?;
+            for (side, side_pairs) in pairs.iter_mut().enumerate() {
+                let table = &mut side_pairs[file];
+                table.pieces = pieces.iter().map(|byte| if side == 0 { byte & 0xf } else { byte >> 4 }).collect();
+                if table.pieces.iter().any(|&piece| piece & 7 == 0 || piece & 7 > 6) {
+                    return Err(corrupt("pieces"));
+                }
+                table.set_groups(material, orders[side], file)?;
+            }
+        }
+        cursor.align(2);
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                side_pairs[file].set_sizes(&mut cursor, self.kind)?;
+            }
+        }
+        if self.kind == Kind::Dtz {
+            for table in pairs[0].iter_mut() {
+                if table.flags & MAPPED == 0 {
+                    continue;
+                }
+                if table.flags & WIDE != 0 {
+                    cursor.align(2);
+                    for i in 0..4 {
+                        table.map_idx[i] = cursor.offset + 2;
+                        let len = cursor.u16_le()? as u64;
+                        cursor.offset += 2 * len;
+                    }
+                } else {
+                    for i in 0..4 {
+                        table.map_idx[i] = cursor.offset + 1;
+                        let len = cursor.u8()? as u64;
+                        cursor.offset += len;
+                    }
+                }
+            }
+            cursor.align(2);
+        }
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                side_pairs[file].sparse_index = cursor.offset;
+                cursor.offset += 6 * side_pairs[file].sparse_index_size;
+            }
+        }
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                side_pairs[file].block_lengths = cursor.offset;
+                cursor.offset += 2 * side_pairs[file].block_length_size;
+            }
+        }
+        // the blocks start 64-byte aligned, though tables with none needn't be padded up to it
+        let mut end = cursor.offset;
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                cursor.align(64);
+                side_pairs[file].data = cursor.offset;
+                if side_pairs[file].num_blocks > 0 {
+                    cursor.offset += side_pairs[file].num_blocks * side_pairs[file].block_size;
+                    end = cursor.offset;
+                }
+            }
+        }
+        if end > self.file.len {
+            return Err(corrupt(&format!("{} is truncated", self.file.name)));
+        }
+        self.pairs = pairs;
+        Ok(())
+    }
+
+    // The value of the position given as the square (0 for a1 to 63 for h8) and code of every piece, White to
+    // move or not, and whether the pieces are the other way round to the table's name (e.g. KvKR for KRvK). A
+    // WDL value is -2 (loss) to 2 (win); a DTZ value is the plies to a capture or pawn move, given the WDL value.
+    pub fn probe(
+        &self,
+        pieces: &[(u8, u8)],
+        white_to_move: bool,
+        mirrored: bool,
+        wdl: i32,
+    ) -> Result<Lookup, SyzygyError> {
+        let (side, file, idx) = match self.locate(pieces, white_to_move, mirrored)? {
+            Some(located) => located,
+            None => return Ok(Lookup::ChangeSideToMove),
+        };
+        let value = self.pairs[side % self.pairs.len()][file].decompress(&self.file, idx)? as i32;
+        Ok(Lookup::Value(match self.kind {
+            Kind::Wdl => value - 2,
+            Kind::Dtz => self.map_dtz(file, value, wdl)?,
+        }))
+    }
+
+    // the side to move's table, the leading pawn's file and the position's index in the table, or none when a
+    // DTZ table doesn't hold the side to move
+    fn locate(
+        &self,
+        pieces: &[(u8, u8)],
+        white_to_move: bool,
+        mirrored: bool,
+    ) -> Result<Option<(usize, usize, u64)>, SyzygyError> {
+        let indexing = indexing();
+        let material = &self.material;
+        // symmetric tables only hold White to move
+        let flip = mirrored || (material.symmetric && !white_to_move);
+        let (flip_color, flip_squares) = if flip { (BLACK, 56) } else { (0, 0) };
+        let side = (flip == white_to_move) as usize;
+        let mut squares = Vec::with_capacity(pieces.len());
+        let mut codes = Vec::with_capacity(pieces.len());
+        let mut file = 0;
+        let mut lead_pawns = 0;
+        if material.has_pawns {
+            // the leading pawns are the ones encoded first; of them the one nearest the edge and then the lowest
+            // rank leads, and decides which file's table is used
+            let lead = self.pairs[0][0].pieces[0] ^ flip_color;
+            for &(square, code) in pieces.iter().filter(|(_, code)| *code == lead) {
+                squares.push(square ^ flip_squares);
+                codes.push(code ^ flip_color);
+            }
+            lead_pawns = squares.len();
+            let leader = (0..lead_pawns).max_by_key(|&i| indexing.map_pawns[squares[i] as usize]);
+            squares.swap(0, leader.ok_or_else(|| corrupt("no leading pawn"))?);
+            file = (squares[0] & 7) as usize;
+            if file > 3 {
+                file = 7 - file;
+            }
+        }
+        let table = &self.pairs[side % self.pairs.len()][file];
+        // symmetric tables without pawns hold both sides to move
+        let both_sides = material.symmetric && !material.has_pawns;
+        if self.kind == Kind::Dtz && (table.flags & STM) as usize != side && !both_sides {
+            return Ok(None);
+        }
+        let lead_code = if material.has_pawns { Some(self.pairs[0][0].pieces[0] ^ flip_color) } else { None };
+        for &(square, code) in pieces.iter().filter(|(_, code)| Some(*code) != lead_code) {
+            squares.push(square ^ flip_squares);
+            codes.push(code ^ flip_color);
+        }
+        if squares.len() != table.pieces.len() {
+            return Err(SyzygyError { reason: "The position doesn't match the table".to_string() });
+        }
+        // put the pieces in the order the table encodes them
+        for i in lead_pawns..codes.len().saturating_sub(1) {
+            if let Some(j) = (i..codes.len()).find(|&j| codes[j] == table.pieces[i]) {
+                codes.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        if codes != table.pieces {
+            return Err(SyzygyError { reason: "The position doesn't match the table".to_string() });
+        }
+        if squares[0] & 7 > 3 {
+            squares.iter_mut().for_each(|square| *square = flip_file(*square));
+        }
+        let mut idx;
+        if material.has_pawns {
+            idx = indexing.lead_pawn_idx[lead_pawns][squares[0] as usize];
+            squares[1..lead_pawns].sort_by_key(|&square| indexing.map_pawns[square as usize]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += indexing.binomial[i][indexing.map_pawns[square as usize] as usize];
+            }
+        } else {
+            if squares[0] >> 3 > 3 {
+                squares.iter_mut().for_each(|square| *square = flip_rank(*square));
+            }
+            // the first piece of the leading group off the diagonal is brought below it
+            if let Some(i) = (0..table.group_len[0]).find(|&i| off_diagonal(squares[i]) != 0) {
+                if off_diagonal(squares[i]) > 0 {
+                    squares[i..].iter_mut().for_each(|square| *square = flip_diagonal(*square));
+                }
+            }
+            idx = if material.has_unique_pieces {
+                let [s0, s1, s2] = [squares[0], squares[1], squares[2]];
+                let adjust1 = (s1 > s0) as u64;
+                let adjust2 = (s2 > s0) as u64 + (s2 > s1) as u64;
+                if off_diagonal(s0) != 0 {
+                    (indexing.map_a1d1d4[s0 as usize] * 63 + (s1 as u64 - adjust1)) * 62 + s2 as u64 - adjust2
+                } else if off_diagonal(s1) != 0 {
+                    (6 * 63 + rank_of(s0) * 28 + indexing.map_b1h1h7[s1 as usize]) * 62 + s2 as u64 - adjust2
+                } else if off_diagonal(s2) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank_of(s0) * 7 * 28
+                        + (rank_of(s1) - adjust1) * 28
+                        + indexing.map_b1h1h7[s2 as usize]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank_of(s0) * 7 * 6 + (rank_of(s1) - adjust1) * 6
+                        + (rank_of(s2) - adjust2)
+                }
+            } else {
+                indexing.map_kk[indexing.map_a1d1d4[squares[0] as usize] as usize][squares[1] as usize]
+            };
+        }
+        idx *= table.group_idx[0];
+        // the other groups by square, each counting only the squares left free by the groups before
+        let mut remaining_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut start = table.group_len[0];
+        for (group, &len) in table.group_len.iter().enumerate().skip(1) {
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&other| square > other).count() as u64;
+                let free = square as u64 - adjust - if remaining_pawns { 8 } else { 0 };
+                n += indexing.binomial[i + 1][free as usize];
+            }
+            remaining_pawns = false;
+            idx += n * table.group_idx[group];
+            start += len;
+        }
+        Ok(Some((side % self.pairs.len(), file, idx)))
+    }
+
+    // DTZ values are stored by frequency within each result, and in moves rather than plies where that's exact
+    fn map_dtz(&self, file: usize, value: i32, wdl: i32) -> Result<i32, SyzygyError> {
+        let table = &self.pairs[0][file];
+        let mut value = value;
+        if table.flags & MAPPED != 0 {
+            let map = table.map_idx[[1, 3, 0, 2, 0][(wdl + 2) as usize]];
+            value = if table.flags & WIDE != 0 {
+                self.file.u16_le(map + 2 * value as u64)? as i32
+            } else {
+                self.file.bytes(map + value as u64, 1)?[0] as i32
+            };
+        }
+        let in_plies = match wdl {
+            2 => table.flags & WIN_PLIES != 0,
+            -2 => table.flags & LOSS_PLIES != 0,
+            _ => false,
+        };
+        if !in_plies {
+            value *= 2;
+        }
+        Ok(value + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn king_placements_are_numbered_once() {
+        let indexing = indexing();
+        let mut seen = vec![false; 462];
+        for first in 0..10 {
+            for second in 0..64 {
+                let code = indexing.map_kk[first][second] as usize;
+                if code != 0 || (first == 0 && second == 0) {
+                    assert!(code < 462);
+                }
+                seen[code] = true;
+            }
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        assert_eq!(indexing.binomial[2][5], 10);
+        assert_eq!(indexing.lead_pawns_size[1].iter().sum::<u64>(), 24);
+    }
+
+    #[test]
+    fn names_give_the_material() {
+        let material = Material::from_name("KRPvKR").unwrap();
+        assert_eq!(material.piece_count, 5);
+        assert!(material.has_pawns && material.has_unique_pieces && !material.symmetric);
+        assert_eq!(material.pawn_count, [1, 0]);
+        assert_eq!(Material::from_name("KPvKPP").unwrap().pawn_count, [1, 2]);
+        assert!(Material::from_name("KNNvKNN").unwrap().symmetric);
+        assert_eq!(Material::from_name("KQvQ"), None);
+        assert_eq!(Material::from_name("KQQQQvKQQ"), None);
+    }
+
+    // the value stored for each position of the synthetic table
+    fn value(idx: u64) -> u8 {
+        ((idx * 7 + idx / 3) % 5) as u8
+    }
+
+    // A KBvK WDL file with White to move stored as 3-bit codes, 85 to a 32-byte block, and every position with
+    // Black to move a draw.
+    fn kbvk() -> Vec<u8> {
+        const SIZE: u64 = 31332;
+        const PER_BLOCK: u64 = 85;
+        let blocks = SIZE.div_ceil(PER_BLOCK);
+        let mut file = WDL_MAGIC.to_vec();
+        file.push(SPLIT);
+        // the group order, then the pieces (king, king, bishop) for each side to move in a nibble
+        file.extend([0x00, 0x66, 0xee, 0x33]);
+        file.resize(file.len().div_ceil(2) * 2, 0);
+        // White to move: blocks of 2^5 bytes, a sparse entry every 2^6 positions, and codes 0 to 4 of 3 bits
+        // standing for the values themselves
+        file.extend([0, 5, 6, 0]);
+        file.extend((blocks as u32).to_le_bytes());
+        file.extend([3, 3, 0, 0, 5, 0]);
+        for symbol in 0..5 {
+            file.extend([symbol, 0xf0, 0xff]);
+        }
+        file.push(0);
+        // Black to move: a single value
+        file.extend([SINGLE_VALUE, 2]);
+        for k in 0..SIZE.div_ceil(64) {
+            let position = k * 64 + 32;
+            file.extend(((position / PER_BLOCK) as u32).to_le_bytes());
+            file.extend(((position % PER_BLOCK) as u16).to_le_bytes());
+        }
+        for block in 0..blocks {
+            let values = PER_BLOCK.min(SIZE - block * PER_BLOCK);
+            file.extend(((values - 1) as u16).to_le_bytes());
+        }
+        file.resize(file.len().div_ceil(64) * 64, 0);
+        for block in 0..blocks {
+            let mut bytes = [0u8; 32];
+            for i in 0..PER_BLOCK.min(SIZE - block * PER_BLOCK) {
+                let code = value(block * PER_BLOCK + i);
+                for bit in 0..3 {
+                    if code >> (2 - bit) & 1 == 1 {
+                        let at = (i * 3 + bit) as usize;
+                        bytes[at / 8] |= 0x80 >> (at % 8);
+                    }
+                }
+            }
+            file.extend(bytes);
+        }
+        file
+    }
+
+    fn open_kbvk(name: &str) -> Table {
+        let path = std::env::temp_dir().join(format!("narmi-{}-{}.rtbw", name, std::process::id()));
+        fs::write(&path, kbvk()).unwrap();
+        let table = Table::open(&TableSource::Path(path.clone()), Kind::Wdl, Material::from_name("KBvK").unwrap());
+        fs::remove_file(&path).unwrap();
+        table.unwrap()
+    }
+
+    #[test]
+    fn values_are_decompressed() {
+        let table = open_kbvk("decompress");
+        assert_eq!(table.pairs.len(), 2);
+        assert_eq!(table.pairs[0][0].size(), 31332);
+        for idx in (0..31332).step_by(7).chain([0, 84, 85, 86, 31331]) {
+            assert_eq!(table.pairs[0][0].decompress(&table.file, idx).unwrap(), value(idx) as u16, "at {}", idx);
+        }
+        assert!(table.pairs[0][0].decompress(&table.file, 31332).is_err());
+        assert_eq!(table.pairs[1][0].decompress(&table.file, 1000).unwrap(), 2);
+    }
+
+    #[test]
+    fn positions_are_probed() {
+        let table = open_kbvk("probe");
+        // White: king e1, bishop c1; Black: king e8
+        let pieces = [(4, 6), (2, 3), (60, 14)];
+        let (_, _, idx) = table.locate(&pieces, true, false).unwrap().unwrap();
+        assert_eq!(table.probe(&pieces, true, false, 0).unwrap(), Lookup::Value(value(idx) as i32 - 2));
+        assert_eq!(table.probe(&pieces, false, false, 0).unwrap(), Lookup::Value(0));
+        // the same position reflected left to right, and with the colours swapped
+        assert_eq!(table.locate(&[(3, 6), (5, 3), (59, 14)], true, false), Ok(Some((0, 0, idx))));
+        assert_eq!(table.locate(&[(60, 14), (58, 11), (4, 6)], false, true), Ok(Some((0, 0, idx))));
+    }
+
+    #[test]
+    fn indices_are_within_the_table() {
+        let table = open_kbvk("indices");
+        for king in (0..64).step_by(3) {
+            for other_king in 0..64 {
+                for bishop in (0..64).step_by(5) {
+                    if king == other_king || bishop == king || bishop == other_king {
+                        continue;
+                    }
+                    let pieces = [(king, 6), (bishop, 3), (other_king, 14)];
+                    let (side, _, idx) = table.locate(&pieces, true, false).unwrap().unwrap();
+                    assert_eq!(side, 0);
+                    assert!(idx < 31332, "{:?}", pieces);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let path = std::env::temp_dir().join(format!("narmi-not-a-table-{}.rtbw", std::process::id()));
+        fs::write(&path, b"not a table").unwrap();
+        let err = Table::open(&TableSource::Path(path.clone()), Kind::Wdl, Material::from_name("KBvK").unwrap());
+        fs::remove_file(&path).unwrap();
+        let err = err.err().unwrap();
+        assert!(err.reason.ends_with("is not a Syzygy WDL table"), "{}", err.reason);
+        let mut file = kbvk();
+        file.truncate(file.len() - 100);
+        let source = TableSource::Bytes("KBvK.rtbw".to_string(), file.into());
+        let err = Table::open(&source, Kind::Wdl, Material::from_name("KBvK").unwrap()).err().unwrap();
+        assert!(err.reason.ends_with("is truncated"), "{}", err.reason);
+    }
+}
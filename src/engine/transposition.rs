@@ -0,0 +1,189 @@
+// A fixed-size table of positions already searched, keyed by Zobrist hash, so that a position reached
+// again (by a different move order, or in the next iteration) needn't be searched again, and so that the
+// best move found for it last time can be tried first.
+
+use std::mem::size_of;
+
+use super::search::{MATE, MAX_PLY};
+use crate::game::chess_move::*;
+
+// what the stored score says about the position's true score
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // the search failed high: the score is at least this
+    Lower,
+    // the search failed low: the score is at most this
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+    // the search (counted since the table was created) which stored the entry
+    generation: u8,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    generation: u8,
+}
+
+// Mate scores are stored relative to the position rather than the root, as the same position can be reached
+// at different distances from the root.
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -(MATE - MAX_PLY as i32) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -(MATE - MAX_PLY as i32) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+impl TranspositionTable {
+    // as many entries as fit in the size given (rounded down to a power of two, and at least one)
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let wanted = (size_mb * 1024 * 1024 / size_of::<Option<Entry>>()).max(1);
+        let count = if wanted.is_power_of_two() { wanted } else { wanted.next_power_of_two() / 2 };
+        TranspositionTable {
+            entries: vec![None; count],
+            generation: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(Option::is_none)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.generation = 0;
+    }
+
+    // entries stored by earlier searches give way to those of the current one
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key & (self.entries.len() as u64 - 1)) as usize
+    }
+
+    // the entry for the position, with any mate score made relative to the ply it's probed from
+    pub fn probe(&self, key: u64, ply: usize) -> Option<Entry> {
+        self.entries[self.index(key)]
+            .filter(|entry| entry.key == key)
+            .map(|entry| Entry {
+                score: score_from_table(entry.score, ply),
+                ..entry
+            })
+    }
+
+    // An entry is replaced by one for a different position if it's from an earlier search or the new one
+    // searched at least as deep; the same position always takes the newer result (keeping the best move if
+    // the new one has none).
+    pub fn store(&mut self, key: u64, depth: i32, score: i32, bound: Bound, best_move: Option<Move>, ply: usize) {
+        let index = self.index(key);
+        let generation = self.generation;
+        let best_move = match self.entries[index] {
+            Some(existing) if existing.key == key => best_move.or(existing.best_move),
+            Some(existing) if existing.generation == generation && existing.depth > depth => return,
+            _ => best_move,
+        };
+        self.entries[index] = Some(Entry {
+            key,
+            best_move,
+            score: score_to_table(score, ply),
+            depth,
+            bound,
+            generation,
+        });
+    }
+
+    // how full the table is, in parts per thousand, judged from a sample
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| entry.is_some_and(|entry| entry.generation == self.generation))
+            .count();
+        (used * 1000 / sample) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::square::Square;
+
+    #[test]
+    fn the_table_size_is_a_power_of_two() {
+        let table = TranspositionTable::new(1);
+        assert!(table.len().is_power_of_two());
+        assert!(table.len() * size_of::<Option<Entry>>() <= 1024 * 1024);
+        assert_eq!(TranspositionTable::new(0).len(), 1);
+    }
+
+    #[test]
+    fn stored_entries_are_found_by_key() {
+        let mut table = TranspositionTable::new(1);
+        let best = Some(Move::new(Square::E2, Square::E4));
+        table.store(42, 3, 25, Bound::Exact, best, 0);
+        let entry = table.probe(42, 0).unwrap();
+        assert_eq!((entry.depth, entry.score, entry.bound, entry.best_move), (3, 25, Bound::Exact, best));
+        assert_eq!(table.probe(42 + table.len() as u64, 0), None);
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_the_position() {
+        let mut table = TranspositionTable::new(1);
+        // mate found 3 plies below a position stored at ply 5
+        table.store(7, 4, MATE - 8, Bound::Exact, None, 5);
+        assert_eq!(table.probe(7, 5).unwrap().score, MATE - 8);
+        // reached at ply 1 by another route, the same mate is 4 plies nearer the root
+        assert_eq!(table.probe(7, 1).unwrap().score, MATE - 4);
+    }
+
+    #[test]
+    fn deeper_entries_of_the_current_search_are_kept() {
+        let mut table = TranspositionTable::new(0);
+        table.store(1, 6, 10, Bound::Exact, None, 0);
+        table.store(2, 2, 20, Bound::Exact, None, 0);
+        assert_eq!(table.probe(1, 0).map(|entry| entry.depth), Some(6));
+        // an entry from an earlier search is replaced whatever its depth
+        table.new_search();
+        table.store(2, 2, 20, Bound::Exact, None, 0);
+        assert_eq!(table.probe(2, 0).map(|entry| entry.depth), Some(2));
+    }
+
+    #[test]
+    fn the_best_move_is_kept_when_the_new_result_has_none() {
+        let mut table = TranspositionTable::new(1);
+        let best = Some(Move::new(Square::G1, Square::F3));
+        table.store(9, 2, 0, Bound::Lower, best, 0);
+        table.store(9, 3, -5, Bound::Upper, None, 0);
+        assert_eq!(table.probe(9, 0).unwrap().best_move, best);
+    }
+}
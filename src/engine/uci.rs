@@ -0,0 +1,588 @@
+// The Universal Chess Interface, which chess GUIs use to talk to engines: commands arrive a line at a time
+// and replies are sent down a channel a line at a time, leaving it to the caller to decide where they go
+// (stdout for narmi-uci). Searches run on a thread of their own so that `stop` and `isready` are answered
+// while the engine is thinking.
+//
+// See http://wbec-ridderkerk.nl/html/UCIProtocol.html
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::book::*;
+use super::search::*;
+use super::syzygy::*;
+use crate::game::board::*;
+use crate::game::color::Color;
+use crate::game::fen;
+use crate::game::notation::*;
+
+pub const NAME: &str = "narmi";
+pub const AUTHORS: &str = "Andrew Gibson, Imran Munir";
+
+const MAX_HASH_MB: usize = 1024;
+// time kept back on every move for the GUI to receive it, as otherwise an engine can lose on time with
+// the move already chosen
+const MOVE_OVERHEAD_MS: u64 = 50;
+// the number of moves the remaining time is shared between when the GUI doesn't say
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+// the arguments of a `go` command
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GoParams {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    // anything not understood is skipped over
+    pub fn parse(args: &[&str]) -> GoParams {
+        let mut params = GoParams::default();
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let mut value = || args.next().and_then(|value| value.parse::<u64>().ok());
+            match arg {
+                "depth" => params.depth = value().map(|depth| depth as u32),
+                "nodes" => params.nodes = value(),
+                "movetime" => params.movetime = value(),
+                "wtime" => params.wtime = value(),
+                "btime" => params.btime = value(),
+                "winc" => params.winc = value(),
+                "binc" => params.binc = value(),
+                "movestogo" => params.movestogo = value(),
+                "infinite" => params.infinite = true,
+                _ => {}
+            }
+        }
+        params
+    }
+
+    // With a clock the search gets an even share of the time left over the moves still to go, plus most of
+    // the increment. GUIs may send a negative time once the flag has fallen, which doesn't parse and so
+    // is treated as no clock at all.
+    pub fn limits(&self, side_to_move: Color) -> SearchLimits {
+        let (time, increment) = match side_to_move {
+            Color::White => (self.wtime, self.winc),
+            Color::Black => (self.btime, self.binc),
+        };
+        let movetime = self.movetime.or_else(|| {
+            time.map(|time| {
+                let share = time / self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1)
+                    + increment.unwrap_or(0) * 3 / 4;
+                share.min(time.saturating_sub(MOVE_OVERHEAD_MS)).max(1)
+            })
+        });
+        SearchLimits {
+            depth: self.depth,
+            nodes: self.nodes,
+            movetime_ms: if self.infinite { None } else { movetime },
+        }
+    }
+}
+
+fn info_line(board: &Board, info: &SearchInfo, castling: UciCastling) -> String {
+    let score = match mate_in(info.score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score),
+    };
+    let mut line = format!(
+        "info depth {} score {} nodes {} nps {} time {} hashfull {}",
+        info.depth,
+        score,
+        info.nodes,
+        info.nodes * 1000 / info.time_ms.max(1),
+        info.time_ms,
+        info.hashfull
+    );
+    // there's no line to give when the game is already over
+    if !info.pv.is_empty() {
+        line.push_str(" pv ");
+        line.push_str(&encode_uci_line(board, &info.pv, castling).join(" "));
+    }
+    line
+}
+
+fn bestmove_line(board: &Board, result: &SearchResult, castling: UciCastling) -> String {
+    let moves = encode_uci_line(board, result.principal_variation(), castling);
+    match moves.as_slice() {
+        [] => "bestmove 0000".to_string(),
+        [best] => format!("bestmove {}", best),
+        [best, ponder, ..] => format!("bestmove {} ponder {}", best, ponder),
+    }
+}
+
+struct RunningSearch {
+    // set by `stop`, which the search passes on to the engine after each iteration in case the engine
+    // hadn't started yet when it was set (starting a search clears the engine's own flag)
+    stopped: Arc<AtomicBool>,
+    engine_stop: Arc<AtomicBool>,
+    thread: JoinHandle<Engine>,
+}
+
+pub struct Uci {
+    out: Sender<String>,
+    board: Board,
+    // king-takes-rook when the GUI has asked for Chess960
+    castling: UciCastling,
+    // lent to the search thread while it runs
+    engine: Option<Engine>,
+    search: Option<RunningSearch>,
+    // the Polyglot book played from when OwnBook is on
+    book: Option<Book>,
+    own_book: bool,
+    // the Syzygy tables played from once few enough pieces are left
+    tablebases: Option<Tablebases>,
+}
+
+impl Uci {
+    pub fn new(out: Sender<String>) -> Uci {
+        Uci {
+            out,
+            board: Board::new(),
+            castling: UciCastling::KingMove,
+            engine: Some(Engine::new()),
+            search: None,
+            book: None,
+            own_book: false,
+            tablebases: None,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn send(&self, line: String) {
+        // with no one listening there's nowhere to report the failure either
+        let _ = self.out.send(line);
+    }
+
+    // handles one line of input, returning false once the engine should quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+        match command {
+            "uci" => self.uci(),
+            "isready" => self.send("readyok".to_string()),
+            "ucinewgame" => {
+                self.stop();
+                self.engine_mut().clear();
+                self.board = Board::new();
+            }
+            "position" => {
+                self.stop();
+                self.position(args);
+            }
+            "go" => {
+                self.stop();
+                self.go(GoParams::parse(args));
+            }
+            "stop" => self.stop(),
+            "setoption" => {
+                self.stop();
+                self.set_option(args);
+            }
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            // debug, register, ponderhit and anything unknown are ignored, as the protocol asks
+            _ => {}
+        }
+        true
+    }
+
+    fn uci(&self) {
+        self.send(format!("id name {} {}", NAME, env!("CARGO_PKG_VERSION")));
+        self.send(format!("id author {}", AUTHORS));
+        self.send(format!(
+            "option name Hash type spin default {} min 1 max {}",
+            SearchOptions::default().hash_mb,
+            MAX_HASH_MB
+        ));
+        self.send("option name Clear Hash type button".to_string());
+        self.send("option name UCI_Chess960 type check default false".to_string());
+        self.send("option name OwnBook type check default false".to_string());
+        self.send("option name Book File type string default <empty>".to_string());
+        self.send("option name SyzygyPath type string default <empty>".to_string());
+        self.send("uciok".to_string());
+    }
+
+    fn engine_mut(&mut self) -> &mut Engine {
+        self.engine.as_mut().expect("the engine is only lent out while searching")
+    }
+
+    // position [startpos | fen <fen>] [moves <move> ...]
+    fn position(&mut self, args: &[&str]) {
+        let moves_at = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+        let board = match args.split_first() {
+            Some((&"startpos", _)) => Ok(Board::new()),
+            Some((&"fen", fen)) => fen::decode(&fen[..moves_at - 1].join(" ")).map_err(|err| err.reason),
+            _ => Err("expected startpos or fen".to_string()),
+        };
+        let mut board = match board {
+            Ok(board) => board,
+            Err(reason) => return self.send(format!("info string invalid position: {}", reason)),
+        };
+        for &text in args.iter().skip(moves_at + 1) {
+            match resolve_uci(&board, text) {
+                Ok(chess_move) => board.make_move(chess_move),
+                Err(err) => {
+                    self.send(format!("info string {}", err.reason));
+                    break;
+                }
+            }
+        }
+        self.board = board;
+    }
+
+    // a move from the book, chosen at random by weight, when the engine is to play from one
+    fn book_move(&self) -> Option<String> {
+        let book = self.book.as_ref().filter(|_| self.own_book)?;
+        let roll = RandomState::new().build_hasher().finish();
+        let chess_move = book.choose(&self.board, roll)?;
+        Some(encode_uci(&self.board, &chess_move, self.castling))
+    }
+
+    // the move keeping the tablebases' verdict, when the position is in them
+    fn tablebase_move(&self) -> Option<String> {
+        let tablebases = self.tablebases.as_ref().filter(|tablebases| tablebases.covers(&self.board))?;
+        match tablebases.verdict(&self.board) {
+            Ok(verdict) => {
+                let chess_move = verdict.best_move?;
+                self.send(format!("info string tablebase {}", verdict));
+                Some(encode_uci(&self.board, &chess_move, self.castling))
+            }
+            Err(err) => {
+                self.send(format!("info string tablebase: {}", err.reason));
+                None
+            }
+        }
+    }
+
+    fn go(&mut self, params: GoParams) {
+        if !params.infinite {
+            if let Some(chess_move) = self.book_move().or_else(|| self.tablebase_move()) {
+                return self.send(format!("bestmove {}", chess_move));
+            }
+        }
+        let mut engine = self.engine.take().expect("the engine is only lent out while searching");
+        let board = self.board.clone();
+        let limits = params.limits(board.side_to_move());
+        let out = self.out.clone();
+        let castling = self.castling;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let engine_stop = engine.stop_handle();
+        let thread = {
+            let stopped = stopped.clone();
+            let engine_stop = engine_stop.clone();
+            thread::spawn(move || {
+                let result = engine.search_with(&board, &limits, |info| {
+                    let _ = out.send(info_line(&board, info, castling));
+                    if stopped.load(Ordering::Relaxed) {
+                        engine_stop.store(true, Ordering::Relaxed);
+                    }
+                });
+                // an infinite search mustn't answer until it's told to stop, even with nothing left to search
+                while params.infinite && !stopped.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                let _ = out.send(bestmove_line(&board, &result, castling));
+                engine
+            })
+        };
+        self.search = Some(RunningSearch {
+            stopped,
+            engine_stop,
+            thread,
+        });
+    }
+
+    // ends any search in progress, which sends its best move before the engine is handed back
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stopped.store(true, Ordering::Relaxed);
+            search.engine_stop.store(true, Ordering::Relaxed);
+            match search.thread.join() {
+                Ok(engine) => self.engine = Some(engine),
+                Err(_) => {
+                    self.send("info string the search failed".to_string());
+                    self.engine = Some(Engine::new());
+                }
+            }
+        }
+    }
+
+    // setoption name <id> [value <x>]
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args.iter().position(|&arg| arg == "value").unwrap_or(args.len());
+        let name = args.get(1..value_at).unwrap_or(&[]).join(" ").to_lowercase();
+        let value = args.get(value_at + 1..).unwrap_or(&[]).join(" ");
+        match name.as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(hash_mb) => {
+                    let options = SearchOptions {
+                        hash_mb: hash_mb.clamp(1, MAX_HASH_MB),
+                        ..self.engine_mut().options()
+                    };
+                    self.engine_mut().set_options(options);
+                }
+                Err(_) => self.send(format!("info string invalid Hash value: {}", value)),
+            },
+            "clear hash" => self.engine_mut().clear(),
+            "uci_chess960" => match value.as_str() {
+                "true" => self.castling = UciCastling::KingTakesRook,
+                "false" => self.castling = UciCastling::KingMove,
+                _ => self.send(format!("info string invalid UCI_Chess960 value: {}", value)),
+            },
+            "ownbook" => match value.as_str() {
+                "true" => self.own_book = true,
+                "false" => self.own_book = false,
+                _ => self.send(format!("info string invalid OwnBook value: {}", value)),
+            },
+            "book file" if value.is_empty() || value == "<empty>" => self.book = None,
+            "book file" => match fs::read(&value).map_err(|err| err.to_string()) {
+                Ok(bytes) => match Book::from_bytes(&bytes) {
+                    Ok(book) => self.book = Some(book),
+                    Err(err) => self.send(format!("info string invalid book {}: {}", value, err.reason)),
+                },
+                Err(reason) => self.send(format!("info string couldn't read {}: {}", value, reason)),
+            },
+            "syzygypath" if value.is_empty() || value == "<empty>" => self.tablebases = None,
+            "syzygypath" => match Tablebases::open(&value) {
+                Ok(tablebases) => {
+                    self.send(format!("info string found {} tablebases", tablebases.len()));
+                    self.tablebases = Some(tablebases);
+                }
+                Err(err) => self.send(format!("info string {}", err.reason)),
+            },
+            _ => self.send(format!("info string unknown option: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::mpsc::{channel, Receiver};
+
+    fn uci() -> (Uci, Receiver<String>) {
+        let (sender, receiver) = channel();
+        (Uci::new(sender), receiver)
+    }
+
+    // everything sent up to and including the best move
+    fn until_bestmove(receiver: &Receiver<String>) -> Vec<String> {
+        let mut lines = vec![];
+        loop {
+            let line = receiver.recv_timeout(Duration::from_secs(30)).expect("no best move");
+            let done = line.starts_with("bestmove");
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    #[test]
+    fn the_engine_identifies_itself() {
+        let (mut uci, receiver) = uci();
+        uci.handle("uci");
+        let lines: Vec<String> = receiver.try_iter().collect();
+        assert!(lines[0].starts_with("id name narmi"));
+        assert!(lines.iter().any(|line| line.starts_with("option name Hash type spin")));
+        assert_eq!(lines.last().unwrap(), "uciok");
+        uci.handle("isready");
+        assert_eq!(receiver.try_recv().unwrap(), "readyok");
+    }
+
+    #[test]
+    fn go_params() {
+        let params = GoParams::parse(&["wtime", "60000", "btime", "30000", "winc", "1000", "depth", "7", "nonsense"]);
+        assert_eq!(params.wtime, Some(60000));
+        assert_eq!(params.btime, Some(30000));
+        assert_eq!(params.winc, Some(1000));
+        assert_eq!(params.depth, Some(7));
+        assert_eq!(params.limits(Color::White).movetime_ms, Some(60000 / 30 + 750));
+        assert_eq!(params.limits(Color::Black).movetime_ms, Some(1000));
+        assert_eq!(GoParams::parse(&["movetime", "250"]).limits(Color::Black).movetime_ms, Some(250));
+        assert_eq!(GoParams::parse(&["infinite"]).limits(Color::White), SearchLimits::default());
+    }
+
+    #[test]
+    fn nearly_out_of_time() {
+        let params = GoParams::parse(&["wtime", "60", "winc", "1000"]);
+        assert_eq!(params.limits(Color::White).movetime_ms, Some(10));
+    }
+
+    #[test]
+    fn positions() {
+        let (mut uci, receiver) = uci();
+        uci.handle("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(
+            fen::encode(uci.board()),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        uci.handle("position fen 4k3/1P6/8/8/8/8/8/4K2R w K - 0 1 moves e1g1 e8d7 b7b8n");
+        assert_eq!(fen::encode(uci.board()), "1N6/3k4/8/8/8/8/8/5RK1 b - - 0 2");
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn an_illegal_move_is_reported() {
+        let (mut uci, receiver) = uci();
+        uci.handle("position startpos moves e2e4 e2e4");
+        assert_eq!(receiver.try_recv().unwrap(), "info string Illegal move: e2e4");
+        assert_eq!(uci.board().ply(), 1);
+    }
+
+    #[test]
+    fn searching_to_a_depth() {
+        let (mut uci, receiver) = uci();
+        uci.handle("position startpos moves e2e4");
+        uci.handle("go depth 3");
+        let lines = until_bestmove(&receiver);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("info depth 1 score cp "));
+        assert!(lines[2].starts_with("info depth 3 "));
+        assert!(lines[2].contains(" nodes ") && lines[2].contains(" nps ") && lines[2].contains(" pv "));
+        assert!(lines[3].starts_with("bestmove "));
+    }
+
+    #[test]
+    fn a_mate_is_scored_in_moves() {
+        let (mut uci, receiver) = uci();
+        uci.handle("position fen r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1");
+        uci.handle("go depth 5");
+        let lines = until_bestmove(&receiver);
+        assert!(lines.iter().any(|line| line.contains("score mate 2") && line.contains("pv e2e8 a8e8 e1e8")));
+        assert_eq!(lines.last().unwrap(), "bestmove e2e8 ponder a8e8");
+    }
+
+    #[test]
+    fn no_moves_to_make() {
+        let (mut uci, receiver) = uci();
+        uci.handle("position fen 7k/6Q1/6K1/8/8/8/8/8 b - - 0 1");
+        uci.handle("go depth 2");
+        let lines = until_bestmove(&receiver);
+        assert!(lines[0].starts_with("info depth 1 score mate 0 ") && !lines[0].contains("pv"));
+        assert_eq!(lines.last().unwrap(), "bestmove 0000");
+    }
+
+    #[test]
+    fn an_infinite_search_waits_to_be_stopped() {
+        let (mut uci, receiver) = uci();
+        // mate is found at once, but the best move still waits for stop
+        uci.handle("position fen 6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1");
+        uci.handle("go infinite");
+        thread::sleep(Duration::from_millis(100));
+        assert!(receiver.try_iter().all(|line| line.starts_with("info")));
+        uci.handle("isready");
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), "readyok");
+        uci.handle("stop");
+        assert_eq!(until_bestmove(&receiver).last().unwrap(), "bestmove e1e8");
+    }
+
+    #[test]
+    fn stopping_at_once() {
+        let (mut uci, receiver) = uci();
+        uci.handle("go infinite");
+        uci.handle("stop");
+        assert!(until_bestmove(&receiver).last().unwrap().starts_with("bestmove "));
+        // the engine is back for the next search
+        uci.handle("go nodes 1000");
+        assert!(until_bestmove(&receiver).last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn options() {
+        let (mut uci, receiver) = uci();
+        uci.handle("setoption name Hash value 4");
+        assert_eq!(uci.engine_mut().options().hash_mb, 4);
+        uci.handle("setoption name Clear Hash");
+        uci.handle("setoption name Hash value lots");
+        assert_eq!(receiver.try_recv().unwrap(), "info string invalid Hash value: lots");
+        uci.handle("setoption name Ponder value true");
+        assert_eq!(receiver.try_recv().unwrap(), "info string unknown option: ponder");
+    }
+
+    #[test]
+    fn chess960_castling() {
+        let (mut uci, receiver) = uci();
+        uci.handle("setoption name UCI_Chess960 value true");
+        uci.handle("position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1h1");
+        assert_eq!(fen::encode(uci.board()), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+        // only castling mates, as Rf1+ leaves g2 free
+        uci.handle("position fen 8/8/8/5N2/8/2N2k1P/3P4/4K2R w K - 0 1");
+        uci.handle("go depth 2");
+        let lines = until_bestmove(&receiver);
+        assert!(lines.iter().any(|line| line.contains("score mate 1 ") && line.ends_with("pv e1h1")));
+        assert_eq!(lines.last().unwrap(), "bestmove e1h1");
+    }
+
+    #[test]
+    fn playing_from_a_book() {
+        let (mut uci, receiver) = uci();
+        let book = Book::build_from_pgn("1. d4 d5 1-0", &BookOptions::default()).0;
+        let path = std::env::temp_dir().join(format!("narmi-uci-{}.bin", std::process::id()));
+        fs::write(&path, book.to_bytes()).unwrap();
+        uci.handle(&format!("setoption name Book File value {}", path.display()));
+        uci.handle("setoption name OwnBook value true");
+        uci.handle("go depth 1");
+        assert_eq!(until_bestmove(&receiver), ["bestmove d2d4"]);
+        // out of the book the engine searches
+        uci.handle("position startpos moves e2e4");
+        uci.handle("go depth 1");
+        assert!(until_bestmove(&receiver).len() > 1);
+        fs::remove_file(&path).unwrap();
+        uci.handle("setoption name Book File value /no/such/book.bin");
+        assert!(receiver.try_recv().unwrap().starts_with("info string couldn't read /no/such/book.bin"));
+    }
+
+    #[test]
+    fn playing_from_tablebases() {
+        let (mut uci, receiver) = uci();
+        let directory = std::env::temp_dir().join(format!("narmi-uci-syzygy-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("KQvK.rtbw"), b"").unwrap();
+        uci.handle(&format!("setoption name SyzygyPath value {}", directory.display()));
+        assert_eq!(receiver.try_recv().unwrap(), "info string found 1 tablebases");
+        // taking the queen is the only move, so the table itself isn't needed
+        uci.handle("position fen k7/1Q6/8/8/8/8/8/7K b - - 0 1");
+        uci.handle("go depth 1");
+        assert_eq!(until_bestmove(&receiver), ["info string tablebase draw", "bestmove a8b7"]);
+        // otherwise it is, and an unreadable one leaves the engine to search
+        uci.handle("position fen k7/8/1Q6/8/8/8/8/7K b - - 0 1");
+        uci.handle("go depth 1");
+        let lines = until_bestmove(&receiver);
+        assert!(lines[0].starts_with("info string tablebase: "), "{}", lines[0]);
+        assert!(lines.len() > 2);
+        fs::remove_dir_all(&directory).unwrap();
+        uci.handle("setoption name SyzygyPath value /no/such/directory");
+        assert!(receiver.try_recv().unwrap().starts_with("info string Couldn't read /no/such/directory"));
+    }
+
+    #[test]
+    fn quitting() {
+        let (mut uci, receiver) = uci();
+        uci.handle("go infinite");
+        assert!(!uci.handle("quit"));
+        assert!(until_bestmove(&receiver).last().unwrap().starts_with("bestmove "));
+    }
+}
@@ -0,0 +1,523 @@
+// The Chess Engine Communication Protocol spoken by XBoard, WinBoard and some other GUIs. Unlike UCI, the
+// engine keeps the game itself: the GUI sends the moves played one at a time and tells the engine which
+// side to play, and the engine replies with its own moves whenever it's that side's turn. As with UCI,
+// replies are sent down a channel a line at a time and searches run on a thread of their own.
+//
+// See https://www.gnu.org/software/xboard/engine-intf.html
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use super::search::*;
+use super::uci::{GoParams, NAME};
+use crate::game::board::*;
+use crate::game::chess_move::*;
+use crate::game::color::Color;
+use crate::game::fen;
+use crate::game::notation::*;
+use crate::game::piece_move::generate_moves;
+
+// how long to think when the GUI has given no clock, time per move or depth
+const DEFAULT_MOVETIME_MS: u64 = 5000;
+// mate scores in thinking output, by the protocol's convention
+const MATE_SCORE: i32 = 100000;
+
+// the clock set with `level`: so many moves (0 for all of them) in a base time, plus an increment
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Level {
+    pub moves: u32,
+    pub base_seconds: f64,
+    pub increment_seconds: f64,
+}
+
+impl Level {
+    // level MPS BASE INC, where the base is in minutes or minutes:seconds
+    pub fn parse(args: &[&str]) -> Option<Level> {
+        match args {
+            [moves, base, increment] => {
+                let base_seconds = match base.split_once(':') {
+                    Some((minutes, seconds)) => minutes.parse::<f64>().ok()? * 60.0 + seconds.parse::<f64>().ok()?,
+                    None => base.parse::<f64>().ok()? * 60.0,
+                };
+                Some(Level {
+                    moves: moves.parse().ok()?,
+                    base_seconds,
+                    increment_seconds: increment.parse().ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+// the result to claim when the game is over on the board, as the protocol writes it
+pub fn game_result(board: &Board) -> Option<String> {
+    if generate_moves(board).is_empty() {
+        Some(match (board.in_check(), board.side_to_move()) {
+            (true, Color::White) => "0-1 {Black mates}".to_string(),
+            (true, Color::Black) => "1-0 {White mates}".to_string(),
+            (false, _) => "1/2-1/2 {Stalemate}".to_string(),
+        })
+    } else if board.halfmove_clock() >= 100 {
+        Some("1/2-1/2 {Draw by the 50 move rule}".to_string())
+    } else {
+        None
+    }
+}
+
+// ply, score, time in centiseconds, nodes and the principal variation
+fn thinking_line(board: &Board, info: &SearchInfo) -> String {
+    let score = match mate_in(info.score) {
+        Some(moves) if moves > 0 => MATE_SCORE + moves,
+        Some(moves) => -MATE_SCORE + moves,
+        None => info.score,
+    };
+    format!(
+        "{} {} {} {} {}",
+        info.depth,
+        score,
+        info.time_ms / 10,
+        info.nodes,
+        encode_line(board, &info.pv).join(" ")
+    )
+}
+
+struct RunningSearch {
+    // set when the search should end early; the engine's move is still played unless it's abandoned
+    stopped: Arc<AtomicBool>,
+    abandoned: Arc<AtomicBool>,
+    // set just before the move is sent, so that whatever the GUI sends in reply finds it set
+    moved: Arc<AtomicBool>,
+    engine_stop: Arc<AtomicBool>,
+    // hands back the engine along with the move it played, if it played one
+    thread: JoinHandle<(Engine, Option<Move>)>,
+}
+
+pub struct Xboard {
+    out: Sender<String>,
+    board: Board,
+    // None in force mode, when the engine just keeps track of the moves played
+    engine_color: Option<Color>,
+    post: bool,
+    level: Option<Level>,
+    seconds_per_move: Option<u64>,
+    depth: Option<u32>,
+    // the engine's clock in milliseconds, as last told by `time`
+    time_left: Option<u64>,
+    engine: Option<Engine>,
+    search: Option<RunningSearch>,
+}
+
+impl Xboard {
+    pub fn new(out: Sender<String>) -> Xboard {
+        Xboard {
+            out,
+            board: Board::new(),
+            engine_color: Some(Color::Black),
+            post: false,
+            level: None,
+            seconds_per_move: None,
+            depth: None,
+            time_left: None,
+            engine: Some(Engine::new()),
+            search: None,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn engine_color(&self) -> Option<Color> {
+        self.engine_color
+    }
+
+    fn send(&self, line: String) {
+        // with no one listening there's nowhere to report the failure either
+        let _ = self.out.send(line);
+    }
+
+    fn error(&self, reason: &str, command: &str) {
+        self.send(format!("Error ({}): {}", reason, command));
+    }
+
+    // handles one line of input, returning false once the engine should quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+        // the engine's move goes on the board once it has been sent
+        if self.search.as_ref().is_some_and(|search| search.moved.load(Ordering::Relaxed)) {
+            self.finish(false);
+        }
+        match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "rating"
+            | "ics" | "otim" | "draw" => {}
+            "protover" => self.features(),
+            "ping" => self.send(format!("pong {}", args.first().unwrap_or(&""))),
+            "new" => {
+                self.finish(true);
+                self.board = Board::new();
+                self.engine_color = Some(Color::Black);
+                self.depth = None;
+                self.engine_mut().clear();
+            }
+            "setboard" => {
+                self.finish(true);
+                match fen::decode(&args.join(" ")) {
+                    Ok(board) => self.board = board,
+                    Err(err) => self.send(format!("tellusererror Illegal position: {}", err.reason)),
+                }
+            }
+            "force" | "result" => {
+                self.finish(true);
+                self.engine_color = None;
+            }
+            "go" => {
+                self.finish(false);
+                self.engine_color = Some(self.board.side_to_move());
+                self.think();
+            }
+            "playother" => {
+                self.finish(false);
+                self.engine_color = Some(self.board.side_to_move().opposite());
+            }
+            "usermove" => match args.first() {
+                Some(text) => self.user_move(text),
+                None => self.error("missing move", line),
+            },
+            "?" => self.finish(false),
+            "undo" => self.take_back(1, line),
+            "remove" => self.take_back(2, line),
+            "time" => match args.first().and_then(|time| time.parse::<u64>().ok()) {
+                Some(centiseconds) => self.time_left = Some(centiseconds * 10),
+                None => self.error("invalid time", line),
+            },
+            "level" => match Level::parse(args) {
+                Some(level) => {
+                    self.level = Some(level);
+                    self.seconds_per_move = None;
+                }
+                None => self.error("invalid level", line),
+            },
+            "st" => match args.first().and_then(|seconds| seconds.parse::<u64>().ok()) {
+                Some(seconds) => {
+                    self.seconds_per_move = Some(seconds);
+                    self.level = None;
+                }
+                None => self.error("invalid time per move", line),
+            },
+            "sd" => match args.first().and_then(|depth| depth.parse::<u32>().ok()) {
+                Some(depth) => self.depth = Some(depth),
+                None => self.error("invalid depth", line),
+            },
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "quit" => {
+                self.finish(true);
+                return false;
+            }
+            // a GUI which turned down the usermove feature sends moves on their own
+            _ if decode_uci(command).is_ok() => self.user_move(command),
+            _ => self.error("unknown command", command),
+        }
+        true
+    }
+
+    fn features(&self) {
+        self.send(format!(
+            "feature myname=\"{} {}\" usermove=1 setboard=1 ping=1 playother=1 san=0 time=1 colors=0 \
+             sigint=0 sigterm=0 analyze=0 reuse=1 done=1",
+            NAME,
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+
+    fn engine_mut(&mut self) -> &mut Engine {
+        self.engine.as_mut().expect("the engine is only lent out while searching")
+    }
+
+    fn user_move(&mut self, text: &str) {
+        self.finish(false);
+        match resolve_uci(&self.board, text) {
+            Ok(chess_move) => {
+                self.board.make_move(chess_move);
+                match game_result(&self.board) {
+                    Some(result) => self.send(format!("result {}", result)),
+                    None if self.engine_color == Some(self.board.side_to_move()) => self.think(),
+                    None => {}
+                }
+            }
+            Err(_) => self.send(format!("Illegal move: {}", text)),
+        }
+    }
+
+    fn take_back(&mut self, moves: usize, command: &str) {
+        self.finish(true);
+        if self.board.ply() < moves {
+            return self.error("command not legal now", command);
+        }
+        for _ in 0..moves {
+            self.board.unmake_move();
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let mut limits = match (self.seconds_per_move, self.level, self.time_left) {
+            (Some(seconds), _, _) => SearchLimits::movetime(seconds * 1000),
+            (None, level, Some(time)) => {
+                let level = level.unwrap_or_default();
+                let increment = (level.increment_seconds * 1000.0) as u64;
+                // the moves left before the clock is topped up again, counting from the first move
+                let movestogo = if level.moves > 0 {
+                    Some((level.moves - (self.board.fullmove_number() - 1) % level.moves) as u64)
+                } else {
+                    None
+                };
+                GoParams {
+                    wtime: Some(time),
+                    btime: Some(time),
+                    winc: Some(increment),
+                    binc: Some(increment),
+                    movestogo,
+                    ..GoParams::default()
+                }
+                .limits(self.board.side_to_move())
+            }
+            (None, _, None) if self.depth.is_some() => SearchLimits::default(),
+            (None, _, None) => SearchLimits::movetime(DEFAULT_MOVETIME_MS),
+        };
+        limits.depth = self.depth;
+        limits
+    }
+
+    fn think(&mut self) {
+        let mut engine = self.engine.take().expect("the engine is only lent out while searching");
+        let board = self.board.clone();
+        let limits = self.limits();
+        let post = self.post;
+        let out = self.out.clone();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let moved = Arc::new(AtomicBool::new(false));
+        let engine_stop = engine.stop_handle();
+        let thread = {
+            let (stopped, abandoned, moved) = (stopped.clone(), abandoned.clone(), moved.clone());
+            let engine_stop = engine_stop.clone();
+            thread::spawn(move || {
+                let result = engine.search_with(&board, &limits, |info| {
+                    if post {
+                        let _ = out.send(thinking_line(&board, info));
+                    }
+                    // the engine clears its own flag as the search starts, so a stop sent before then is
+                    // passed on here
+                    if stopped.load(Ordering::Relaxed) {
+                        engine_stop.store(true, Ordering::Relaxed);
+                    }
+                });
+                let chess_move = match result.best() {
+                    Some(chess_move) if !abandoned.load(Ordering::Relaxed) => chess_move,
+                    _ => return (engine, None),
+                };
+                moved.store(true, Ordering::Relaxed);
+                let _ = out.send(format!("move {}", encode_uci(&board, &chess_move, UciCastling::KingMove)));
+                let mut after = board;
+                after.make_move(chess_move);
+                if let Some(result) = game_result(&after) {
+                    let _ = out.send(format!("result {}", result));
+                }
+                (engine, Some(chess_move))
+            })
+        };
+        self.search = Some(RunningSearch {
+            stopped,
+            abandoned,
+            moved,
+            engine_stop,
+            thread,
+        });
+    }
+
+    // Waits for any search in progress to end, playing its move unless it's abandoned. Only `?` stops a
+    // search early to have it move at once; other commands either abandon it or only come after its move.
+    fn finish(&mut self, abandon: bool) {
+        if let Some(search) = self.search.take() {
+            if abandon {
+                search.abandoned.store(true, Ordering::Relaxed);
+            }
+            search.stopped.store(true, Ordering::Relaxed);
+            search.engine_stop.store(true, Ordering::Relaxed);
+            match search.thread.join() {
+                Ok((engine, chess_move)) => {
+                    self.engine = Some(engine);
+                    if let Some(chess_move) = chess_move {
+                        self.board.make_move(chess_move);
+                    }
+                }
+                Err(_) => {
+                    self.send("tellusererror The search failed".to_string());
+                    self.engine = Some(Engine::new());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::time::Duration;
+
+    fn xboard() -> (Xboard, Receiver<String>) {
+        let (sender, receiver) = channel();
+        let mut xboard = Xboard::new(sender);
+        xboard.handle("xboard");
+        xboard.handle("protover 2");
+        assert!(receiver.recv().unwrap().starts_with("feature myname=\"narmi "));
+        (xboard, receiver)
+    }
+
+    // everything sent up to and including the engine's move
+    fn until_move(receiver: &Receiver<String>) -> Vec<String> {
+        let mut lines = vec![];
+        loop {
+            let line = receiver.recv_timeout(Duration::from_secs(30)).expect("no move");
+            let done = line.starts_with("move ");
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    #[test]
+    fn levels() {
+        let level = Level::parse(&["40", "5", "0"]).unwrap();
+        assert_eq!((level.moves, level.base_seconds, level.increment_seconds), (40, 300.0, 0.0));
+        let level = Level::parse(&["0", "2:30", "0.5"]).unwrap();
+        assert_eq!((level.moves, level.base_seconds, level.increment_seconds), (0, 150.0, 0.5));
+        assert!(Level::parse(&["40", "5"]).is_none());
+        assert!(Level::parse(&["forty", "5", "0"]).is_none());
+    }
+
+    #[test]
+    fn the_engine_answers_as_black() {
+        let (mut xboard, receiver) = xboard();
+        xboard.handle("new");
+        xboard.handle("sd 2");
+        xboard.handle("usermove e2e4");
+        let lines = until_move(&receiver);
+        assert_eq!(lines.len(), 1);
+        xboard.handle("ping 7");
+        assert_eq!(receiver.recv().unwrap(), "pong 7");
+        // the engine's move is on the board once the next command comes in
+        assert_eq!(xboard.board().ply(), 2);
+        assert_eq!(xboard.board().side_to_move(), Color::White);
+    }
+
+    #[test]
+    fn thinking_is_posted() {
+        let (mut xboard, receiver) = xboard();
+        xboard.handle("new");
+        xboard.handle("post");
+        xboard.handle("sd 3");
+        xboard.handle("go");
+        let lines = until_move(&receiver);
+        assert_eq!(lines.len(), 4);
+        let fields: Vec<&str> = lines[2].split(' ').collect();
+        assert_eq!(fields[0], "3");
+        assert!(fields[1].parse::<i32>().is_ok() && fields[2].parse::<u64>().is_ok());
+        assert!(fields[3].parse::<u64>().unwrap() > 0);
+        assert_eq!(fields.len(), 7);
+        assert_eq!(xboard.engine_color(), Some(Color::White));
+    }
+
+    #[test]
+    fn mates_are_posted_and_claimed() {
+        let (mut xboard, receiver) = xboard();
+        xboard.handle("force");
+        xboard.handle("setboard 6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1");
+        xboard.handle("post");
+        xboard.handle("sd 3");
+        xboard.handle("go");
+        let lines = until_move(&receiver);
+        assert!(lines.iter().any(|line| line.split(' ').nth(1) == Some("100001") && line.ends_with(" Re8#")));
+        assert_eq!(lines.last().unwrap(), "move e1e8");
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), "result 1-0 {White mates}");
+    }
+
+    #[test]
+    fn force_mode_only_follows_the_game() {
+        let (mut xboard, receiver) = xboard();
+        xboard.handle("new");
+        xboard.handle("force");
+        xboard.handle("usermove e2e4");
+        xboard.handle("usermove e7e5");
+        xboard.handle("g1f3");
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(
+            fen::encode(xboard.board()),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        xboard.handle("remove");
+        assert_eq!(xboard.board().ply(), 1);
+        xboard.handle("undo");
+        xboard.handle("undo");
+        assert_eq!(receiver.try_recv().unwrap(), "Error (command not legal now): undo");
+    }
+
+    #[test]
+    fn illegal_moves_and_unknown_commands() {
+        let (mut xboard, receiver) = xboard();
+        xboard.handle("force");
+        xboard.handle("usermove e2e5");
+        assert_eq!(receiver.try_recv().unwrap(), "Illegal move: e2e5");
+        xboard.handle("analyze");
+        assert_eq!(receiver.try_recv().unwrap(), "Error (unknown command): analyze");
+        xboard.handle("setboard not a position");
+        assert!(receiver.try_recv().unwrap().starts_with("tellusererror Illegal position"));
+    }
+
+    #[test]
+    fn the_clock_decides_the_time_to_think() {
+        let (mut xboard, _receiver) = xboard();
+        assert_eq!(xboard.limits(), SearchLimits::movetime(DEFAULT_MOVETIME_MS));
+        xboard.handle("level 40 5 0");
+        xboard.handle("time 30000");
+        assert_eq!(xboard.limits().movetime_ms, Some(300_000 / 40));
+        xboard.handle("level 0 1 2");
+        xboard.handle("time 6000");
+        assert_eq!(xboard.limits().movetime_ms, Some(60_000 / 30 + 1500));
+        xboard.handle("st 3");
+        assert_eq!(xboard.limits().movetime_ms, Some(3000));
+        xboard.handle("sd 4");
+        assert_eq!(xboard.limits(), SearchLimits { depth: Some(4), ..SearchLimits::movetime(3000) });
+    }
+
+    #[test]
+    fn move_now() {
+        let (mut xboard, receiver) = xboard();
+        xboard.handle("st 1000");
+        xboard.handle("go");
+        xboard.handle("?");
+        assert!(until_move(&receiver).last().unwrap().starts_with("move "));
+        assert_eq!(xboard.board().ply(), 1);
+    }
+
+    #[test]
+    fn an_abandoned_search_makes_no_move() {
+        let (mut xboard, receiver) = xboard();
+        xboard.handle("st 1000");
+        xboard.handle("go");
+        xboard.handle("result 1/2-1/2 {Draw agreed}");
+        assert_eq!(xboard.board().ply(), 0);
+        assert_eq!(xboard.engine_color(), None);
+        assert!(receiver.try_iter().all(|line| !line.starts_with("move ")));
+    }
+}
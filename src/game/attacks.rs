@@ -0,0 +1,354 @@
+// Attack lookups for every piece type.
+//
+// Knight, king and pawn attacks don't depend on the rest of the board, so they are tabulated per square at
+// compile time. Bishop and rook attacks depend on which squares are occupied along their lines, so they use
+// "magic" bitboards: the occupied squares which matter (the mask) are multiplied by a magic number chosen
+// so that the top bits of the product index a table holding the attacks for that occupancy, without
+// collisions between occupancies which need different attacks. The magic numbers are found, and the
+// tables filled, the first time they are needed.
+
+use std::sync::OnceLock;
+
+use super::bitboard::*;
+use super::color::*;
+use super::piece_type::*;
+use super::square::*;
+
+const fn leaper_table(steps: &[(i8, i8)]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let file = (square % 8) as i8;
+        let rank = (square / 8) as i8;
+        let mut step = 0;
+        while step < steps.len() {
+            let to_file = file + steps[step].0;
+            let to_rank = rank + steps[step].1;
+            if to_file >= 0 && to_file < 8 && to_rank >= 0 && to_rank < 8 {
+                table[square] |= 1 << (to_rank * 8 + to_file);
+            }
+            step += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+// steps are (file, rank) offsets
+const KNIGHT_ATTACKS: [u64; 64] =
+    leaper_table(&[(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)]);
+const KING_ATTACKS: [u64; 64] =
+    leaper_table(&[(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)]);
+const PAWN_ATTACKS: [[u64; 64]; 2] = [leaper_table(&[(-1, 1), (1, 1)]), leaper_table(&[(-1, -1), (1, -1)])];
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    Bitboard(KNIGHT_ATTACKS[square.index() as usize])
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    Bitboard(KING_ATTACKS[square.index() as usize])
+}
+
+// the squares a pawn of the given colour on the square attacks (diagonally forwards)
+pub fn pawn_attacks(color: Color, square: Square) -> Bitboard {
+    Bitboard(PAWN_ATTACKS[color.index()][square.index() as usize])
+}
+
+// the squares a slider on the square attacks in the given directions, stopping at (and including) the first
+// occupied square in each direction
+fn sliding_attacks(square: Square, occupied: u64, directions: &[(i8, i8)]) -> u64 {
+    let mut attacks = 0;
+    for (file_delta, rank_delta) in directions.iter() {
+        let mut current = square;
+        while let Some(next) = current.offset(*file_delta, *rank_delta) {
+            attacks |= 1 << next.index();
+            if occupied & (1 << next.index()) != 0 {
+                break;
+            }
+            current = next;
+        }
+    }
+    attacks
+}
+
+// the squares whose occupancy affects a slider's attacks - its lines, not including the edge of the board
+// (a piece on the edge can't block anything further)
+fn relevant_occupancy(square: Square, directions: &[(i8, i8)]) -> u64 {
+    let mut mask = 0;
+    for (file_delta, rank_delta) in directions.iter() {
+        let mut current = square;
+        while let Some(next) = current.offset(*file_delta, *rank_delta) {
+            if next.offset(*file_delta, *rank_delta).is_none() {
+                break;
+            }
+            mask |= 1 << next.index();
+            current = next;
+        }
+    }
+    mask
+}
+
+struct Magic {
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: u64) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct SlidingTables {
+    bishop: Vec<Magic>,
+    rook: Vec<Magic>,
+    attacks: Vec<u64>,
+}
+
+// xorshift64* with fixed seeds, so the same magics are found every time
+struct Random(u64);
+
+// seeds (one per rank) known to find magics after few attempts
+const SEEDS: [u64; 8] = [728, 10316, 55013, 32803, 12281, 15100, 16645, 255];
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // magics with few bits set are found much more quickly
+    fn sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
+
+fn find_magic(square: Square, directions: &[(i8, i8)], attacks: &mut Vec<u64>) -> Magic {
+    let mut random = Random(SEEDS[square.rank().index() as usize]);
+    let mask = relevant_occupancy(square, directions);
+    let bits = mask.count_ones();
+    let size = 1 << bits;
+
+    // every subset of the mask, enumerated with the Carry-Rippler trick, with the attacks it gives
+    let mut occupancies = Vec::with_capacity(size);
+    let mut expected = Vec::with_capacity(size);
+    let mut subset: u64 = 0;
+    loop {
+        occupancies.push(subset);
+        expected.push(sliding_attacks(square, subset, directions));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+
+    let offset = attacks.len();
+    attacks.resize(offset + size, 0);
+    let mut filled = vec![0u32; size];
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let magic = random.sparse();
+        // the magic must spread the mask's bits well enough to reach the top byte
+        if (mask.wrapping_mul(magic) & 0xff00_0000_0000_0000).count_ones() < 6 {
+            continue;
+        }
+        let candidate = Magic {
+            mask,
+            magic,
+            shift: 64 - bits,
+            offset,
+        };
+        let collides = occupancies.iter().zip(expected.iter()).any(|(occupied, attack)| {
+            let index = candidate.index(*occupied) - offset;
+            if filled[index] != attempt {
+                filled[index] = attempt;
+                attacks[offset + index] = *attack;
+                false
+            } else {
+                // two occupancies sharing an entry is only fine if they give the same attacks
+                attacks[offset + index] != *attack
+            }
+        });
+        if !collides {
+            return candidate;
+        }
+    }
+}
+
+fn tables() -> &'static SlidingTables {
+    static TABLES: OnceLock<SlidingTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut attacks = Vec::with_capacity(107_648);
+        let bishop = Square::all()
+            .map(|square| find_magic(square, &BISHOP_DIRECTIONS, &mut attacks))
+            .collect();
+        let rook = Square::all()
+            .map(|square| find_magic(square, &ROOK_DIRECTIONS, &mut attacks))
+            .collect();
+        SlidingTables { bishop, rook, attacks }
+    })
+}
+
+// generates the sliding and line tables up front, rather than on first use
+pub fn init() {
+    tables();
+    line_tables();
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    Bitboard(tables.attacks[tables.bishop[square.index() as usize].index(occupied.0)])
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    Bitboard(tables.attacks[tables.rook[square.index() as usize].index(occupied.0)])
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    bishop_attacks(square, occupied) | rook_attacks(square, occupied)
+}
+
+// the squares attacked by a piece of the given type and colour on the square
+pub fn piece_attacks(piece_type: PieceType, color: Color, square: Square, occupied: Bitboard) -> Bitboard {
+    match piece_type {
+        PieceType::Pawn => pawn_attacks(color, square),
+        PieceType::Knight => knight_attacks(square),
+        PieceType::Bishop => bishop_attacks(square, occupied),
+        PieceType::Rook => rook_attacks(square, occupied),
+        PieceType::Queen => queen_attacks(square, occupied),
+        PieceType::King => king_attacks(square),
+        PieceType::Empty => Bitboard::EMPTY,
+    }
+}
+
+struct LineTables {
+    between: Vec<u64>,
+    line: Vec<u64>,
+}
+
+fn line_tables() -> &'static LineTables {
+    static TABLES: OnceLock<LineTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut between = vec![0; 64 * 64];
+        let mut line = vec![0; 64 * 64];
+        for from in Square::all() {
+            for direction in ROOK_DIRECTIONS.iter().chain(BISHOP_DIRECTIONS.iter()) {
+                let opposite = (-direction.0, -direction.1);
+                let full = sliding_attacks(from, 0, &[*direction, opposite]) | 1 << from.index();
+                let mut passed = 0;
+                let mut current = from;
+                while let Some(to) = current.offset(direction.0, direction.1) {
+                    let index = from.index() as usize * 64 + to.index() as usize;
+                    between[index] = passed;
+                    line[index] = full;
+                    passed |= 1 << to.index();
+                    current = to;
+                }
+            }
+        }
+        LineTables { between, line }
+    })
+}
+
+// the squares strictly between two squares on the same rank, file or diagonal (empty if they aren't)
+pub fn between(from: Square, to: Square) -> Bitboard {
+    Bitboard(line_tables().between[from.index() as usize * 64 + to.index() as usize])
+}
+
+// the whole rank, file or diagonal through two squares, edge to edge (empty if they aren't aligned)
+pub fn line(from: Square, to: Square) -> Bitboard {
+    Bitboard(line_tables().line[from.index() as usize * 64 + to.index() as usize])
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn squares(list: &[Square]) -> Bitboard {
+        list.iter().fold(Bitboard::EMPTY, |board, square| board.with(*square))
+    }
+
+    #[test]
+    fn knights_attack_in_an_l_shape() {
+        assert_eq!(knight_attacks(Square::A1), squares(&[Square::B3, Square::C2]));
+        assert_eq!(knight_attacks(Square::E4).count(), 8);
+    }
+
+    #[test]
+    fn kings_attack_adjacent_squares() {
+        assert_eq!(king_attacks(Square::H8), squares(&[Square::G8, Square::G7, Square::H7]));
+        assert_eq!(king_attacks(Square::E4).count(), 8);
+    }
+
+    #[test]
+    fn pawns_attack_diagonally_forwards() {
+        assert_eq!(pawn_attacks(Color::White, Square::E4), squares(&[Square::D5, Square::F5]));
+        assert_eq!(pawn_attacks(Color::Black, Square::E4), squares(&[Square::D3, Square::F3]));
+        assert_eq!(pawn_attacks(Color::White, Square::A2), squares(&[Square::B3]));
+    }
+
+    #[test]
+    fn rooks_are_blocked_by_the_first_piece_in_each_direction() {
+        let occupied = squares(&[Square::D6, Square::B4, Square::D2, Square::H4]);
+        assert_eq!(
+            rook_attacks(Square::D4, occupied),
+            squares(&[
+                Square::D5, Square::D6, Square::E4, Square::F4, Square::G4, Square::H4, Square::D3, Square::D2,
+                Square::C4, Square::B4,
+            ])
+        );
+    }
+
+    #[test]
+    fn bishops_are_blocked_by_the_first_piece_in_each_direction() {
+        let occupied = squares(&[Square::F3, Square::B2]);
+        assert_eq!(
+            bishop_attacks(Square::A1, occupied),
+            squares(&[Square::B2])
+        );
+        assert_eq!(bishop_attacks(Square::D1, occupied), squares(&[Square::E2, Square::F3, Square::C2, Square::B3, Square::A4]));
+    }
+
+    #[test]
+    fn magic_lookups_agree_with_ray_scanning() {
+        let mut random = Random(42);
+        for _ in 0..200 {
+            let occupied = Bitboard(random.next() & random.next());
+            for square in Square::all() {
+                assert_eq!(rook_attacks(square, occupied).0, sliding_attacks(square, occupied.0, &ROOK_DIRECTIONS));
+                assert_eq!(bishop_attacks(square, occupied).0, sliding_attacks(square, occupied.0, &BISHOP_DIRECTIONS));
+            }
+        }
+    }
+
+    #[test]
+    fn between_holds_the_squares_separating_aligned_squares() {
+        assert_eq!(between(Square::A1, Square::D4), squares(&[Square::B2, Square::C3]));
+        assert_eq!(between(Square::E1, Square::E4), squares(&[Square::E2, Square::E3]));
+        assert_eq!(between(Square::E1, Square::E2), Bitboard::EMPTY);
+        assert_eq!(between(Square::A1, Square::B3), Bitboard::EMPTY);
+    }
+
+    #[test]
+    fn line_runs_edge_to_edge_through_aligned_squares() {
+        assert_eq!(line(Square::C3, Square::E5), line(Square::A1, Square::H8));
+        assert_eq!(line(Square::C3, Square::E5).count(), 8);
+        assert_eq!(line(Square::E4, Square::E6), Bitboard::file(File::E));
+        assert_eq!(line(Square::A1, Square::B3), Bitboard::EMPTY);
+    }
+
+    #[test]
+    fn queens_combine_rook_and_bishop_attacks() {
+        assert_eq!(queen_attacks(Square::D4, Bitboard::EMPTY).count(), 27);
+    }
+}
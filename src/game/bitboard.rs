@@ -0,0 +1,189 @@
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+use super::square::*;
+
+// a set of squares, one bit per square numbered as Square::index (a1 is the least significant bit)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const FULL: Bitboard = Bitboard(!0);
+
+    pub fn from_square(square: Square) -> Bitboard {
+        Bitboard(1 << square.index())
+    }
+
+    pub fn file(file: File) -> Bitboard {
+        Bitboard(0x0101_0101_0101_0101 << file.index())
+    }
+
+    pub fn rank(rank: Rank) -> Bitboard {
+        Bitboard(0xff << (8 * rank.index()))
+    }
+
+    pub fn contains(self, square: Square) -> bool {
+        self.0 & (1 << square.index()) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    // the lowest numbered square in the set
+    pub fn first(self) -> Option<Square> {
+        if self.0 == 0 {
+            None
+        } else {
+            Square::from_index(self.0.trailing_zeros() as u8)
+        }
+    }
+
+    pub fn with(self, square: Square) -> Bitboard {
+        Bitboard(self.0 | 1 << square.index())
+    }
+
+    pub fn without(self, square: Square) -> Bitboard {
+        Bitboard(self.0 & !(1 << square.index()))
+    }
+
+    pub fn squares(self) -> BitboardSquares {
+        BitboardSquares(self.0)
+    }
+}
+
+pub struct BitboardSquares(u64);
+
+impl Iterator for BitboardSquares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Square::from_index(index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl IntoIterator for Bitboard {
+    type Item = Square;
+    type IntoIter = BitboardSquares;
+
+    fn into_iter(self) -> BitboardSquares {
+        self.squares()
+    }
+}
+
+impl From<Square> for Bitboard {
+    fn from(square: Square) -> Bitboard {
+        Bitboard::from_square(square)
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 & other.0)
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 | other.0)
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+
+    fn bitxor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 ^ other.0)
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, other: Bitboard) {
+        self.0 &= other.0;
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, other: Bitboard) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, other: Bitboard) {
+        self.0 ^= other.0;
+    }
+}
+
+// an 8x8 grid with rank 8 at the top, for debugging
+impl fmt::Display for Bitboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rank in Rank::all().collect::<Vec<Rank>>().into_iter().rev() {
+            let row: String = File::all()
+                .map(|file| if self.contains(Square::new(file, rank)) { '1' } else { '.' })
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn squares_are_iterated_in_ascending_order() {
+        let board = Bitboard::EMPTY.with(Square::H8).with(Square::A1).with(Square::E4);
+        assert_eq!(board.squares().collect::<Vec<Square>>(), vec![Square::A1, Square::E4, Square::H8]);
+        assert_eq!(board.count(), 3);
+        assert_eq!(board.first(), Some(Square::A1));
+    }
+
+    #[test]
+    fn files_and_ranks_hold_eight_squares() {
+        assert_eq!(Bitboard::file(File::C).count(), 8);
+        assert!(Bitboard::file(File::C).contains(Square::C7));
+        assert_eq!(Bitboard::rank(Rank::THREE).count(), 8);
+        assert!(Bitboard::rank(Rank::THREE).contains(Square::H3));
+        assert_eq!(Bitboard::file(File::C) & Bitboard::rank(Rank::THREE), Bitboard::from_square(Square::C3));
+    }
+
+    #[test]
+    fn squares_can_be_removed() {
+        let board = Bitboard::from_square(Square::E4).without(Square::E4);
+        assert!(board.is_empty());
+    }
+}
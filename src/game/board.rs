@@ -0,0 +1,739 @@
+use std::fmt;
+
+use super::attacks::*;
+use super::bitboard::*;
+use super::chess_move::*;
+use super::color::*;
+use super::piece_type::*;
+use super::position::*;
+use super::square::*;
+use super::zobrist::ZOBRIST;
+
+// which castling moves are still available, one bit per king and side
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CastlingRights(pub u8);
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights(0);
+    pub const WHITE_KING_SIDE: CastlingRights = CastlingRights(1);
+    pub const WHITE_QUEEN_SIDE: CastlingRights = CastlingRights(2);
+    pub const BLACK_KING_SIDE: CastlingRights = CastlingRights(4);
+    pub const BLACK_QUEEN_SIDE: CastlingRights = CastlingRights(8);
+    pub const ALL: CastlingRights = CastlingRights(15);
+
+    pub fn king_side(color: Color) -> CastlingRights {
+        match color {
+            Color::White => CastlingRights::WHITE_KING_SIDE,
+            Color::Black => CastlingRights::BLACK_KING_SIDE,
+        }
+    }
+
+    pub fn queen_side(color: Color) -> CastlingRights {
+        match color {
+            Color::White => CastlingRights::WHITE_QUEEN_SIDE,
+            Color::Black => CastlingRights::BLACK_QUEEN_SIDE,
+        }
+    }
+
+    pub fn contains(self, rights: CastlingRights) -> bool {
+        self.0 & rights.0 == rights.0
+    }
+
+    pub fn with(self, rights: CastlingRights) -> CastlingRights {
+        CastlingRights(self.0 | rights.0)
+    }
+
+    pub fn without(self, rights: CastlingRights) -> CastlingRights {
+        CastlingRights(self.0 & !rights.0)
+    }
+
+    // the rights lost when a piece moves from or to the square (king and rook home squares)
+    fn lost_by_touching(square: Square) -> CastlingRights {
+        match square {
+            Square::E1 => CastlingRights(CastlingRights::WHITE_KING_SIDE.0 | CastlingRights::WHITE_QUEEN_SIDE.0),
+            Square::H1 => CastlingRights::WHITE_KING_SIDE,
+            Square::A1 => CastlingRights::WHITE_QUEEN_SIDE,
+            Square::E8 => CastlingRights(CastlingRights::BLACK_KING_SIDE.0 | CastlingRights::BLACK_QUEEN_SIDE.0),
+            Square::H8 => CastlingRights::BLACK_KING_SIDE,
+            Square::A8 => CastlingRights::BLACK_QUEEN_SIDE,
+            _ => CastlingRights::NONE,
+        }
+    }
+}
+
+// what is needed to take back a move exactly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Undo {
+    chess_move: Move,
+    moved: PieceType,
+    captured: PieceType,
+    castling: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    hash: u64,
+}
+
+// A board held as one bitboard per piece type and per colour (plus a square-by-square lookup of piece
+// types). Moves are made and unmade in place, with the hash, castling rights, en passant square and clocks
+// updated incrementally rather than recalculated.
+#[derive(Clone, Debug)]
+pub struct Board {
+    pieces: [Bitboard; 7],
+    colors: [Bitboard; 2],
+    squares: [PieceType; 64],
+    side_to_move: Color,
+    castling: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    hash: u64,
+    history: Vec<Undo>,
+    // the hashes of positions before the moves which can be unmade, oldest first, counted for repetition
+    earlier: Vec<u64>,
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
+}
+
+// boards are equal when they describe the same position, whatever moves led to them
+impl PartialEq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        self.pieces == other.pieces
+            && self.colors == other.colors
+            && self.side_to_move == other.side_to_move
+            && self.castling == other.castling
+            && self.en_passant == other.en_passant
+            && self.halfmove_clock == other.halfmove_clock
+            && self.fullmove_number == other.fullmove_number
+    }
+}
+
+impl Eq for Board {}
+
+impl Board {
+    // the standard starting position
+    pub fn new() -> Board {
+        Board::from_positions(&INITIAL_POSITIONS, Color::White)
+    }
+
+    fn empty() -> Board {
+        Board {
+            pieces: [Bitboard::EMPTY; 7],
+            colors: [Bitboard::EMPTY; 2],
+            squares: [PieceType::Empty; 64],
+            side_to_move: Color::White,
+            castling: CastlingRights::NONE,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+            history: vec![],
+            earlier: vec![],
+        }
+    }
+
+    // Builds a board from a list of positions. Castling rights are assumed wherever a king and rook are
+    // still on their starting squares, as the list doesn't say whether they have moved.
+    pub fn from_positions(positions: &[Position], side_to_move: Color) -> Board {
+        let mut board = Board::empty();
+        for position in positions.iter().filter(|p| p.piece_type != PieceType::Empty) {
+            board.put_piece(position.color, position.piece_type, position.square);
+        }
+        board.side_to_move = side_to_move;
+        let mut castling = CastlingRights::NONE;
+        for color in Color::all() {
+            let home = |file| Square::new(file, Rank::ONE.relative_to(color));
+            let has = |piece_type, square| board.piece_at(square) == Some((piece_type, color));
+            if has(PieceType::King, home(File::E)) {
+                if has(PieceType::Rook, home(File::H)) {
+                    castling = castling.with(CastlingRights::king_side(color));
+                }
+                if has(PieceType::Rook, home(File::A)) {
+                    castling = castling.with(CastlingRights::queen_side(color));
+                }
+            }
+        }
+        board.castling = castling;
+        board.hash = board.compute_hash();
+        board
+    }
+
+    // Builds a board from its full state (as found in FEN, for example)
+    pub fn from_parts(
+        positions: &[Position],
+        side_to_move: Color,
+        castling: CastlingRights,
+        en_passant: Option<Square>,
+        halfmove_clock: u32,
+        fullmove_number: u32,
+    ) -> Board {
+        let mut board = Board::from_positions(positions, side_to_move);
+        board.castling = castling;
+        board.en_passant = en_passant;
+        board.halfmove_clock = halfmove_clock;
+        board.fullmove_number = fullmove_number;
+        board.hash = board.compute_hash();
+        board
+    }
+
+    pub fn to_positions(&self) -> Vec<Position> {
+        let mut positions: Vec<Position> = self
+            .occupied()
+            .squares()
+            .map(|square| {
+                let (piece_type, color) = self.piece_at(square).unwrap();
+                Position { square, piece_type, color }
+            })
+            .collect();
+        positions.sort();
+        positions
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling
+    }
+
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    // half moves since the last capture or pawn move (for the fifty-move rule)
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // the number of moves made on this board which can be unmade
+    pub fn ply(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        self.history.last().map(|undo| undo.chess_move)
+    }
+
+    // has the position (with the same side to move) occurred before since the last capture or pawn move?
+    pub fn is_repetition(&self) -> bool {
+        self.history
+            .iter()
+            .rev()
+            .map(|undo| undo.hash)
+            .chain(self.earlier.iter().rev().copied())
+            .take(self.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|hash| hash == self.hash)
+    }
+
+    // Forgets the moves made on the board, which can't be unmade afterwards, and the positions before them, so
+    // that copying the board costs the same however it was reached.
+    pub fn forget_moves(&mut self) {
+        self.history.clear();
+        self.earlier.clear();
+    }
+
+    // the positions before this one, oldest first, by their hashes, for repetitions of them to be recognised
+    pub fn set_earlier_positions(&mut self, hashes: Vec<u64>) {
+        self.earlier = hashes;
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    pub fn color(&self, color: Color) -> Bitboard {
+        self.colors[color.index()]
+    }
+
+    pub fn pieces(&self, piece_type: PieceType) -> Bitboard {
+        self.pieces[piece_type as usize]
+    }
+
+    pub fn pieces_of(&self, color: Color, piece_type: PieceType) -> Bitboard {
+        self.pieces[piece_type as usize] & self.colors[color.index()]
+    }
+
+    pub fn piece_type_at(&self, square: Square) -> PieceType {
+        self.squares[square.index() as usize]
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<(PieceType, Color)> {
+        match self.squares[square.index() as usize] {
+            PieceType::Empty => None,
+            piece_type => {
+                let color = if self.colors[0].contains(square) { Color::White } else { Color::Black };
+                Some((piece_type, color))
+            }
+        }
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        self.pieces_of(color, PieceType::King).first()
+    }
+
+    // every piece, of either colour, attacking the square given the occupied squares (which can differ
+    // from the board's to see through pieces which are about to move)
+    pub fn attackers_to(&self, square: Square, occupied: Bitboard) -> Bitboard {
+        let diagonal = self.pieces(PieceType::Bishop) | self.pieces(PieceType::Queen);
+        let straight = self.pieces(PieceType::Rook) | self.pieces(PieceType::Queen);
+        (pawn_attacks(Color::Black, square) & self.pieces_of(Color::White, PieceType::Pawn))
+            | (pawn_attacks(Color::White, square) & self.pieces_of(Color::Black, PieceType::Pawn))
+            | (knight_attacks(square) & self.pieces(PieceType::Knight))
+            | (king_attacks(square) & self.pieces(PieceType::King))
+            | (bishop_attacks(square, occupied) & diagonal)
+            | (rook_attacks(square, occupied) & straight)
+    }
+
+    pub fn is_attacked(&self, square: Square, by: Color) -> bool {
+        (self.attackers_to(square, self.occupied()) & self.color(by)).any()
+    }
+
+    // the opponent's pieces giving check to the side to move
+    pub fn checkers(&self) -> Bitboard {
+        match self.king_square(self.side_to_move) {
+            Some(king) => self.attackers_to(king, self.occupied()) & self.color(!self.side_to_move),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.checkers().any()
+    }
+
+    // the hash calculated from scratch, which the incrementally updated hash should always equal
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for square in self.occupied().squares() {
+            let (piece_type, color) = self.piece_at(square).unwrap();
+            hash ^= ZOBRIST.piece(color, piece_type, square);
+        }
+        hash ^= ZOBRIST.castling(self.castling.0);
+        if let Some(square) = self.en_passant {
+            hash ^= ZOBRIST.en_passant(square.file());
+        }
+        if self.side_to_move == Color::Black {
+            hash ^= ZOBRIST.black_to_move();
+        }
+        hash
+    }
+
+    fn put_piece(&mut self, color: Color, piece_type: PieceType, square: Square) {
+        let bit = Bitboard::from_square(square);
+        self.pieces[piece_type as usize] |= bit;
+        self.colors[color.index()] |= bit;
+        self.squares[square.index() as usize] = piece_type;
+        self.hash ^= ZOBRIST.piece(color, piece_type, square);
+    }
+
+    fn remove_piece(&mut self, color: Color, piece_type: PieceType, square: Square) {
+        let bit = Bitboard::from_square(square);
+        self.pieces[piece_type as usize] ^= bit;
+        self.colors[color.index()] ^= bit;
+        self.squares[square.index() as usize] = PieceType::Empty;
+        self.hash ^= ZOBRIST.piece(color, piece_type, square);
+    }
+
+    fn set_castling(&mut self, castling: CastlingRights) {
+        self.hash ^= ZOBRIST.castling(self.castling.0) ^ ZOBRIST.castling(castling.0);
+        self.castling = castling;
+    }
+
+    fn set_en_passant(&mut self, en_passant: Option<Square>) {
+        if let Some(square) = self.en_passant {
+            self.hash ^= ZOBRIST.en_passant(square.file());
+        }
+        if let Some(square) = en_passant {
+            self.hash ^= ZOBRIST.en_passant(square.file());
+        }
+        self.en_passant = en_passant;
+    }
+
+    // the rook's origin and destination when the king castles to the given square
+    fn castling_rook(king_to: Square) -> (Square, Square) {
+        let rank = king_to.rank();
+        if king_to.file() == File::G {
+            (Square::new(File::H, rank), Square::new(File::F, rank))
+        } else {
+            (Square::new(File::A, rank), Square::new(File::D, rank))
+        }
+    }
+
+    pub fn is_castling(&self, chess_move: &Move) -> bool {
+        self.piece_type_at(chess_move.from) == PieceType::King
+            && (chess_move.from.file().index() as i8 - chess_move.to.file().index() as i8).abs() == 2
+    }
+
+    pub fn is_en_passant(&self, chess_move: &Move) -> bool {
+        self.piece_type_at(chess_move.from) == PieceType::Pawn
+            && Some(chess_move.to) == self.en_passant
+            && chess_move.from.file() != chess_move.to.file()
+    }
+
+    pub fn is_capture(&self, chess_move: &Move) -> bool {
+        self.piece_at(chess_move.to).is_some() || self.is_en_passant(chess_move)
+    }
+
+    // the piece type which the move would capture, if any
+    pub fn captured_piece_type(&self, chess_move: &Move) -> Option<PieceType> {
+        if self.is_en_passant(chess_move) {
+            Some(PieceType::Pawn)
+        } else {
+            self.piece_at(chess_move.to).map(|(piece_type, _)| piece_type)
+        }
+    }
+
+    // Makes the move in place. The move is assumed to be legal for the side to move - castling is recognised
+    // by the king moving two files and en passant by a pawn moving diagonally onto the en passant square.
+    pub fn make_move(&mut self, chess_move: Move) {
+        let color = self.side_to_move;
+        if chess_move.is_null() {
+            self.make_null_move();
+            return;
+        }
+        let moved = self.piece_type_at(chess_move.from);
+        let en_passant = self.is_en_passant(&chess_move);
+        let castling = self.is_castling(&chess_move);
+        let captured_square = if en_passant {
+            Square::new(chess_move.to.file(), chess_move.from.rank())
+        } else {
+            chess_move.to
+        };
+        let captured = self.piece_type_at(captured_square);
+        self.history.push(Undo {
+            chess_move,
+            moved,
+            captured,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        });
+
+        if captured != PieceType::Empty {
+            self.remove_piece(!color, captured, captured_square);
+        }
+        self.remove_piece(color, moved, chess_move.from);
+        self.put_piece(color, chess_move.promotion.unwrap_or(moved), chess_move.to);
+        if castling {
+            let (rook_from, rook_to) = Board::castling_rook(chess_move.to);
+            self.remove_piece(color, PieceType::Rook, rook_from);
+            self.put_piece(color, PieceType::Rook, rook_to);
+        }
+
+        let double_step = moved == PieceType::Pawn
+            && (chess_move.from.rank().index() as i8 - chess_move.to.rank().index() as i8).abs() == 2;
+        self.set_en_passant(if double_step {
+            Square::from_index((chess_move.from.index() + chess_move.to.index()) / 2)
+        } else {
+            None
+        });
+        let castling_rights = self
+            .castling
+            .without(CastlingRights::lost_by_touching(chess_move.from))
+            .without(CastlingRights::lost_by_touching(chess_move.to));
+        self.set_castling(castling_rights);
+
+        if moved == PieceType::Pawn || captured != PieceType::Empty {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = !color;
+        self.hash ^= ZOBRIST.black_to_move();
+    }
+
+    // only the turn passes; nothing earlier can repeat after it, as the side to move differs
+    fn make_null_move(&mut self) {
+        self.history.push(Undo {
+            chess_move: Move::NULL,
+            moved: PieceType::Empty,
+            captured: PieceType::Empty,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        });
+        self.set_en_passant(None);
+        self.halfmove_clock = 0;
+        if self.side_to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = !self.side_to_move;
+        self.hash ^= ZOBRIST.black_to_move();
+    }
+
+    // Takes back the last move made, restoring the board exactly. Returns the move, or None if there are no
+    // moves to take back.
+    pub fn unmake_move(&mut self) -> Option<Move> {
+        let undo = self.history.pop()?;
+        let chess_move = undo.chess_move;
+        let color = !self.side_to_move;
+        self.side_to_move = color;
+        if color == Color::Black {
+            self.fullmove_number -= 1;
+        }
+        if chess_move.is_null() {
+            self.en_passant = undo.en_passant;
+            self.halfmove_clock = undo.halfmove_clock;
+            self.hash = undo.hash;
+            return Some(chess_move);
+        }
+
+        self.remove_piece(color, chess_move.promotion.unwrap_or(undo.moved), chess_move.to);
+        self.put_piece(color, undo.moved, chess_move.from);
+        if undo.moved == PieceType::King
+            && (chess_move.from.file().index() as i8 - chess_move.to.file().index() as i8).abs() == 2
+        {
+            let (rook_from, rook_to) = Board::castling_rook(chess_move.to);
+            self.remove_piece(color, PieceType::Rook, rook_to);
+            self.put_piece(color, PieceType::Rook, rook_from);
+        }
+        if undo.captured != PieceType::Empty {
+            let en_passant = undo.moved == PieceType::Pawn
+                && Some(chess_move.to) == undo.en_passant
+                && chess_move.from.file() != chess_move.to.file();
+            let captured_square = if en_passant {
+                Square::new(chess_move.to.file(), chess_move.from.rank())
+            } else {
+                chess_move.to
+            };
+            self.put_piece(!color, undo.captured, captured_square);
+        }
+
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+        Some(chess_move)
+    }
+}
+
+// the board as text with rank 8 at the top, upper case for white and lower case for black
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rank in Rank::all().collect::<Vec<Rank>>().into_iter().rev() {
+            let row: String = File::all()
+                .map(|file| match self.piece_at(Square::new(file, rank)) {
+                    Some((piece_type, color)) => {
+                        let symbol = piece_type.symbol().unwrap_or('P');
+                        if color.is_white() { symbol } else { symbol.to_ascii_lowercase() }
+                    }
+                    None => '.',
+                })
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn play(board: &mut Board, moves: &[(Square, Square)]) {
+        for (from, to) in moves.iter() {
+            board.make_move(Move::new(*from, *to));
+            assert_eq!(board.hash(), board.compute_hash());
+        }
+    }
+
+    fn unplay(board: &mut Board, count: usize) {
+        for _ in 0..count {
+            board.unmake_move().unwrap();
+            assert_eq!(board.hash(), board.compute_hash());
+        }
+    }
+
+    #[test]
+    fn the_initial_board_matches_the_initial_positions() {
+        let board = Board::new();
+        assert_eq!(board.to_positions(), calculate_positions(vec![]));
+        assert_eq!(board.castling_rights(), CastlingRights::ALL);
+        assert_eq!(board.side_to_move(), Color::White);
+        assert_eq!(board.occupied().count(), 32);
+        assert_eq!(board.king_square(Color::Black), Some(Square::E8));
+    }
+
+    #[test]
+    fn making_and_unmaking_moves_restores_the_board_exactly() {
+        let mut board = Board::new();
+        let initial = board.clone();
+        play(&mut board, &[(Square::E2, Square::E4), (Square::D7, Square::D5), (Square::E4, Square::D5)]);
+        assert_eq!(board.piece_at(Square::D5), Some((PieceType::Pawn, Color::White)));
+        assert_eq!(board.occupied().count(), 31);
+        assert_eq!(board.fullmove_number(), 2);
+        unplay(&mut board, 3);
+        assert_eq!(board, initial);
+        assert_eq!(board.hash(), initial.hash());
+        assert_eq!(board.unmake_move(), None);
+    }
+
+    #[test]
+    fn a_double_pawn_step_sets_the_en_passant_square() {
+        let mut board = Board::new();
+        play(&mut board, &[(Square::E2, Square::E4)]);
+        assert_eq!(board.en_passant(), Some(Square::E3));
+        play(&mut board, &[(Square::G8, Square::F6)]);
+        assert_eq!(board.en_passant(), None);
+    }
+
+    #[test]
+    fn en_passant_captures_the_passed_pawn() {
+        let mut board = Board::new();
+        play(
+            &mut board,
+            &[(Square::E2, Square::E4), (Square::A7, Square::A6), (Square::E4, Square::E5), (Square::D7, Square::D5)],
+        );
+        let before = board.clone();
+        assert!(board.is_en_passant(&Move::new(Square::E5, Square::D6)));
+        play(&mut board, &[(Square::E5, Square::D6)]);
+        assert_eq!(board.piece_at(Square::D5), None);
+        assert_eq!(board.piece_at(Square::D6), Some((PieceType::Pawn, Color::White)));
+        unplay(&mut board, 1);
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn castling_moves_the_rook_and_removes_the_rights() {
+        let positions = vec![
+            Position { square: Square::E1, piece_type: PieceType::King, color: Color::White },
+            Position { square: Square::H1, piece_type: PieceType::Rook, color: Color::White },
+            Position { square: Square::A1, piece_type: PieceType::Rook, color: Color::White },
+            Position { square: Square::E8, piece_type: PieceType::King, color: Color::Black },
+        ];
+        let mut board = Board::from_positions(&positions, Color::White);
+        assert_eq!(board.castling_rights(), CastlingRights(3));
+        let before = board.clone();
+        play(&mut board, &[(Square::E1, Square::G1)]);
+        assert_eq!(board.piece_at(Square::F1), Some((PieceType::Rook, Color::White)));
+        assert_eq!(board.piece_at(Square::H1), None);
+        assert_eq!(board.castling_rights(), CastlingRights::NONE);
+        unplay(&mut board, 1);
+        assert_eq!(board, before);
+        play(&mut board, &[(Square::E1, Square::C1)]);
+        assert_eq!(board.piece_at(Square::D1), Some((PieceType::Rook, Color::White)));
+    }
+
+    #[test]
+    fn capturing_a_rook_removes_its_castling_right() {
+        let positions = vec![
+            Position { square: Square::E1, piece_type: PieceType::King, color: Color::White },
+            Position { square: Square::E8, piece_type: PieceType::King, color: Color::Black },
+            Position { square: Square::H8, piece_type: PieceType::Rook, color: Color::Black },
+            Position { square: Square::B2, piece_type: PieceType::Bishop, color: Color::White },
+        ];
+        let mut board = Board::from_positions(&positions, Color::White);
+        assert_eq!(board.castling_rights(), CastlingRights::BLACK_KING_SIDE);
+        play(&mut board, &[(Square::B2, Square::H8)]);
+        assert_eq!(board.castling_rights(), CastlingRights::NONE);
+        assert_eq!(board.halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn promotion_replaces_the_pawn() {
+        let positions = vec![
+            Position { square: Square::E1, piece_type: PieceType::King, color: Color::White },
+            Position { square: Square::E8, piece_type: PieceType::King, color: Color::Black },
+            Position { square: Square::A7, piece_type: PieceType::Pawn, color: Color::White },
+            Position { square: Square::B8, piece_type: PieceType::Knight, color: Color::Black },
+        ];
+        let mut board = Board::from_positions(&positions, Color::White);
+        let before = board.clone();
+        board.make_move(Move::promoting(Square::A7, Square::B8, PieceType::Queen));
+        assert_eq!(board.hash(), board.compute_hash());
+        assert_eq!(board.piece_at(Square::B8), Some((PieceType::Queen, Color::White)));
+        assert_eq!(board.pieces(PieceType::Pawn), Bitboard::EMPTY);
+        unplay(&mut board, 1);
+        assert_eq!(board, before);
+        assert_eq!(board.to_positions(), {
+            let mut sorted = positions.clone();
+            sorted.sort();
+            sorted
+        });
+    }
+
+    #[test]
+    fn the_clocks_count_moves() {
+        let mut board = Board::new();
+        play(&mut board, &[(Square::G1, Square::F3), (Square::G8, Square::F6), (Square::F3, Square::G1)]);
+        assert_eq!(board.halfmove_clock(), 3);
+        assert_eq!(board.fullmove_number(), 2);
+        play(&mut board, &[(Square::E7, Square::E5)]);
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 3);
+    }
+
+    #[test]
+    fn transpositions_have_the_same_hash() {
+        let mut first = Board::new();
+        play(&mut first, &[(Square::G1, Square::F3), (Square::G8, Square::F6), (Square::B1, Square::C3)]);
+        let mut second = Board::new();
+        play(&mut second, &[(Square::B1, Square::C3), (Square::G8, Square::F6), (Square::G1, Square::F3)]);
+        assert_eq!(first.hash(), second.hash());
+        assert_ne!(first.hash(), Board::new().hash());
+    }
+
+    #[test]
+    fn shuffling_pieces_back_repeats_the_position() {
+        let mut board = Board::new();
+        assert!(!board.is_repetition());
+        for (from, to) in [(Square::G1, Square::F3), (Square::G8, Square::F6), (Square::F3, Square::G1)] {
+            board.make_move(Move::new(from, to));
+            assert!(!board.is_repetition());
+        }
+        board.make_move(Move::new(Square::F6, Square::G8));
+        assert!(board.is_repetition());
+        // a pawn move can't be undone so nothing before it can repeat
+        board.make_move(Move::new(Square::E2, Square::E4));
+        assert!(!board.is_repetition());
+        board.forget_moves();
+        assert_eq!(board.ply(), 0);
+        assert_eq!(board.unmake_move(), None);
+    }
+
+    #[test]
+    fn earlier_positions_count_for_repetition() {
+        let start = Board::new();
+        let mut board = Board::new();
+        play(&mut board, &[(Square::G1, Square::F3), (Square::G8, Square::F6)]);
+        board.forget_moves();
+        play(&mut board, &[(Square::F3, Square::G1), (Square::F6, Square::G8)]);
+        assert!(!board.is_repetition());
+        let mut middle = Board::new();
+        play(&mut middle, &[(Square::G1, Square::F3)]);
+        board.set_earlier_positions(vec![start.hash(), middle.hash()]);
+        assert!(board.is_repetition());
+    }
+
+    #[test]
+    fn a_null_move_passes_the_turn_and_can_be_taken_back() {
+        let mut board = crate::game::fen::decode("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let before = board.clone();
+        board.make_move(Move::NULL);
+        assert_eq!(board.side_to_move(), Color::Black);
+        assert_eq!(board.en_passant(), None);
+        assert_eq!(board.hash(), board.compute_hash());
+        assert_eq!(board.unmake_move(), Some(Move::NULL));
+        assert_eq!(board, before);
+        assert_eq!(board.hash(), before.hash());
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+
+use super::piece_type::*;
+use super::square::*;
+
+// a move of a piece from one square to another, optionally promoting a pawn on arrival
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    // passing the turn without moving, which the search uses to see whether a position is good even if the
+    // opponent were allowed a free move (it's never legal in a game)
+    pub const NULL: Move = Move {
+        from: Square::A1,
+        to: Square::A1,
+        promotion: None,
+    };
+
+    pub fn is_null(&self) -> bool {
+        self.from == self.to
+    }
+
+    pub fn new(from: Square, to: Square) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+
+    pub fn promoting(from: Square, to: Square, promotion: PieceType) -> Move {
+        Move {
+            from,
+            to,
+            promotion: Some(promotion),
+        }
+    }
+}
+
+// long algebraic notation, e.g. "e2-e4" or "e7-e8=Q"
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.from, self.to)?;
+        match self.promotion.and_then(|piece_type| piece_type.symbol()) {
+            Some(symbol) => write!(f, "={}", symbol),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn moves_display_in_long_algebraic_notation() {
+        assert_eq!(Move::new(Square::E2, Square::E4).to_string(), "e2-e4");
+        assert_eq!(
+            Move::promoting(Square::E7, Square::E8, PieceType::Queen).to_string(),
+            "e7-e8=Q"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use wasm_bindgen::prelude::*;
+
+use super::game::Game;
+use super::rules::build_rules;
+
+// the outcome of checking a single rule from build_rules() against a proposed move
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleEvaluation {
+    pub article: String,
+    pub title: String,
+    pub passed: bool,
+    pub reason: Option<String>,
+}
+
+// a report of every rule which was checked for a proposed move, in the order they were checked
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveDiagnostics {
+    pub next_move: String,
+    pub evaluations: Vec<RuleEvaluation>,
+}
+
+#[wasm_bindgen]
+impl MoveDiagnostics {
+    // the move is accepted only if every rule passed
+    pub fn accepted(&self) -> bool {
+        self.evaluations.iter().all(|evaluation| evaluation.passed)
+    }
+
+    pub fn failures(&self) -> Vec<RuleEvaluation> {
+        self.evaluations
+            .iter()
+            .filter(|evaluation| !evaluation.passed)
+            .cloned()
+            .collect()
+    }
+}
+
+// runs every rule against the proposed move (rather than stopping at the first failure) so that the report
+// explains everything which is wrong with it
+pub fn diagnose_move(game: &Game, next_move: &str) -> MoveDiagnostics {
+    let evaluations = build_rules()
+        .iter()
+        .map(|rule| {
+            let result = rule.validate(game, next_move.to_string());
+            RuleEvaluation {
+                article: rule.article().to_string(),
+                title: rule.title().to_string(),
+                passed: result.is_ok(),
+                reason: result.err().map(|err| err.reason),
+            }
+        })
+        .collect();
+    MoveDiagnostics {
+        next_move: next_move.to_string(),
+        evaluations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::game_state::GameState;
+
+    #[test]
+    fn every_rule_is_reported_for_an_accepted_move() {
+        let diagnostics = diagnose_move(&Game::new(), "e4");
+        assert!(diagnostics.accepted());
+        let articles: Vec<&str> = diagnostics
+            .evaluations
+            .iter()
+            .map(|evaluation| evaluation.article.as_str())
+            .collect();
+        assert_eq!(articles, vec!["1.2", "1.3", "1.4", "1.5", "2.1", "3.10"]);
+        assert!(diagnostics.failures().is_empty());
+    }
+
+    #[test]
+    fn every_failing_rule_is_reported_with_its_reason() {
+        let game = Game {
+            state: GameState::WhiteCheckmate,
+            ..Game::new()
+        };
+        let diagnostics = diagnose_move(&game, "i9");
+        assert!(!diagnostics.accepted());
+        let failures: Vec<(String, Option<String>)> = diagnostics
+            .failures()
+            .into_iter()
+            .map(|evaluation| (evaluation.article, evaluation.reason))
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                ("1.4".to_string(), Some("Attempt to move after checkmate".to_string())),
+                ("1.5".to_string(), Some("Attempt to move after stalemate".to_string())),
+                ("2.1".to_string(), Some("Move is outside the confines of the chess board".to_string())),
+            ]
+        );
+    }
+}
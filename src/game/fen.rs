@@ -0,0 +1,272 @@
+// Forsyth–Edwards Notation: a position in one line of text, e.g. the starting position is
+// "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+
+use super::board::*;
+use super::color::*;
+use super::piece_type::*;
+use super::position::*;
+use super::square::*;
+
+pub const INITIAL_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, PartialEq)]
+pub struct FenError {
+    pub reason: String,
+}
+
+fn invalid<T>(why: String) -> Result<T, FenError> {
+    Err(FenError { reason: why })
+}
+
+fn decode_piece(c: char) -> Option<(PieceType, Color)> {
+    let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+    let piece_type = match c.to_ascii_uppercase() {
+        'K' => PieceType::King,
+        'Q' => PieceType::Queen,
+        'R' => PieceType::Rook,
+        'B' => PieceType::Bishop,
+        'N' => PieceType::Knight,
+        'P' => PieceType::Pawn,
+        _ => return None,
+    };
+    Some((piece_type, color))
+}
+
+fn decode_placement(placement: &str) -> Result<Vec<Position>, FenError> {
+    let rows: Vec<&str> = placement.split('/').collect();
+    if rows.len() != 8 {
+        return invalid(format!("Expected 8 ranks but found {}", rows.len()));
+    }
+    let mut positions = vec![];
+    // ranks are listed from the eighth down to the first
+    for (row, rank) in rows.iter().zip(Rank::all().collect::<Vec<Rank>>().into_iter().rev()) {
+        let mut file = 0;
+        for c in row.chars() {
+            if let Some(empty) = c.to_digit(10).filter(|empty| (1..=8).contains(empty)) {
+                if file + empty as u8 > 8 {
+                    return invalid(format!("Too many squares on rank {}", rank));
+                }
+                file += empty as u8;
+                continue;
+            }
+            match (decode_piece(c), File::new(file)) {
+                (Some((PieceType::Pawn, _)), Some(_)) if rank == Rank::ONE || rank == Rank::EIGHT => {
+                    return invalid(format!("Pawn on rank {}", rank))
+                }
+                (Some((piece_type, color)), Some(file)) => positions.push(Position {
+                    square: Square::new(file, rank),
+                    piece_type,
+                    color,
+                }),
+                (None, _) => return invalid(format!("Invalid piece: {}", c)),
+                (_, None) => return invalid(format!("Too many squares on rank {}", rank)),
+            }
+            file += 1;
+        }
+        if file != 8 {
+            return invalid(format!("Expected 8 squares on rank {} but found {}", rank, file));
+        }
+    }
+    Ok(positions)
+}
+
+fn decode_castling(castling: &str) -> Result<CastlingRights, FenError> {
+    if castling == "-" {
+        return Ok(CastlingRights::NONE);
+    }
+    castling.chars().try_fold(CastlingRights::NONE, |rights, c| {
+        let right = match c {
+            'K' => CastlingRights::WHITE_KING_SIDE,
+            'Q' => CastlingRights::WHITE_QUEEN_SIDE,
+            'k' => CastlingRights::BLACK_KING_SIDE,
+            'q' => CastlingRights::BLACK_QUEEN_SIDE,
+            _ => return invalid(format!("Invalid castling rights: {}", castling)),
+        };
+        Ok(rights.with(right))
+    })
+}
+
+fn decode_number(field: Option<&&str>, default: u32, name: &str) -> Result<u32, FenError> {
+    match field {
+        None => Ok(default),
+        Some(text) => text
+            .parse()
+            .or_else(|_| invalid(format!("Invalid {}: {}", name, text))),
+    }
+}
+
+fn is_en_passant_square(positions: &[Position], square: Square, side_to_move: Color) -> bool {
+    let forward = if side_to_move.is_white() { 1 } else { -1 };
+    let occupant = |rank_delta| {
+        let square = square.offset(0, rank_delta)?;
+        positions.iter().find(|p| p.square == square)
+    };
+    square.rank() == Rank::SIX.relative_to(side_to_move)
+        && occupant(0).is_none()
+        && occupant(forward).is_none()
+        && occupant(-forward).is_some_and(|p| p.piece_type == PieceType::Pawn && p.color != side_to_move)
+}
+
+// the move clocks may be left off, in which case they are taken to be "0 1"
+pub fn decode(fen: &str) -> Result<Board, FenError> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 || fields.len() > 6 {
+        return invalid(format!("Expected 4 to 6 fields but found {}", fields.len()));
+    }
+    let positions = decode_placement(fields[0])?;
+    let side_to_move = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        other => return invalid(format!("Invalid side to move: {}", other)),
+    };
+    let castling = decode_castling(fields[2])?;
+    let en_passant = match fields[3] {
+        "-" => None,
+        // the square passed over by the pawn which just moved two squares, so behind it as the side to move sees
+        // it, with the pawn beyond it and nothing on it or the square the pawn came from
+        name => match name.parse::<Square>() {
+            Ok(square) if is_en_passant_square(&positions, square, side_to_move) => Some(square),
+            _ => return invalid(format!("Invalid en passant square: {}", name)),
+        },
+    };
+    let halfmove_clock = decode_number(fields.get(4), 0, "halfmove clock")?;
+    let fullmove_number = decode_number(fields.get(5), 1, "fullmove number")?;
+    for color in Color::all() {
+        let kings = positions
+            .iter()
+            .filter(|p| p.color == color && p.piece_type == PieceType::King)
+            .count();
+        if kings != 1 {
+            return invalid(format!("Expected one {} king but found {}", color, kings));
+        }
+    }
+    Ok(Board::from_parts(
+        &positions,
+        side_to_move,
+        castling,
+        en_passant,
+        halfmove_clock,
+        fullmove_number,
+    ))
+}
+
+pub fn encode(board: &Board) -> String {
+    let mut rows = vec![];
+    for rank in Rank::all().collect::<Vec<Rank>>().into_iter().rev() {
+        let mut row = String::new();
+        let mut empty = 0;
+        for file in File::all() {
+            match board.piece_at(Square::new(file, rank)) {
+                Some((piece_type, color)) => {
+                    if empty > 0 {
+                        row.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    let symbol = piece_type.symbol().unwrap_or('P');
+                    row.push(if color.is_white() { symbol } else { symbol.to_ascii_lowercase() });
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            row.push_str(&empty.to_string());
+        }
+        rows.push(row);
+    }
+    let rights = board.castling_rights();
+    let castling: String = [
+        (CastlingRights::WHITE_KING_SIDE, 'K'),
+        (CastlingRights::WHITE_QUEEN_SIDE, 'Q'),
+        (CastlingRights::BLACK_KING_SIDE, 'k'),
+        (CastlingRights::BLACK_QUEEN_SIDE, 'q'),
+    ]
+    .iter()
+    .filter(|(right, _)| rights.contains(*right))
+    .map(|(_, symbol)| *symbol)
+    .collect();
+    format!(
+        "{} {} {} {} {} {}",
+        rows.join("/"),
+        if board.side_to_move().is_white() { "w" } else { "b" },
+        if castling.is_empty() { "-".to_string() } else { castling },
+        board.en_passant().map_or("-".to_string(), |square| square.to_string()),
+        board.halfmove_clock(),
+        board.fullmove_number()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn the_initial_fen_decodes_to_the_initial_board() {
+        assert_eq!(decode(INITIAL_FEN).unwrap(), Board::new());
+        assert_eq!(encode(&Board::new()), INITIAL_FEN);
+    }
+
+    #[test]
+    fn fens_round_trip() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ]
+        .iter()
+        {
+            assert_eq!(encode(&decode(fen).unwrap()), *fen);
+        }
+    }
+
+    #[test]
+    fn the_move_clocks_are_optional() {
+        let board = decode("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 1);
+    }
+
+    #[test]
+    fn decoding_reads_the_whole_state() {
+        let board = decode("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR b Kq e3 3 7").unwrap();
+        assert_eq!(board.side_to_move(), Color::Black);
+        assert_eq!(board.castling_rights(), CastlingRights(9));
+        assert_eq!(board.en_passant(), Some(Square::E3));
+        assert_eq!(board.halfmove_clock(), 3);
+        assert_eq!(board.fullmove_number(), 7);
+        assert_eq!(board.piece_at(Square::C5), Some((PieceType::Pawn, Color::Black)));
+    }
+
+    #[test]
+    fn invalid_fens_fail() {
+        for (fen, reason) in [
+            ("8/8/8/8/8/8/8 w - -", "Expected 8 ranks but found 7"),
+            ("4k3/8/8/8/8/8/8/4K3", "Expected 4 to 6 fields but found 1"),
+            ("4k3/8/8/8/8/8/8/4K2 w - -", "Expected 8 squares on rank 1 but found 7"),
+            ("4k3/8/8/8/8/8/8/4K3P w - -", "Too many squares on rank 1"),
+            ("4k3/8/8/8/8/8/8/4X3 w - -", "Invalid piece: X"),
+            ("4k3/8/8/8/8/8/8/4K3 x - -", "Invalid side to move: x"),
+            ("4k3/8/8/8/8/8/8/4K3 w KX -", "Invalid castling rights: KX"),
+            ("4k3/8/8/8/8/8/8/4K3 w - e9", "Invalid en passant square: e9"),
+            ("4k3/8/8/8/8/8/8/4K3 w - a1", "Invalid en passant square: a1"),
+            ("4k3/8/8/8/8/8/8/4K3 w - e3", "Invalid en passant square: e3"),
+            ("4k3/8/8/8/8/8/8/4K3 b - e6", "Invalid en passant square: e6"),
+            ("4k3/8/8/3Pn3/8/8/8/4K3 w - e6 0 1", "Invalid en passant square: e6"),
+            ("4k3/8/8/3PP3/8/8/8/4K3 w - e6 0 1", "Invalid en passant square: e6"),
+            ("4k3/4r3/8/3Pp3/8/8/8/4K3 w - e6 0 1", "Invalid en passant square: e6"),
+            ("4k3/8/4r3/3Pp3/8/8/8/4K3 w - e6 0 1", "Invalid en passant square: e6"),
+            ("4k3/8/8/8/3pP3/8/8/4K3 b - e6 0 1", "Invalid en passant square: e6"),
+            ("4k3/8/8/8/8/8/8/4K2P w - -", "Pawn on rank 1"),
+            ("p3k3/8/8/8/8/8/8/4K3 w - -", "Pawn on rank 8"),
+            ("4k3/8/8/8/8/8/8/4K36 w - -", "Too many squares on rank 1"),
+            ("4k3/8/8/8/8/8/8/8888 w - -", "Too many squares on rank 1"),
+            ("4k3/8/8/8/8/8/8/4K03 w - -", "Invalid piece: 0"),
+            ("4k3/8/8/8/8/8/8/4K3 w - - x", "Invalid halfmove clock: x"),
+            ("8/8/8/8/8/8/8/4K3 w - -", "Expected one black king but found 0"),
+        ]
+        .iter()
+        {
+            assert_eq!(decode(fen).err().unwrap().reason, *reason);
+        }
+    }
+}
@@ -1,24 +1,377 @@
+use std::sync::{Mutex, OnceLock};
 use std::vec::*;
+use wasm_bindgen::prelude::*;
+use super::board::*;
+use crate::engine::evaluation::{explain, Evaluation};
+use crate::engine::search::*;
+use super::color::*;
+use super::diagnostics::*;
 use super::game_state::*;
+use super::history::History;
+use super::legal_destination::*;
+use super::move_record::MoveRecord;
 use super::moving::*;
+use super::notation::*;
+use super::position::*;
+use super::see::*;
+use super::takeback::*;
+use super::square::*;
 
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
 pub struct Game {
     pub state: GameState,
-    pub moves: Vec<String>
+    // the moves made, shared with the games this one was made from
+    pub(crate) history: History,
+    // moves taken back which can be replayed, the most recent last, each kept as the history it ended so that
+    // replaying it brings back its record unchanged
+    pub(crate) undone: Vec<History>,
+    // the player asking for a takeback, until the opponent answers
+    pub takeback: Option<Color>,
 }
 
+// the engine behind bestMove, kept between calls (and games) so its transposition table is allocated once and
+// what it learnt carries over; the table is smaller than a UCI engine's, to suit a web page
+fn analysis_engine() -> &'static Mutex<Engine> {
+    static ENGINE: OnceLock<Mutex<Engine>> = OnceLock::new();
+    ENGINE.get_or_init(|| Mutex::new(Engine::with_options(SearchOptions { hash_mb: 4, ..SearchOptions::default() })))
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
+
+#[wasm_bindgen]
 impl Game {
+    #[wasm_bindgen(constructor)]
     pub fn new() -> Game {
         Game {
             state: GameState::NotStarted,
-            moves: vec![],
+            history: History::new(),
+            undone: vec![],
+            takeback: None,
         }
     }
 
+    // the moves made, as they were given. This was a field: JavaScript reads `game.moves` as before, but it can no
+    // longer be assigned, and Rust callers call `moves()`; games are made by making moves from one another
+    #[wasm_bindgen(getter)]
+    pub fn moves(&self) -> Vec<String> {
+        self.history.moves()
+    }
+
+    #[wasm_bindgen(js_name = makeMove)]
     pub fn make_move(&self, next_move: String) -> Result<Game, MoveError> {
         match make_move_internal(self, next_move) {
             Ok(game) => Ok(game),
             Err(e) => Err(e)
         }
     }
-}
\ No newline at end of file
+
+    // makes the move, noting when it was made and the time the player had left, both in milliseconds
+    #[wasm_bindgen(js_name = makeTimedMove)]
+    pub fn make_timed_move(
+        &self,
+        next_move: String,
+        timestamp_ms: Option<f64>,
+        clock_ms: Option<u32>,
+    ) -> Result<Game, MoveError> {
+        let game = make_move_internal(self, next_move)?;
+        let history = game.history.amend_last(|record| {
+            record.timestamp_ms = timestamp_ms;
+            record.clock_ms = clock_ms;
+        });
+        Ok(Game { history: history.unwrap_or_else(|| game.history.clone()), ..game })
+    }
+
+    // a record of each move made, first to last
+    #[wasm_bindgen(getter)]
+    pub fn records(&self) -> Vec<MoveRecord> {
+        self.history.records()
+    }
+
+    #[wasm_bindgen(getter, js_name = lastRecord)]
+    pub fn last_record(&self) -> Option<MoveRecord> {
+        self.history.last_record().cloned()
+    }
+
+    // adds annotation glyphs (e.g. 1 for "!") and a comment to the last move
+    #[wasm_bindgen(js_name = annotateLastMove)]
+    pub fn annotate_last_move(&self, nags: Vec<u8>, comment: Option<String>) -> Result<Game, MoveError> {
+        let history = self.history.amend_last(|record| {
+            for nag in nags {
+                if !record.nags.contains(&nag) {
+                    record.nags.push(nag);
+                }
+            }
+            if comment.is_some() {
+                record.comment = comment;
+            }
+        });
+        let history = history.ok_or_else(|| MoveError { reason: "No move to annotate".to_string() })?;
+        Ok(Game { history, ..self.clone() })
+    }
+
+    // moves taken back which can be replayed, as they were given, the most recent last
+    #[wasm_bindgen(getter)]
+    pub fn undone(&self) -> Vec<String> {
+        self.undone.iter().filter_map(|history| history.last().map(str::to_string)).collect()
+    }
+
+    // takes back the last move
+    pub fn undo(&self) -> Result<Game, MoveError> {
+        undo_internal(self, 1)
+    }
+
+    // takes back the last two moves, leaving the same player to move
+    #[wasm_bindgen(js_name = undoMovePair)]
+    pub fn undo_move_pair(&self) -> Result<Game, MoveError> {
+        undo_internal(self, 2)
+    }
+
+    // replays the move last taken back
+    pub fn redo(&self) -> Result<Game, MoveError> {
+        redo_internal(self)
+    }
+
+    // the player asks to take back their last move, and the opponent's reply if it's their turn again
+    #[wasm_bindgen(js_name = requestTakeback)]
+    pub fn request_takeback(&self, by: Color) -> Result<Game, MoveError> {
+        request_takeback_internal(self, by)
+    }
+
+    #[wasm_bindgen(js_name = acceptTakeback)]
+    pub fn accept_takeback(&self, by: Color) -> Result<Game, MoveError> {
+        answer_takeback_internal(self, by, true)
+    }
+
+    #[wasm_bindgen(js_name = declineTakeback)]
+    pub fn decline_takeback(&self, by: Color) -> Result<Game, MoveError> {
+        answer_takeback_internal(self, by, false)
+    }
+
+    // reports every rule checked for the move, whether or not it would be accepted
+    #[wasm_bindgen(js_name = explainMove)]
+    pub fn explain_move(&self, next_move: String) -> MoveDiagnostics {
+        diagnose_move(self, &next_move)
+    }
+
+    // the squares the piece on the named square (e.g. "e2") can legally move to
+    #[wasm_bindgen(js_name = legalDestinations)]
+    pub fn legal_destinations_from(&self, square: String) -> Result<Vec<LegalDestination>, MoveError> {
+        let from = square.parse::<Square>().map_err(|err| MoveError { reason: err.reason })?;
+        self.legal_destinations(from)
+    }
+
+    // how many of the side's pieces attack each square, a1, b1 .. h8
+    #[wasm_bindgen(js_name = attackCounts)]
+    pub fn attack_counts(&self, color: Color) -> Result<Vec<u8>, MoveError> {
+        Ok(attack_counts(&self.board()?, color).to_vec())
+    }
+
+    pub fn pins(&self, color: Color) -> Result<Vec<Pin>, MoveError> {
+        Ok(pins(&self.board()?, color))
+    }
+
+    // the squares of the pieces giving check to the player having the move
+    pub fn checkers(&self) -> Result<Vec<String>, MoveError> {
+        Ok(checkers(&self.board()?).iter().map(|square| square.to_string()).collect())
+    }
+
+    #[wasm_bindgen(js_name = isDefended)]
+    pub fn is_defended(&self, square: String, by: Color) -> Result<bool, MoveError> {
+        let square = square.parse::<Square>().map_err(|err| MoveError { reason: err.reason })?;
+        Ok(is_defended(&self.board()?, square, by))
+    }
+
+    #[wasm_bindgen(js_name = hangingPieces)]
+    pub fn hanging_pieces(&self, color: Color) -> Result<Vec<String>, MoveError> {
+        Ok(hanging_pieces(&self.board()?, color).iter().map(|square| square.to_string()).collect())
+    }
+
+    // the material (in centipawns) the move wins or loses once the exchange on its destination is over, e.g.
+    // -200 for a bishop taking a pawn defended by a pawn
+    #[wasm_bindgen(js_name = staticExchange)]
+    pub fn static_exchange(&self, next_move: String) -> Result<i32, MoveError> {
+        let board = self.board()?;
+        let chess_move = decode(next_move)
+            .and_then(|notation| resolve(&board, &notation))
+            .map_err(|err| MoveError { reason: err.reason })?;
+        Ok(see(&board, &chess_move))
+    }
+
+    // the engine's choice of move for the player having the move, searching until the first of the limits
+    // given is reached (a depth of 4 if none are)
+    #[wasm_bindgen(js_name = bestMove)]
+    pub fn best_move(
+        &self,
+        depth: Option<u32>,
+        movetime_ms: Option<u32>,
+        nodes: Option<u32>,
+    ) -> Result<SearchResult, MoveError> {
+        let mut limits = SearchLimits {
+            depth,
+            nodes: nodes.map(u64::from),
+            movetime_ms: movetime_ms.map(u64::from),
+        };
+        if limits == SearchLimits::default() {
+            limits.depth = Some(4);
+        }
+        let mut engine = analysis_engine().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // the search avoids or heads for repeating the game's earlier positions
+        Ok(engine.search(&self.history.board_with_earlier_positions(), &limits))
+    }
+
+    // has the position occurred before since the last capture or pawn move?
+    #[wasm_bindgen(js_name = isRepetition)]
+    pub fn is_repetition(&self) -> bool {
+        self.history.is_repetition()
+    }
+
+    // the engine's static assessment of the position, term by term, in centipawns (positive favours White)
+    pub fn evaluate(&self) -> Result<Evaluation, MoveError> {
+        Ok(explain(&self.board()?))
+    }
+}
+
+impl Game {
+    // the board reached by playing the game's moves
+    pub fn board(&self) -> Result<Board, MoveError> {
+        Ok(self.history.board())
+    }
+
+    pub fn legal_destinations(&self, from: Square) -> Result<Vec<LegalDestination>, MoveError> {
+        Ok(legal_destinations(&self.board()?, from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::fen;
+
+    #[test]
+    fn legal_destinations_follow_the_moves_made() {
+        let game = Game::new()
+            .make_move("e4".to_string())
+            .and_then(|game| game.make_move("d5".to_string()))
+            .unwrap();
+        let destinations: Vec<(String, bool)> = game
+            .legal_destinations_from("e4".to_string())
+            .unwrap()
+            .iter()
+            .map(|destination| (destination.to_name(), destination.capture))
+            .collect();
+        assert_eq!(destinations.len(), 2);
+        assert!(destinations.contains(&("e5".to_string(), false)));
+        assert!(destinations.contains(&("d5".to_string(), true)));
+    }
+
+    #[test]
+    fn games_made_from_one_share_its_moves() {
+        let opening = Game::new().make_move("e4".to_string()).unwrap().make_move("e5".to_string()).unwrap();
+        let open = opening.make_move("Nf3".to_string()).unwrap();
+        let gambit = opening.make_move("f4".to_string()).unwrap();
+        assert_eq!(opening.moves(), ["e4", "e5"]);
+        assert_eq!(open.moves(), ["e4", "e5", "Nf3"]);
+        assert_eq!(gambit.moves(), ["e4", "e5", "f4"]);
+        assert_eq!(gambit.board().unwrap(), replay_moves(&gambit.moves()).unwrap());
+
+        // the knights shuffling back and forth repeat the position every four moves
+        let mut game = open;
+        for next_move in ["Nf6", "Ng1", "Ng8", "Nf3"].iter().cycle().take(400) {
+            game = game.make_move(next_move.to_string()).unwrap();
+        }
+        assert_eq!(game.moves().len(), 403);
+        let board = game.board().unwrap();
+        assert_eq!(board.halfmove_clock(), 401);
+        assert_eq!(board.ply(), 0);
+        assert!(game.is_repetition());
+        assert!(!game.make_move("Nc6".to_string()).unwrap().is_repetition());
+    }
+
+    #[test]
+    fn each_move_made_is_recorded() {
+        let game = Game::new()
+            .make_timed_move("e2e4".to_string(), Some(1_000.0), Some(299_000))
+            .unwrap()
+            .make_move("d5".to_string())
+            .unwrap()
+            .make_move("exd5".to_string())
+            .unwrap()
+            .annotate_last_move(vec![1, 1, 3], Some("grabs the pawn".to_string()))
+            .unwrap();
+        let records = game.records();
+        assert_eq!(records.iter().map(|record| record.san.as_str()).collect::<Vec<_>>(), ["e4", "d5", "exd5"]);
+        assert_eq!(records[0].text, "e2e4");
+        assert_eq!((records[0].timestamp_ms, records[0].clock_ms), (Some(1_000.0), Some(299_000)));
+        assert_eq!(records[1].clock_ms, None);
+        assert_eq!(records[2].captured(), Some("pawn".to_string()));
+        assert_eq!(records[2].fen, fen::encode(&game.board().unwrap()));
+        assert_eq!(records[2].nags, [1, 3]);
+        assert_eq!(game.last_record().unwrap().comment, Some("grabs the pawn".to_string()));
+        assert!(Game::new().annotate_last_move(vec![1], None).is_err());
+        // the end of game marker has no record
+        let resigned = game.make_move("1-0".to_string()).unwrap();
+        assert_eq!(resigned.records().len(), 3);
+        assert_eq!(resigned.last_record(), None);
+    }
+
+    #[test]
+    fn legal_destinations_need_a_valid_square() {
+        let actual = Game::new().legal_destinations_from("e9".to_string());
+        assert_eq!(actual.err().unwrap().reason, "Invalid square: e9");
+    }
+
+    #[test]
+    fn analysis_follows_the_moves_made() {
+        let game = ["e4", "e5", "Nf3", "Nc6", "Bb5", "d6", "Nxe5"]
+            .iter()
+            .try_fold(Game::new(), |game, next_move| game.make_move(next_move.to_string()))
+            .unwrap();
+        assert_eq!(game.hanging_pieces(Color::White).unwrap(), vec!["e5".to_string()]);
+        assert_eq!(game.pins(Color::Black).unwrap()[0].pinned_name(), "c6");
+        assert!(game.checkers().unwrap().is_empty());
+        assert_eq!(game.attack_counts(Color::Black).unwrap()[Square::E5.index() as usize], 2);
+        assert!(game.is_defended("e5".to_string(), Color::Black).unwrap());
+    }
+
+    #[test]
+    fn the_static_exchange_of_a_move_is_reported() {
+        let game = ["e4", "e5", "Nf3", "d6", "d4"]
+            .iter()
+            .try_fold(Game::new(), |game, next_move| game.make_move(next_move.to_string()))
+            .unwrap();
+        assert_eq!(game.static_exchange("exd4".to_string()), Ok(0));
+        assert_eq!(game.static_exchange("Bg4".to_string()), Ok(0));
+        assert_eq!(game.static_exchange("Bh3".to_string()), Ok(-300));
+    }
+
+    #[test]
+    fn the_engine_plays_for_the_side_to_move() {
+        let game = ["f3", "e5", "g4"]
+            .iter()
+            .try_fold(Game::new(), |game, next_move| game.make_move(next_move.to_string()))
+            .unwrap();
+        let result = game.best_move(Some(2), None, None).unwrap();
+        assert_eq!(result.best_move, Some("Qh4#".to_string()));
+        assert!(game.make_move(result.best_move.unwrap()).is_ok());
+        // the same engine answers again
+        assert_eq!(game.best_move(Some(2), None, None).unwrap().best_move, Some("Qh4#".to_string()));
+        assert_eq!(analysis_engine().lock().unwrap().options().hash_mb, 4);
+    }
+
+    #[test]
+    fn the_evaluation_favours_the_side_ahead() {
+        let game = ["e4", "d5", "exd5"]
+            .iter()
+            .try_fold(Game::new(), |game, next_move| game.make_move(next_move.to_string()))
+            .unwrap();
+        let evaluation = game.evaluate().unwrap();
+        assert!(evaluation.score > 50);
+        assert_eq!(evaluation.terms.len(), 8);
+    }
+}
@@ -0,0 +1,613 @@
+// A game with its variations, for analysis: every position reached is a node, and the moves from it lead to
+// its children, the first being the main continuation and the rest variations. Nodes carry comments and
+// numeric annotation glyphs (NAGs, e.g. $1 for "!"), and the whole tree reads from and writes to PGN, with
+// variations as recursive annotation variations: "1. e4 e5 (1... c5 {Sicilian}) 2. Nf3".
+//
+// One node is current, as on an analysis board: moves are played from it, and it moves back and forth through
+// the tree.
+
+use wasm_bindgen::prelude::*;
+
+use super::board::*;
+use super::chess_move::*;
+use super::color::Color;
+use super::fen;
+use super::game::Game;
+use super::moving::MoveError;
+use super::notation::*;
+use super::pgn::{split_games, GameText, Token};
+
+const ROOT: usize = 0;
+
+#[derive(Clone, Debug)]
+struct Node {
+    // none for the start position
+    chess_move: Option<Move>,
+    san: String,
+    parent: Option<usize>,
+    // the main continuation first, then the variations
+    children: Vec<usize>,
+    comment: Option<String>,
+    nags: Vec<u8>,
+}
+
+impl Node {
+    fn new(chess_move: Option<Move>, san: String, parent: Option<usize>) -> Node {
+        Node { chess_move, san, parent, children: vec![], comment: None, nags: vec![] }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    start: Board,
+    // deleted nodes stay here, detached from their parents
+    nodes: Vec<Node>,
+    current: usize,
+    // the position at the current node
+    board: Board,
+    // from the PGN read, written back out
+    tags: Vec<(String, String)>,
+    result: Option<String>,
+}
+
+impl Default for GameTree {
+    fn default() -> GameTree {
+        GameTree::new()
+    }
+}
+
+fn move_error(reason: String) -> MoveError {
+    MoveError { reason }
+}
+
+#[wasm_bindgen]
+impl GameTree {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GameTree {
+        GameTree::from_board(&Board::new())
+    }
+
+    #[wasm_bindgen(js_name = fromFen)]
+    pub fn from_fen(text: &str) -> Result<GameTree, MoveError> {
+        fen::decode(text).map(|board| GameTree::from_board(&board)).map_err(|err| move_error(err.reason))
+    }
+
+    // the game's moves as the main line
+    #[wasm_bindgen(js_name = fromGame)]
+    pub fn from_game(game: &Game) -> Result<GameTree, MoveError> {
+        let mut tree = GameTree::new();
+        for next_move in game.moves() {
+            let notation = decode(next_move.clone()).map_err(|err| move_error(err.reason))?;
+            if notation.end_of_game.is_none() {
+                tree.play(next_move)?;
+            }
+        }
+        tree.to_start();
+        Ok(tree)
+    }
+
+    // the first game in the text, with its variations, comments and NAGs
+    #[wasm_bindgen(js_name = fromPgn)]
+    pub fn from_pgn(text: &str) -> Result<GameTree, MoveError> {
+        let GameText { tags, tokens } = match split_games(text).into_iter().next() {
+            Some(game) => game.map_err(|err| move_error(err.reason))?,
+            None => GameText::default(),
+        };
+        let mut tree = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, text)) => GameTree::from_fen(text)?,
+            None => GameTree::new(),
+        };
+        tree.tags = tags;
+        // where each open variation returns to, and the last move played before it
+        let mut returns = vec![];
+        let mut last = None;
+        let mut pending_comment: Option<String> = None;
+        for token in tokens {
+            match token {
+                Token::Move(san) => {
+                    let node = tree.play(san)?;
+                    if let Some(comment) = pending_comment.take() {
+                        tree.nodes[node].comment = Some(comment);
+                    }
+                    last = Some(node);
+                }
+                Token::Nag(nag) => tree.add_nag(nag),
+                // a comment before a variation's first move goes with that move
+                Token::Comment(comment) if last.is_none() && !returns.is_empty() => pending_comment = Some(comment),
+                Token::Comment(comment) => {
+                    let joined = match tree.comment() {
+                        Some(earlier) => format!("{} {}", earlier, comment),
+                        None => comment,
+                    };
+                    tree.set_comment(Some(joined));
+                }
+                Token::Open => {
+                    let node = last.ok_or_else(|| move_error("Variation before any move".to_string()))?;
+                    returns.push((tree.current, last));
+                    tree.go_to(tree.nodes[node].parent.unwrap_or(ROOT));
+                    last = None;
+                }
+                Token::Close => {
+                    let (node, before) = returns.pop().ok_or_else(|| move_error("Unmatched ')'".to_string()))?;
+                    tree.go_to(node);
+                    last = before;
+                }
+                Token::Result(result) => tree.result = Some(result),
+            }
+        }
+        if !returns.is_empty() {
+            return Err(move_error("Unterminated variation".to_string()));
+        }
+        tree.to_start();
+        Ok(tree)
+    }
+
+    // Plays the move, in SAN, from the current node and makes its node current. A move already in the tree is
+    // followed; a new one is added, as a variation if the node already has a continuation.
+    pub fn play(&mut self, san: String) -> Result<usize, MoveError> {
+        let chess_move = decode(san)
+            .and_then(|notation| resolve(&self.board, &notation))
+            .map_err(|err| move_error(err.reason))?;
+        let existing = self.nodes[self.current]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].chess_move == Some(chess_move));
+        let node = match existing {
+            Some(node) => node,
+            None => {
+                let san = encode(&self.board, &chess_move);
+                self.nodes.push(Node::new(Some(chess_move), san, Some(self.current)));
+                let node = self.nodes.len() - 1;
+                self.nodes[self.current].children.push(node);
+                node
+            }
+        };
+        self.board.make_move(chess_move);
+        self.current = node;
+        Ok(node)
+    }
+
+    // follows the main continuation, or the variation given (1 being the first)
+    pub fn forward(&mut self, variation: Option<usize>) -> bool {
+        match self.nodes[self.current].children.get(variation.unwrap_or(0)).copied() {
+            Some(child) => {
+                self.board.make_move(self.nodes[child].chess_move.unwrap());
+                self.current = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.board.unmake_move();
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[wasm_bindgen(js_name = toStart)]
+    pub fn to_start(&mut self) {
+        self.go_to(ROOT);
+    }
+
+    // to the end of the current line, following main continuations
+    #[wasm_bindgen(js_name = toEnd)]
+    pub fn to_end(&mut self) {
+        while self.forward(None) {}
+    }
+
+    // to the ply given along the current line: back if it's been played, otherwise on by main continuations
+    #[wasm_bindgen(js_name = goToPly)]
+    pub fn go_to_ply(&mut self, ply: usize) -> bool {
+        let current = self.current;
+        while self.ply() > ply {
+            self.back();
+        }
+        while self.ply() < ply {
+            if !self.forward(None) {
+                self.go_to(current);
+                return false;
+            }
+        }
+        true
+    }
+
+    // makes the node given current, unless it's been deleted
+    #[wasm_bindgen(js_name = goTo)]
+    pub fn go_to(&mut self, node: usize) -> bool {
+        if node >= self.nodes.len() || !self.is_attached(node) {
+            return false;
+        }
+        let mut board = self.start.clone();
+        for chess_move in self.path(node).iter().filter_map(|&node| self.nodes[node].chess_move) {
+            board.make_move(chess_move);
+        }
+        self.board = board;
+        self.current = node;
+        true
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    // the moves played to reach the current node
+    pub fn ply(&self) -> usize {
+        self.path(self.current).len() - 1
+    }
+
+    pub fn fen(&self) -> String {
+        fen::encode(&self.board)
+    }
+
+    // the move leading to the current node, in SAN
+    pub fn san(&self) -> Option<String> {
+        self.nodes[self.current].chess_move.map(|_| self.nodes[self.current].san.clone())
+    }
+
+    // the moves from the current node, the main continuation first
+    pub fn variations(&self) -> Vec<String> {
+        self.nodes[self.current].children.iter().map(|&child| self.nodes[child].san.clone()).collect()
+    }
+
+    // the moves from the start to the current node
+    pub fn line(&self) -> Vec<String> {
+        self.path(self.current).iter().skip(1).map(|&node| self.nodes[node].san.clone()).collect()
+    }
+
+    #[wasm_bindgen(js_name = mainLine)]
+    pub fn main_line(&self) -> Vec<String> {
+        let mut moves = vec![];
+        let mut node = ROOT;
+        while let Some(&child) = self.nodes[node].children.first() {
+            moves.push(self.nodes[child].san.clone());
+            node = child;
+        }
+        moves
+    }
+
+    // Makes the variation the current node is in the main continuation from where it branches, returning
+    // false when the current node is already on the main line.
+    #[wasm_bindgen(js_name = promoteVariation)]
+    pub fn promote_variation(&mut self) -> bool {
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            let children = &mut self.nodes[parent].children;
+            let index = children.iter().position(|&child| child == node).unwrap();
+            if index > 0 {
+                children.remove(index);
+                children.insert(0, node);
+                return true;
+            }
+            node = parent;
+        }
+        false
+    }
+
+    // removes the current node's move and everything after it, making its parent current
+    #[wasm_bindgen(js_name = deleteFromHere)]
+    pub fn delete_from_here(&mut self) -> bool {
+        let node = self.current;
+        match self.nodes[node].parent {
+            Some(parent) => {
+                self.nodes[parent].children.retain(|&child| child != node);
+                self.back();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // the comment after the current node's move, or before the first move at the start
+    pub fn comment(&self) -> Option<String> {
+        self.nodes[self.current].comment.clone()
+    }
+
+    #[wasm_bindgen(js_name = setComment)]
+    pub fn set_comment(&mut self, comment: Option<String>) {
+        self.nodes[self.current].comment = comment.filter(|comment| !comment.trim().is_empty());
+    }
+
+    pub fn nags(&self) -> Vec<u8> {
+        self.nodes[self.current].nags.clone()
+    }
+
+    #[wasm_bindgen(js_name = addNag)]
+    pub fn add_nag(&mut self, nag: u8) {
+        let nags = &mut self.nodes[self.current].nags;
+        if self.current != ROOT && !nags.contains(&nag) {
+            nags.push(nag);
+        }
+    }
+
+    #[wasm_bindgen(js_name = removeNag)]
+    pub fn remove_nag(&mut self, nag: u8) {
+        self.nodes[self.current].nags.retain(|&other| other != nag);
+    }
+
+    // the tree as a PGN game, wrapped at 80 columns
+    #[wasm_bindgen(js_name = toPgn)]
+    pub fn to_pgn(&self) -> String {
+        let mut text = String::new();
+        let mut tags = self.tags.clone();
+        if self.start != Board::new() && !tags.iter().any(|(name, _)| name == "FEN") {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen::encode(&self.start)));
+        }
+        for (name, value) in tags.iter() {
+            text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+        if !tags.is_empty() {
+            text.push('\n');
+        }
+        let mut tokens = vec![];
+        if let Some(comment) = &self.nodes[ROOT].comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        self.write_continuation(ROOT, &mut tokens, true);
+        tokens.push(self.result.clone().unwrap_or_else(|| "*".to_string()));
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        text.push_str(&line);
+        text.push('\n');
+        text
+    }
+}
+
+impl GameTree {
+    pub fn from_board(board: &Board) -> GameTree {
+        GameTree {
+            start: board.clone(),
+            nodes: vec![Node::new(None, String::new(), None)],
+            current: ROOT,
+            board: board.clone(),
+            tags: vec![],
+            result: None,
+        }
+    }
+
+    // the position at the current node
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    // the nodes from the start to the one given
+    fn path(&self, node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        while let Some(parent) = self.nodes[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    // whether the node can still be reached from the start
+    fn is_attached(&self, node: usize) -> bool {
+        let path = self.path(node);
+        path.windows(2).all(|pair| self.nodes[pair[0]].children.contains(&pair[1]))
+    }
+
+    // writes the move to the node, numbered if it's White's or `numbered` asks, with its NAGs and comment
+    fn write_move(&self, node: usize, tokens: &mut Vec<String>, numbered: bool) {
+        let before = self.path(node).len() - 2;
+        let black_first = self.start.side_to_move() == Color::Black;
+        let plies = before + black_first as usize;
+        let number = self.start.fullmove_number() as usize + plies / 2;
+        if plies.is_multiple_of(2) {
+            tokens.push(format!("{}.", number));
+        } else if numbered {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(self.nodes[node].san.clone());
+        tokens.extend(self.nodes[node].nags.iter().map(|nag| format!("${}", nag)));
+        if let Some(comment) = &self.nodes[node].comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+    }
+
+    // writes the main continuation from the node with its variations, then the main line on from there
+    fn write_continuation(&self, node: usize, tokens: &mut Vec<String>, numbered: bool) {
+        let children = &self.nodes[node].children;
+        let Some(&main) = children.first() else {
+            return;
+        };
+        self.write_move(main, tokens, numbered);
+        for &variation in children[1..].iter() {
+            let first = tokens.len();
+            self.write_move(variation, tokens, true);
+            self.write_continuation(variation, tokens, false);
+            tokens[first].insert(0, '(');
+            tokens.last_mut().unwrap().push(')');
+        }
+        // after a variation or comment Black's move needs its number again
+        let renumber = children.len() > 1 || self.nodes[main].comment.is_some();
+        self.write_continuation(main, tokens, renumber);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn played(moves: &[&str]) -> GameTree {
+        let mut tree = GameTree::new();
+        for san in moves {
+            tree.play(san.to_string()).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn variations_branch_from_the_current_node() {
+        let mut tree = played(&["e4", "e5", "Nf3"]);
+        assert!(tree.go_to_ply(1));
+        tree.play("c5".to_string()).unwrap();
+        tree.play("Nf3".to_string()).unwrap();
+        assert_eq!(tree.line(), ["e4", "c5", "Nf3"]);
+        assert_eq!(tree.main_line(), ["e4", "e5", "Nf3"]);
+        tree.go_to_ply(1);
+        assert_eq!(tree.variations(), ["e5", "c5"]);
+        // playing a move already there follows it
+        let nodes = tree.nodes.len();
+        tree.play("c5".to_string()).unwrap();
+        assert_eq!(tree.nodes.len(), nodes);
+        assert!(tree.play("Ke3".to_string()).is_err());
+    }
+
+    #[test]
+    fn navigation() {
+        let mut tree = played(&["d4", "d5", "c4"]);
+        assert_eq!(tree.ply(), 3);
+        assert!(tree.back());
+        assert_eq!(tree.san(), Some("d5".to_string()));
+        tree.to_start();
+        assert_eq!(tree.ply(), 0);
+        assert_eq!(tree.fen(), fen::INITIAL_FEN);
+        assert!(!tree.back());
+        tree.to_end();
+        assert_eq!(tree.line(), ["d4", "d5", "c4"]);
+        assert!(tree.go_to_ply(1));
+        assert!(!tree.go_to_ply(4));
+        assert_eq!(tree.ply(), 1);
+        assert!(tree.forward(None));
+        assert_eq!(tree.fen(), "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq d6 0 2");
+        assert!(!tree.forward(Some(1)));
+    }
+
+    #[test]
+    fn promoting_and_deleting() {
+        let mut tree = played(&["e4", "e5", "Nf3"]);
+        tree.go_to_ply(1);
+        tree.play("c5".to_string()).unwrap();
+        let d4 = tree.play("d4".to_string()).unwrap();
+        assert!(tree.promote_variation());
+        assert_eq!(tree.main_line(), ["e4", "c5", "d4"]);
+        assert!(!tree.promote_variation());
+
+        tree.go_to_ply(1);
+        assert!(tree.forward(Some(1)));
+        assert_eq!(tree.san(), Some("e5".to_string()));
+        assert!(tree.delete_from_here());
+        assert_eq!(tree.ply(), 1);
+        assert_eq!(tree.variations(), ["c5"]);
+        tree.to_start();
+        assert!(!tree.delete_from_here());
+        // deleted nodes can't be gone to, the rest can
+        assert!(tree.go_to(d4));
+        assert!(tree.back());
+        assert!(tree.delete_from_here());
+        assert!(!tree.go_to(d4));
+    }
+
+    #[test]
+    fn comments_and_nags() {
+        let mut tree = played(&["e4"]);
+        tree.set_comment(Some("Best by test".to_string()));
+        tree.add_nag(1);
+        tree.add_nag(1);
+        tree.add_nag(14);
+        assert_eq!(tree.nags(), [1, 14]);
+        tree.remove_nag(14);
+        assert_eq!(tree.comment(), Some("Best by test".to_string()));
+        tree.set_comment(Some(" ".to_string()));
+        assert_eq!(tree.comment(), None);
+        tree.to_start();
+        tree.add_nag(1);
+        assert!(tree.nags().is_empty());
+    }
+
+    const PGN: &str = r#"[Event "Casual"]
+[Result "1-0"]
+
+{A gambit} 1. e4 e5 2. f4 {The King's Gambit} exf4 3. Bc4 Qh4+ (3... Nf6 $1 4. Nc3
+(4. e5 d5) 4... c6) (3... d5!?) 4. Kf1 b5 5. Bxb5 1-0
+"#;
+
+    #[test]
+    fn pgn_variations_are_read() {
+        let mut tree = GameTree::from_pgn(PGN).unwrap();
+        assert_eq!(tree.main_line(), ["e4", "e5", "f4", "exf4", "Bc4", "Qh4+", "Kf1", "b5", "Bxb5"]);
+        assert_eq!(tree.comment(), Some("A gambit".to_string()));
+        tree.go_to_ply(3);
+        assert_eq!(tree.comment(), Some("The King's Gambit".to_string()));
+        tree.go_to_ply(5);
+        assert_eq!(tree.variations(), ["Qh4+", "Nf6", "d5"]);
+        tree.forward(Some(1));
+        assert_eq!(tree.nags(), [1]);
+        tree.forward(None);
+        assert_eq!(tree.variations(), ["c6"]);
+        tree.back();
+        tree.forward(Some(1));
+        assert_eq!(tree.san(), Some("e5".to_string()));
+        tree.go_to_ply(5);
+        tree.forward(Some(2));
+        assert_eq!(tree.nags(), [5]);
+    }
+
+    #[test]
+    fn pgn_round_trips() {
+        let tree = GameTree::from_pgn(PGN).unwrap();
+        let text = tree.to_pgn();
+        assert_eq!(
+            text,
+            "[Event \"Casual\"]
+[Result \"1-0\"]
+
+{A gambit} 1. e4 e5 2. f4 {The King's Gambit} 2... exf4 3. Bc4 Qh4+ (3... Nf6 $1
+4. Nc3 (4. e5 d5) 4... c6) (3... d5 $5) 4. Kf1 b5 5. Bxb5 1-0
+"
+        );
+        let again = GameTree::from_pgn(&text).unwrap();
+        assert_eq!(again.to_pgn(), text);
+    }
+
+    #[test]
+    fn other_starts() {
+        let mut tree = GameTree::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+        tree.play("Kd7".to_string()).unwrap();
+        tree.play("e4".to_string()).unwrap();
+        let text = tree.to_pgn();
+        assert!(text.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]"));
+        assert!(text.ends_with("\n1... Kd7 2. e4 *\n"));
+        assert_eq!(GameTree::from_pgn(&text).unwrap().main_line(), ["Kd7", "e4"]);
+
+        let game = Game::new().make_move("e4".to_string()).unwrap().make_move("c5".to_string()).unwrap();
+        let tree = GameTree::from_game(&game).unwrap();
+        assert_eq!(tree.main_line(), ["e4", "c5"]);
+        assert_eq!(tree.ply(), 0);
+    }
+
+    #[test]
+    fn broken_pgn() {
+        assert!(GameTree::from_pgn("1. e4 (1. d4").is_err());
+        assert!(GameTree::from_pgn("1. e4 e5)").is_err());
+        assert!(GameTree::from_pgn("(1. d4) 1. e4").is_err());
+        assert!(GameTree::from_pgn("1. e4 {unfinished").is_err());
+        assert!(GameTree::from_pgn("1. e4 e5 2. Ke3").is_err());
+    }
+
+    #[test]
+    fn only_the_first_game_is_read() {
+        let tree = GameTree::from_pgn("[Event \"A\"]\n1. e4 e5\n\n[Event \"B\"]\n1. d4 d5 1-0").unwrap();
+        assert_eq!(tree.main_line(), ["e4", "e5"]);
+        assert!(tree.to_pgn().starts_with("[Event \"A\"]\n"));
+    }
+}
@@ -0,0 +1,214 @@
+// The moves of a game as a persistent list: each move is a node pointing back at the one before, so a game made
+// by moving shares every earlier move with the game it was made from rather than copying them. Each node also
+// holds the board after its move, without the moves which led to it, so the position is at hand without
+// replaying the game and copying it costs the same however long the game: making a move takes constant time.
+// Repetition is found by walking back along the nodes to the last capture or pawn move, comparing hashes.
+// Alongside the text given, each move has a record of what it did, kept for move lists and analysis.
+
+use std::rc::Rc;
+
+use super::board::Board;
+use super::move_record::MoveRecord;
+
+#[derive(Debug)]
+struct Entry {
+    // the move as it was given
+    text: String,
+    // none for an end of game marker
+    record: Option<MoveRecord>,
+    board: Board,
+    previous: Option<Rc<Entry>>,
+    len: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    last: Option<Rc<Entry>>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.last.as_ref().map_or(0, |entry| entry.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last.is_none()
+    }
+
+    // the history with the move added, the board being the position after it
+    pub fn push(&self, text: String, record: Option<MoveRecord>, mut board: Board) -> History {
+        board.forget_moves();
+        let entry = Entry { text, record, board, previous: self.last.clone(), len: self.len() + 1 };
+        History { last: Some(Rc::new(entry)) }
+    }
+
+    // the history with the record of the last move changed, e.g. to annotate it
+    pub fn amend_last(&self, amend: impl FnOnce(&mut MoveRecord)) -> Option<History> {
+        let entry = self.last.as_ref()?;
+        let mut record = entry.record.clone()?;
+        amend(&mut record);
+        let amended = Entry {
+            text: entry.text.clone(),
+            record: Some(record),
+            board: entry.board.clone(),
+            previous: entry.previous.clone(),
+            len: entry.len,
+        };
+        Some(History { last: Some(Rc::new(amended)) })
+    }
+
+    // the history before the last move, and that move
+    pub fn pop(&self) -> Option<(History, &str)> {
+        let entry = self.last.as_ref()?;
+        Some((History { last: entry.previous.clone() }, &entry.text))
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.last.as_ref().map(|entry| entry.text.as_str())
+    }
+
+    pub fn last_record(&self) -> Option<&MoveRecord> {
+        self.last.as_ref().and_then(|entry| entry.record.as_ref())
+    }
+
+    // the position after the last move, or the initial position before any
+    pub fn board(&self) -> Board {
+        self.last.as_ref().map_or_else(Board::new, |entry| entry.board.clone())
+    }
+
+    // the board along with the positions before it since the last capture or pawn move, so that it knows when
+    // they repeat, as a search needs to
+    pub fn board_with_earlier_positions(&self) -> Board {
+        let mut board = self.board();
+        board.set_earlier_positions(self.earlier_positions(board.halfmove_clock() as usize));
+        board
+    }
+
+    // has the position after the last move occurred before since the last capture or pawn move?
+    pub fn is_repetition(&self) -> bool {
+        self.board_with_earlier_positions().is_repetition()
+    }
+
+    // the hashes of as many positions before the last as are asked for, oldest first; an end of game marker
+    // leaves the position as it was, so only the moves count
+    fn earlier_positions(&self, count: usize) -> Vec<u64> {
+        let mut hashes: Vec<u64> = self
+            .entries()
+            .filter(|entry| entry.record.is_some())
+            .map(|entry| entry.board.hash())
+            .chain(std::iter::once(Board::new().hash()))
+            .skip(1)
+            .take(count)
+            .collect();
+        hashes.reverse();
+        hashes
+    }
+
+    // the entries, last to first
+    fn entries(&self) -> impl Iterator<Item = &Entry> {
+        std::iter::successors(self.last.as_deref(), |entry| entry.previous.as_deref())
+    }
+
+    // the moves, first to last
+    pub fn moves(&self) -> Vec<String> {
+        let mut moves: Vec<String> = self.entries().map(|entry| entry.text.clone()).collect();
+        moves.reverse();
+        moves
+    }
+
+    // the records of the moves, first to last, leaving out any end of game marker
+    pub fn records(&self) -> Vec<MoveRecord> {
+        let mut records: Vec<MoveRecord> = self.entries().filter_map(|entry| entry.record.clone()).collect();
+        records.reverse();
+        records
+    }
+}
+
+// a long list is dropped a node at a time rather than recursively, which could overflow the stack
+impl Drop for History {
+    fn drop(&mut self) {
+        let mut entry = self.last.take();
+        while let Some(current) = entry {
+            entry = match Rc::try_unwrap(current) {
+                Ok(mut unshared) => unshared.previous.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::notation::{decode, resolve};
+
+    fn played(history: &History, san: &str) -> History {
+        let mut board = history.board();
+        let chess_move = resolve(&board, &decode(san.to_string()).unwrap()).unwrap();
+        let record = MoveRecord::play(&mut board, chess_move, san);
+        history.push(san.to_string(), Some(record), board)
+    }
+
+    #[test]
+    fn histories_share_their_earlier_moves() {
+        let start = played(&played(&History::new(), "e4"), "e5");
+        let main = played(&start, "Nf3");
+        let side = played(&start, "f4");
+        assert_eq!(main.moves(), ["e4", "e5", "Nf3"]);
+        assert_eq!(side.moves(), ["e4", "e5", "f4"]);
+        assert!(Rc::ptr_eq(main.last.as_ref().unwrap().previous.as_ref().unwrap(), start.last.as_ref().unwrap()));
+        let (before, last) = side.pop().unwrap();
+        assert_eq!(last, "f4");
+        assert_eq!(before.moves(), start.moves());
+        assert!(History::new().pop().is_none());
+    }
+
+    #[test]
+    fn records_can_be_amended() {
+        let history = played(&played(&History::new(), "e4"), "e5");
+        let amended = history.amend_last(|record| record.nags.push(1)).unwrap();
+        assert_eq!(amended.last_record().unwrap().nags, [1]);
+        assert!(history.last_record().unwrap().nags.is_empty());
+        assert_eq!(amended.records().iter().map(|record| record.san.as_str()).collect::<Vec<_>>(), ["e4", "e5"]);
+        assert_eq!(amended.board(), history.board());
+        assert!(History::new().amend_last(|_| {}).is_none());
+    }
+
+    #[test]
+    fn repetitions_are_found_back_to_the_last_pawn_move() {
+        let mut history = played(&History::new(), "d3");
+        for san in ["Nf6", "Nf3", "Ng8"] {
+            history = played(&history, san);
+            assert!(!history.is_repetition());
+        }
+        history = played(&history, "Ng1");
+        assert!(history.is_repetition());
+        // boards keep none of the moves which led to them
+        assert_eq!(history.board().ply(), 0);
+        assert!(!history.board().is_repetition());
+        assert_eq!(history.board_with_earlier_positions().halfmove_clock(), 4);
+        // the initial position counts too, but not across a pawn move
+        let mut history = History::new();
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            history = played(&history, san);
+        }
+        assert!(history.is_repetition());
+        assert!(!played(&history, "e4").is_repetition());
+    }
+
+    #[test]
+    fn long_histories_can_be_dropped() {
+        let board = Board::new();
+        let mut history = History::new();
+        for _ in 0..200_000 {
+            history = history.push("Nf3".to_string(), None, board.clone());
+        }
+        assert_eq!(history.len(), 200_000);
+        drop(history);
+    }
+}
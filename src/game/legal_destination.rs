@@ -0,0 +1,127 @@
+use wasm_bindgen::prelude::*;
+
+use super::board::*;
+use super::chess_move::*;
+use super::piece_move::*;
+use super::square::*;
+
+// a square the piece on the origin square can legally move to, flagged so that a UI can mark it up. A pawn
+// reaching the last rank has a single destination (flagged as a promotion) rather than one per piece.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LegalDestination {
+    from: Square,
+    to: Square,
+    pub capture: bool,
+    pub promotion: bool,
+    pub castle: bool,
+    #[wasm_bindgen(js_name = enPassant)]
+    pub en_passant: bool,
+}
+
+impl LegalDestination {
+    pub fn new(board: &Board, chess_move: &Move) -> LegalDestination {
+        LegalDestination {
+            from: chess_move.from,
+            to: chess_move.to,
+            capture: board.is_capture(chess_move),
+            promotion: chess_move.promotion.is_some(),
+            castle: board.is_castling(chess_move),
+            en_passant: board.is_en_passant(chess_move),
+        }
+    }
+
+    pub fn origin(&self) -> Square {
+        self.from
+    }
+
+    pub fn destination(&self) -> Square {
+        self.to
+    }
+}
+
+#[wasm_bindgen]
+impl LegalDestination {
+    // square names, e.g. "e2"
+    #[wasm_bindgen(getter, js_name = from)]
+    pub fn from_name(&self) -> String {
+        self.from.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = to)]
+    pub fn to_name(&self) -> String {
+        self.to.to_string()
+    }
+}
+
+// the legal destinations of the piece on the square, which must belong to the side to move (otherwise there
+// are none), in the order the moves are generated
+pub fn legal_destinations(board: &Board, from: Square) -> Vec<LegalDestination> {
+    let mut destinations: Vec<LegalDestination> = vec![];
+    for chess_move in generate_moves(board).iter().filter(|chess_move| chess_move.from == from) {
+        if !destinations.iter().any(|destination| destination.to == chess_move.to) {
+            destinations.push(LegalDestination::new(board, chess_move));
+        }
+    }
+    destinations
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::fen::decode;
+
+    fn destinations(fen: &str, from: Square) -> Vec<(String, bool, bool, bool, bool)> {
+        let board = decode(fen).unwrap_or_else(|err| panic!("{}", err.reason));
+        legal_destinations(&board, from)
+            .iter()
+            .map(|d| (d.to_name(), d.capture, d.promotion, d.castle, d.en_passant))
+            .collect()
+    }
+
+    #[test]
+    fn a_knight_in_the_initial_position_has_two_destinations() {
+        let mut actual = destinations(crate::game::fen::INITIAL_FEN, Square::G1);
+        actual.sort();
+        assert_eq!(
+            actual,
+            vec![("f3".to_string(), false, false, false, false), ("h3".to_string(), false, false, false, false)]
+        );
+    }
+
+    #[test]
+    fn the_opponents_pieces_and_empty_squares_have_no_destinations() {
+        assert!(destinations(crate::game::fen::INITIAL_FEN, Square::G8).is_empty());
+        assert!(destinations(crate::game::fen::INITIAL_FEN, Square::E4).is_empty());
+    }
+
+    #[test]
+    fn castling_is_flagged_on_the_kings_destination() {
+        let actual = destinations("4k3/8/8/8/8/8/8/4K2R w K - 0 1", Square::E1);
+        assert!(actual.contains(&("g1".to_string(), false, false, true, false)));
+        assert!(actual.contains(&("f1".to_string(), false, false, false, false)));
+    }
+
+    #[test]
+    fn en_passant_is_flagged_as_a_capture() {
+        let actual = destinations("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 2", Square::D5);
+        assert!(actual.contains(&("e6".to_string(), true, false, false, true)));
+        assert!(actual.contains(&("d6".to_string(), false, false, false, false)));
+    }
+
+    #[test]
+    fn promotions_are_reported_once_per_square() {
+        let actual = destinations("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", Square::A7);
+        assert_eq!(
+            actual,
+            vec![("a8".to_string(), false, true, false, false), ("b8".to_string(), true, true, false, false)]
+        );
+    }
+
+    #[test]
+    fn a_pinned_piece_has_no_destinations_off_the_pin() {
+        let actual = destinations("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1", Square::E2);
+        assert!(actual.is_empty());
+    }
+}
@@ -1,9 +1,27 @@
+pub mod attacks;
+pub mod bitboard;
+pub mod board;
+pub mod chess_move;
+pub mod color;
+pub mod diagnostics;
 pub mod end_of_game_type;
+pub mod fen;
+#[allow(clippy::module_inception)]
 pub mod game;
+pub mod game_tree;
+pub mod history;
 pub mod game_state;
+pub mod legal_destination;
+pub mod move_record;
 pub mod moving;
 pub mod notation;
+pub mod perft;
+pub mod pgn;
 pub mod piece_move;
 pub mod piece_type;
 pub mod position;
 pub mod rules;
+pub mod see;
+pub mod square;
+pub mod takeback;
+pub mod zobrist;
@@ -0,0 +1,169 @@
+use wasm_bindgen::prelude::*;
+
+use super::board::*;
+use super::chess_move::*;
+use super::fen;
+use super::notation::encode;
+use super::piece_move::generate_moves;
+use super::piece_type::PieceType;
+use super::square::*;
+
+fn piece_name(piece_type: PieceType) -> String {
+    format!("{:?}", piece_type).to_lowercase()
+}
+
+// A move as it was played in a game: what moved where and what it took, written canonically alongside the text
+// the player gave, with the position it led to. Timings and annotations are optional, given by whoever records
+// the game.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveRecord {
+    chess_move: Move,
+    piece: PieceType,
+    captured: Option<PieceType>,
+    // as the player gave it
+    pub text: String,
+    pub san: String,
+    pub castling: bool,
+    #[wasm_bindgen(js_name = enPassant)]
+    pub en_passant: bool,
+    pub check: bool,
+    pub checkmate: bool,
+    // the position after the move
+    pub fen: String,
+    pub hash: u64,
+    // when the move was made, in milliseconds since the epoch
+    #[wasm_bindgen(js_name = timestampMs)]
+    pub timestamp_ms: Option<f64>,
+    // the time the player had left after the move
+    #[wasm_bindgen(js_name = clockMs)]
+    pub clock_ms: Option<u32>,
+    // numeric annotation glyphs, e.g. 1 for "!"
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+}
+
+impl MoveRecord {
+    // plays the legal move on the board, recording it
+    pub fn play(board: &mut Board, chess_move: Move, text: &str) -> MoveRecord {
+        let san = encode(board, &chess_move);
+        let piece = board.piece_type_at(chess_move.from);
+        let captured = board.captured_piece_type(&chess_move);
+        let castling = board.is_castling(&chess_move);
+        let en_passant = board.is_en_passant(&chess_move);
+        board.make_move(chess_move);
+        let check = board.in_check();
+        MoveRecord {
+            chess_move,
+            piece,
+            captured,
+            text: text.to_string(),
+            san,
+            castling,
+            en_passant,
+            check,
+            checkmate: check && generate_moves(board).is_empty(),
+            fen: fen::encode(board),
+            hash: board.hash(),
+            timestamp_ms: None,
+            clock_ms: None,
+            nags: vec![],
+            comment: None,
+        }
+    }
+
+    pub fn chess_move(&self) -> Move {
+        self.chess_move
+    }
+
+    pub fn origin(&self) -> Square {
+        self.chess_move.from
+    }
+
+    pub fn destination(&self) -> Square {
+        self.chess_move.to
+    }
+
+    pub fn piece_type(&self) -> PieceType {
+        self.piece
+    }
+
+    pub fn captured_piece_type(&self) -> Option<PieceType> {
+        self.captured
+    }
+
+    pub fn promotion_piece_type(&self) -> Option<PieceType> {
+        self.chess_move.promotion
+    }
+}
+
+#[wasm_bindgen]
+impl MoveRecord {
+    // square names, e.g. "e2"
+    #[wasm_bindgen(getter, js_name = from)]
+    pub fn from_name(&self) -> String {
+        self.chess_move.from.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = to)]
+    pub fn to_name(&self) -> String {
+        self.chess_move.to.to_string()
+    }
+
+    // piece names, e.g. "knight"
+    #[wasm_bindgen(getter)]
+    pub fn piece(&self) -> String {
+        piece_name(self.piece)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn captured(&self) -> Option<String> {
+        self.captured.map(piece_name)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn promotion(&self) -> Option<String> {
+        self.chess_move.promotion.map(piece_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn record(fen_text: &str, from: Square, to: Square) -> MoveRecord {
+        let mut board = fen::decode(fen_text).unwrap();
+        MoveRecord::play(&mut board, Move::new(from, to), "typed")
+    }
+
+    #[test]
+    fn captures_and_checks_are_recorded() {
+        let record = record("4k3/3p4/8/8/6Q1/8/8/4K3 w - - 0 1", Square::G4, Square::D7);
+        assert_eq!((record.from_name(), record.to_name()), ("g4".to_string(), "d7".to_string()));
+        assert_eq!(record.piece(), "queen");
+        assert_eq!(record.captured(), Some("pawn".to_string()));
+        assert_eq!(record.san, "Qxd7+");
+        assert!(record.check);
+        assert!(!record.checkmate);
+        assert_eq!(record.text, "typed");
+        assert_eq!(record.fen, "4k3/3Q4/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(record.hash, fen::decode(&record.fen).unwrap().hash());
+    }
+
+    #[test]
+    fn special_moves_are_flagged() {
+        let castle = record("4k3/8/8/8/8/8/8/4K2R w K - 0 1", Square::E1, Square::G1);
+        assert!(castle.castling);
+        assert_eq!(castle.san, "O-O");
+        let en_passant = record("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", Square::E5, Square::D6);
+        assert!(en_passant.en_passant);
+        assert_eq!(en_passant.captured_piece_type(), Some(PieceType::Pawn));
+        let mut board = fen::decode("8/P3k3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = Move::promoting(Square::A7, Square::A8, PieceType::Queen);
+        let promotion = MoveRecord::play(&mut board, promotion, "a8=Q");
+        assert_eq!(promotion.promotion(), Some("queen".to_string()));
+        assert_eq!(promotion.piece(), "pawn");
+        assert_eq!(promotion.captured(), None);
+    }
+}
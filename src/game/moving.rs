@@ -1,5 +1,13 @@
+use wasm_bindgen::prelude::*;
+
+use super::board::*;
 use super::game::*;
+use super::history::History;
+use super::move_record::MoveRecord;
+use super::notation::*;
+use super::rules::build_rules;
 
+#[wasm_bindgen(getter_with_clone)]
 #[derive(Debug, PartialEq)]
 pub struct MoveError {
     pub reason: String
@@ -9,12 +17,44 @@ pub struct MoveError {
 // a MoveError explaining why the move could not be made
 pub fn make_move_internal(game: &Game, next_move: String) -> Result<Game, MoveError> {
 
-    // mot implemented - just always accepts whatever is passed in as the new move and preserves the previous game state
+    // the move is rejected by the first rule which fails; diagnose_move only reports on the rules, checking them all
+    for rule in build_rules() {
+        rule.validate(game, next_move.clone())?;
+    }
 
-    let mut moves = game.moves.clone();
-    moves.push(next_move);
+    // the move is recorded with the board after it, sharing the earlier moves with the previous game state,
+    // which is preserved
     Ok(Game {
         state: game.state,
-        moves: moves
+        history: record_move(&game.history, next_move)?,
+        undone: vec![],
+        takeback: None,
     })
-}
\ No newline at end of file
+}
+
+// adds the move to the history, with its record and the board it leads to; an end of game marker has no record
+// and leaves the board as it was
+pub fn record_move(history: &History, next_move: String) -> Result<History, MoveError> {
+    let mut board = history.board();
+    let notation = decode(next_move.clone()).map_err(|err| MoveError { reason: err.reason })?;
+    let mut record = None;
+    if notation.end_of_game.is_none() {
+        let chess_move = resolve(&board, &notation).map_err(|err| MoveError { reason: err.reason })?;
+        record = Some(MoveRecord::play(&mut board, chess_move, &next_move));
+    }
+    Ok(history.push(next_move, record, board))
+}
+
+// plays the moves from the initial position, skipping the end of game marker
+pub fn replay_moves(moves: &[String]) -> Result<Board, MoveError> {
+    let mut board = Board::new();
+    for next_move in moves {
+        let notation = decode(next_move.clone()).map_err(|err| MoveError { reason: err.reason })?;
+        if notation.end_of_game.is_some() {
+            continue;
+        }
+        let chess_move = resolve(&board, &notation).map_err(|err| MoveError { reason: err.reason })?;
+        board.make_move(chess_move);
+    }
+    Ok(board)
+}
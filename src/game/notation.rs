@@ -1,26 +1,32 @@
 
+use super::board::*;
+use super::chess_move::*;
 use super::end_of_game_type::*;
+use super::piece_move::*;
 use super::piece_type::*;
+use super::square::*;
+
+#[derive(Debug)]
 pub struct NotationError {
     pub reason: String,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Notation {
-    text: String,
-    to_rank: Option<u8>,
-    to_file: Option<u8>,
-    piece_type: Option<PieceType>,
-    capture: bool,
-    from_file: Option<u8>,
-    from_rank: Option<u8>,
-    check: bool,
-    checkmate: bool,
-    enpassant: bool,
-    queen_side_castle: bool,
-    king_side_castle: bool,
-    promoted_to_piece_type: Option<PieceType>,
-    end_of_game: Option<EndOfGameType>,
+    pub(crate) text: String,
+    pub(crate) to_rank: Option<Rank>,
+    pub(crate) to_file: Option<File>,
+    pub(crate) piece_type: Option<PieceType>,
+    pub(crate) capture: bool,
+    pub(crate) from_file: Option<File>,
+    pub(crate) from_rank: Option<Rank>,
+    pub(crate) check: bool,
+    pub(crate) checkmate: bool,
+    pub(crate) enpassant: bool,
+    pub(crate) queen_side_castle: bool,
+    pub(crate) king_side_castle: bool,
+    pub(crate) promoted_to_piece_type: Option<PieceType>,
+    pub(crate) end_of_game: Option<EndOfGameType>,
 }
 
 impl Notation {
@@ -45,22 +51,6 @@ impl Notation {
         }
     }
 }
-/*
-    Try to parse the character to 0..7
-        If it's a rank (a..h) then we'll want to treat it as base 18 (0..h) and subtract 10
-        If it's a file (1..8) then we'll want to treat it as base 10 (0..9) and subtract 1
-*/
-fn parse_coordinate(c: char, parse_radix: u32, parse_offset: u32) -> Option<u8> {
-    let digit = c.to_digit(parse_radix)?; // will return None if this wasn't a digit
-                                          // is it out of range?
-    if (digit < parse_offset) || (digit > parse_offset + 7) {
-        None
-    } else {
-        // calculate the resulting coordinate
-        Some((digit - parse_offset) as u8)
-    }
-}
-
 fn decode_piecetype(piecetype_character: &str) -> Option<PieceType> {
     match piecetype_character {
         "K" => Some(PieceType::King),
@@ -89,33 +79,29 @@ fn process_capture(notation: &str) -> (bool, &str) {
     }
 }
 
-fn parse_and_trim_coordinate_suffix(
+fn parse_and_trim_coordinate_suffix<T>(
     last_char: Option<char>,
     notation: &str,
-    parse_radix: u32,
-    parse_offset: u32,
-) -> (Option<u8>, &str) {
-    match last_char {
-        Some(x) => match parse_coordinate(x, parse_radix, parse_offset) {
-            None => (None, notation),
-            some_parsed_coordinate => (some_parsed_coordinate, &notation[..notation.len() - 1]),
-        },
+    parse: fn(char) -> Option<T>,
+) -> (Option<T>, &str) {
+    match last_char.and_then(parse) {
         None => (None, notation),
+        some_parsed_coordinate => (some_parsed_coordinate, &notation[..notation.len() - 1]),
     }
 }
 
-fn process_coordinates(notation: &str) -> (Option<u8>, Option<u8>, &str) {
+fn process_coordinates(notation: &str) -> (Option<Rank>, Option<File>, &str) {
     // destination is always file, rank
     let mut chars = notation.chars().rev();
-    let rank_char = chars.nth(0);
-    let (rank, without_rank) = parse_and_trim_coordinate_suffix(rank_char, notation, 10, 1);
+    let rank_char = chars.next();
+    let (rank, without_rank) = parse_and_trim_coordinate_suffix(rank_char, notation, Rank::from_char);
     let file_char = if rank.is_some() {
-        chars.nth(0)
+        chars.next()
     } else {
         rank_char
     };
     let (file, without_file_and_rank) =
-        parse_and_trim_coordinate_suffix(file_char, without_rank, 18, 10);
+        parse_and_trim_coordinate_suffix(file_char, without_rank, File::from_char);
     (rank, file, without_file_and_rank)
 }
 
@@ -206,10 +192,16 @@ pub fn decode(notation: String) -> Result<Notation, NotationError> {
         let (promoted_to_piece_type, ex_promotion) = process_promotion(ex_suffix);
         let (rank, file, ex_destination) = process_coordinates(ex_promotion);
         let (capture, ex_capture) = process_capture(ex_destination);
-        let (piece_type, ex_piece_type) = process_piece_type(ex_capture);
-        let (from_rank, from_file, ex_from_coordinates) = process_coordinates(ex_piece_type);
+        // the origin is written after the piece in SAN ("Nbd2") but before it in the older style ("c2Qa1")
+        let (san_from_rank, san_from_file, ex_san_from_coordinates) = process_coordinates(ex_capture);
+        let (piece_type, ex_piece_type) = process_piece_type(ex_san_from_coordinates);
+        let (prefix_from_rank, prefix_from_file, ex_from_coordinates) = process_coordinates(ex_piece_type);
+        let from_given_twice = (san_from_rank.is_some() || san_from_file.is_some())
+            && (prefix_from_rank.is_some() || prefix_from_file.is_some());
+        let from_rank = san_from_rank.or(prefix_from_rank);
+        let from_file = san_from_file.or(prefix_from_file);
         match ex_from_coordinates {
-            "" => {
+            "" if !from_given_twice => {
                 parsed.to_rank = rank;
                 parsed.to_file = file;
                 parsed.piece_type = piece_type;
@@ -235,6 +227,195 @@ pub fn decode(notation: String) -> Result<Notation, NotationError> {
     }
 }
 
+// Finds the legal move on the board which the (decoded) notation describes. The notation must identify
+// exactly one move: "Nd2" is ambiguous when both knights can reach d2, and a pawn reaching the last rank
+// must say what it promotes to.
+pub fn resolve(board: &Board, notation: &Notation) -> Result<Move, NotationError> {
+    let candidates: Vec<Move> = generate_moves(board)
+        .into_iter()
+        .filter(|chess_move| {
+            if notation.king_side_castle || notation.queen_side_castle {
+                let file = if notation.king_side_castle { File::G } else { File::C };
+                return board.is_castling(chess_move) && chess_move.to.file() == file;
+            }
+            notation.piece_type == Some(board.piece_type_at(chess_move.from))
+                && !board.is_castling(chess_move)
+                && notation.to_file == Some(chess_move.to.file())
+                && notation.to_rank == Some(chess_move.to.rank())
+                && notation.from_file.is_none_or(|file| file == chess_move.from.file())
+                && notation.from_rank.is_none_or(|rank| rank == chess_move.from.rank())
+                && notation.promoted_to_piece_type == chess_move.promotion
+        })
+        .collect();
+    match candidates.as_slice() {
+        [chess_move] => Ok(*chess_move),
+        [] if notation.end_of_game.is_some() => Err(NotationError {
+            reason: format!("Not a move: {}", notation.text),
+        }),
+        [] => Err(NotationError {
+            reason: format!("Illegal move: {}", notation.text),
+        }),
+        _ => Err(NotationError {
+            reason: format!("Ambiguous move: {}", notation.text),
+        }),
+    }
+}
+
+// Writes the move in standard algebraic notation, e.g. "Nbd2", "exd6", "e8=Q+" or "O-O#". The move must be
+// legal on the board.
+pub fn encode(board: &Board, chess_move: &Move) -> String {
+    let mut text = String::new();
+    let legal_moves = generate_moves(board);
+    if board.is_castling(chess_move) {
+        text.push_str(if chess_move.to.file() == File::G { "O-O" } else { "O-O-O" });
+    } else {
+        let piece_type = board.piece_type_at(chess_move.from);
+        match piece_type.symbol() {
+            Some(symbol) => {
+                text.push(symbol);
+                // name the origin's file, rank or both when another piece of the same type could also move there
+                let rivals: Vec<&Move> = legal_moves
+                    .iter()
+                    .filter(|other| {
+                        other.to == chess_move.to
+                            && other.from != chess_move.from
+                            && board.piece_type_at(other.from) == piece_type
+                    })
+                    .collect();
+                if !rivals.is_empty() {
+                    let same_file = rivals.iter().any(|other| other.from.file() == chess_move.from.file());
+                    let same_rank = rivals.iter().any(|other| other.from.rank() == chess_move.from.rank());
+                    if !same_file {
+                        text.push(chess_move.from.file().to_char());
+                    } else if !same_rank {
+                        text.push(chess_move.from.rank().to_char());
+                    } else {
+                        text.push_str(&chess_move.from.to_string());
+                    }
+                }
+            }
+            None if board.is_capture(chess_move) => text.push(chess_move.from.file().to_char()),
+            None => {}
+        }
+        if board.is_capture(chess_move) {
+            text.push('x');
+        }
+        text.push_str(&chess_move.to.to_string());
+        if let Some(symbol) = chess_move.promotion.and_then(|piece_type| piece_type.symbol()) {
+            text.push('=');
+            text.push(symbol);
+        }
+    }
+    let mut after = board.clone();
+    after.make_move(*chess_move);
+    if after.in_check() {
+        text.push(if generate_moves(&after).is_empty() { '#' } else { '+' });
+    }
+    text
+}
+
+// writes a sequence of moves played from the board, e.g. a principal variation
+pub fn encode_line(board: &Board, moves: &[Move]) -> Vec<String> {
+    let mut board = board.clone();
+    moves
+        .iter()
+        .map(|chess_move| {
+            let text = encode(&board, chess_move);
+            board.make_move(*chess_move);
+            text
+        })
+        .collect()
+}
+
+// How castling is written in UCI notation: as the move the king makes ("e1g1"), or as the king taking its
+// own rook ("e1h1"), which Chess960 needs as there the king may only move one square, or not at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UciCastling {
+    #[default]
+    KingMove,
+    KingTakesRook,
+}
+
+// Reads a move in UCI long algebraic notation, the squares moved from and to followed by any promotion,
+// e.g. "e2e4" or "e7e8q". "0000" is the null move. Nothing is checked against a position, so castling
+// comes out as written.
+pub fn decode_uci(text: &str) -> Result<Move, NotationError> {
+    let invalid = || NotationError {
+        reason: format!("Invalid notation: {}", text),
+    };
+    if text == "0000" {
+        return Ok(Move::NULL);
+    }
+    if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+        return Err(invalid());
+    }
+    let from = text[0..2].parse::<Square>().map_err(|_| invalid())?;
+    let to = text[2..4].parse::<Square>().map_err(|_| invalid())?;
+    match &text[4..] {
+        "" => Ok(Move::new(from, to)),
+        promotion => match decode_piecetype(&promotion.to_uppercase()) {
+            Some(PieceType::King) | None => Err(invalid()),
+            Some(piece_type) => Ok(Move::promoting(from, to, piece_type)),
+        },
+    }
+}
+
+// Finds the legal move on the board which the UCI notation describes. Castling is understood whether it's
+// written as the king's move or as the king taking its rook.
+pub fn resolve_uci(board: &Board, text: &str) -> Result<Move, NotationError> {
+    let mut chess_move = decode_uci(text)?;
+    let king = board.piece_at(chess_move.from) == Some((PieceType::King, board.side_to_move()));
+    if king && board.piece_at(chess_move.to) == Some((PieceType::Rook, board.side_to_move())) {
+        let file = if chess_move.to.file() > chess_move.from.file() { File::G } else { File::C };
+        chess_move.to = Square::new(file, chess_move.from.rank());
+    }
+    if generate_moves(board).contains(&chess_move) {
+        Ok(chess_move)
+    } else {
+        Err(NotationError {
+            reason: format!("Illegal move: {}", text),
+        })
+    }
+}
+
+// writes the move in UCI long algebraic notation, e.g. "e2e4", "e7e8q" or, for castling, "e1g1" or "e1h1"
+pub fn encode_uci(board: &Board, chess_move: &Move, castling: UciCastling) -> String {
+    if chess_move.is_null() {
+        return "0000".to_string();
+    }
+    let mut to = chess_move.to;
+    if castling == UciCastling::KingTakesRook && board.is_castling(chess_move) {
+        let file = if to.file() == File::G { File::H } else { File::A };
+        to = Square::new(file, to.rank());
+    }
+    let mut text = format!("{}{}", chess_move.from, to);
+    if let Some(symbol) = chess_move.promotion.and_then(|piece_type| piece_type.symbol()) {
+        text.push(symbol.to_ascii_lowercase());
+    }
+    text
+}
+
+pub fn encode_uci_line(board: &Board, moves: &[Move], castling: UciCastling) -> Vec<String> {
+    let mut board = board.clone();
+    moves
+        .iter()
+        .map(|chess_move| {
+            let text = encode_uci(&board, chess_move, castling);
+            board.make_move(*chess_move);
+            text
+        })
+        .collect()
+}
+
+pub fn san_to_uci(board: &Board, san: &str, castling: UciCastling) -> Result<String, NotationError> {
+    let chess_move = resolve(board, &decode(san.to_string())?)?;
+    Ok(encode_uci(board, &chess_move, castling))
+}
+
+pub fn uci_to_san(board: &Board, uci: &str) -> Result<String, NotationError> {
+    Ok(encode(board, &resolve_uci(board, uci)?))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -243,8 +424,8 @@ mod tests {
     fn test_decode(notation: &str, configure_expected: impl Fn(&mut Notation)) {
         let mut expected = Notation {
             text: notation.to_string(),
-            to_file: Some(0),
-            to_rank: Some(0),
+            to_file: Some(File::A),
+            to_rank: Some(Rank::ONE),
             piece_type: Some(PieceType::Pawn),
             capture: false,
             from_file: None,
@@ -259,7 +440,7 @@ mod tests {
         };
         configure_expected(&mut expected);
         let actual = decode(notation.to_string()).unwrap_or_else(|err| {
-            panic!(err);
+            panic!("{}", err.reason);
         });
         assert_eq!(expected, actual);
     }
@@ -376,7 +557,7 @@ mod tests {
     fn should_handle_enpassant_suffix() {
         test_decode("a2e.p.", |x| {
             x.enpassant = true;
-            x.to_rank = Some(1);
+            x.to_rank = Some(Rank::TWO);
         });
     }
 
@@ -385,8 +566,8 @@ mod tests {
     fn should_handle_promotion_suffix_PGN() {
         test_decode("e8=Q", |x| {
             x.promoted_to_piece_type = Some(PieceType::Queen);
-            x.to_file = Some(4);
-            x.to_rank = Some(7);
+            x.to_file = Some(File::E);
+            x.to_rank = Some(Rank::EIGHT);
         });
     }
 
@@ -395,24 +576,24 @@ mod tests {
     #[test]
     fn translate_rank_and_file() {
         test_decode("e4", |x| {
-            x.to_rank = Some(3);
-            x.to_file = Some(4);
+            x.to_rank = Some(Rank::FOUR);
+            x.to_file = Some(File::E);
         });
     }
 
     #[test]
     fn translate_rank_and_file_upper_bounds() {
         test_decode("h8", |x| {
-            x.to_rank = Some(7);
-            x.to_file = Some(7);
+            x.to_rank = Some(Rank::EIGHT);
+            x.to_file = Some(File::H);
         });
     }
 
     #[test]
     fn translate_rank_and_file_lower_bounds() {
         test_decode("a1", |x| {
-            x.to_rank = Some(0);
-            x.to_file = Some(0);
+            x.to_rank = Some(Rank::ONE);
+            x.to_file = Some(File::A);
         });
     }
 
@@ -478,7 +659,7 @@ mod tests {
         .iter()
         {
             let actual = decode(notation.to_string()).unwrap_or_else(|err| {
-                panic!(err);
+                panic!("{}", err.reason);
             });
             assert_eq!(actual.piece_type.unwrap(), *piece_type)
         }
@@ -494,7 +675,7 @@ mod tests {
     fn note_from_file() {
         test_decode("bRa1", |x| {
             x.piece_type = Some(PieceType::Rook);
-            x.from_file = Some(1);
+            x.from_file = Some(File::B);
         });
     }
 
@@ -502,7 +683,7 @@ mod tests {
     fn note_from_rank() {
         test_decode("2Ra1", |x| {
             x.piece_type = Some(PieceType::Rook);
-            x.from_rank = Some(1);
+            x.from_rank = Some(Rank::TWO);
         });
     }
 
@@ -510,11 +691,47 @@ mod tests {
     fn note_from_rank_and_file() {
         test_decode("c2Qa1", |x| {
             x.piece_type = Some(PieceType::Queen);
-            x.from_rank = Some(1);
-            x.from_file = Some(2);
+            x.from_rank = Some(Rank::TWO);
+            x.from_file = Some(File::C);
+        });
+    }
+
+    #[test]
+    fn note_san_from_file() {
+        test_decode("Nbd2", |x| {
+            x.piece_type = Some(PieceType::Knight);
+            x.from_file = Some(File::B);
+            x.to_file = Some(File::D);
+            x.to_rank = Some(Rank::TWO);
+        });
+    }
+
+    #[test]
+    fn note_san_from_rank_and_file_with_capture() {
+        test_decode("Qh4xe1", |x| {
+            x.piece_type = Some(PieceType::Queen);
+            x.from_file = Some(File::H);
+            x.from_rank = Some(Rank::FOUR);
+            x.capture = true;
+            x.to_file = Some(File::E);
+        });
+    }
+
+    #[test]
+    fn pawn_capture_notes_the_from_file() {
+        test_decode("exd5", |x| {
+            x.from_file = Some(File::E);
+            x.capture = true;
+            x.to_file = Some(File::D);
+            x.to_rank = Some(Rank::FIVE);
         });
     }
 
+    #[test]
+    fn from_coordinates_cannot_be_given_twice() {
+        test_decode_failure("c2Qc2a1", "Invalid notation: c2Qc2a1");
+    }
+
     #[test]
     fn invalid_from_rank() {
         test_decode_failure("iRa1", "Invalid notation: iRa1");
@@ -530,4 +747,164 @@ mod tests {
         test_decode_failure("i9Ra1", "Invalid notation: i9Ra1");
     }
 
-}
\ No newline at end of file
+    // resolving against a board
+
+    fn test_resolve(fen: &str, notation: &str) -> Result<Move, String> {
+        let board = crate::game::fen::decode(fen).unwrap_or_else(|err| panic!("{}", err.reason));
+        let decoded = decode(notation.to_string()).unwrap_or_else(|err| panic!("{}", err.reason));
+        resolve(&board, &decoded).map_err(|err| err.reason)
+    }
+
+    #[test]
+    fn resolves_a_pawn_move() {
+        assert_eq!(test_resolve(crate::game::fen::INITIAL_FEN, "e4"), Ok(Move::new(Square::E2, Square::E4)));
+    }
+
+    #[test]
+    fn resolves_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(test_resolve(fen, "O-O"), Ok(Move::new(Square::E1, Square::G1)));
+        assert_eq!(test_resolve(fen, "0-0-0"), Ok(Move::new(Square::E1, Square::C1)));
+    }
+
+    #[test]
+    fn a_disambiguated_move_resolves() {
+        let fen = "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1";
+        assert_eq!(test_resolve(fen, "Nd2"), Err("Ambiguous move: Nd2".to_string()));
+        assert_eq!(test_resolve(fen, "Nbd2"), Ok(Move::new(Square::B1, Square::D2)));
+    }
+
+    #[test]
+    fn a_promotion_must_name_the_piece() {
+        let fen = "4k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(test_resolve(fen, "a8"), Err("Illegal move: a8".to_string()));
+        assert_eq!(
+            test_resolve(fen, "a8=N"),
+            Ok(Move::promoting(Square::A7, Square::A8, PieceType::Knight))
+        );
+    }
+
+    #[test]
+    fn a_move_which_leaves_the_king_in_check_is_illegal() {
+        let fen = "4k3/8/8/8/8/8/4R3/4K3 b - - 0 1";
+        assert_eq!(test_resolve(fen, "Ke7"), Err("Illegal move: Ke7".to_string()));
+        assert!(test_resolve(fen, "Kd7").is_ok());
+    }
+
+    // encoding
+
+    fn test_encode(fen: &str, notation: &str) {
+        let board = crate::game::fen::decode(fen).unwrap_or_else(|err| panic!("{}", err.reason));
+        let decoded = decode(notation.to_string()).unwrap_or_else(|err| panic!("{}", err.reason));
+        let chess_move = resolve(&board, &decoded).unwrap_or_else(|err| panic!("{}", err.reason));
+        assert_eq!(encode(&board, &chess_move), notation);
+    }
+
+    #[test]
+    fn encodes_simple_moves() {
+        test_encode(crate::game::fen::INITIAL_FEN, "e4");
+        test_encode(crate::game::fen::INITIAL_FEN, "Nf3");
+    }
+
+    #[test]
+    fn encodes_captures_checks_and_mates() {
+        test_encode("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2", "exd5");
+        test_encode("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "Ra8+");
+        test_encode("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "Ra8#");
+        test_encode("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 2", "dxe6");
+    }
+
+    #[test]
+    fn encodes_castling_and_promotion() {
+        test_encode("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "O-O");
+        test_encode("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "O-O-O");
+        test_encode("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "axb8=N");
+    }
+
+    #[test]
+    fn encodes_only_the_disambiguation_needed() {
+        test_encode("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "Nbd2");
+        test_encode("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "R1a3");
+        test_encode("4k3/8/8/8/8/Q1Q5/8/Q3K3 w - - 0 1", "Qa3b2");
+    }
+
+    // UCI long algebraic notation
+
+    fn board(fen: &str) -> Board {
+        crate::game::fen::decode(fen).unwrap_or_else(|err| panic!("{}", err.reason))
+    }
+
+    #[test]
+    fn decodes_uci() {
+        assert_eq!(decode_uci("e2e4").unwrap(), Move::new(Square::E2, Square::E4));
+        assert_eq!(decode_uci("e7e8q").unwrap(), Move::promoting(Square::E7, Square::E8, PieceType::Queen));
+        assert_eq!(decode_uci("a2a1N").unwrap(), Move::promoting(Square::A2, Square::A1, PieceType::Knight));
+        assert_eq!(decode_uci("0000").unwrap(), Move::NULL);
+    }
+
+    #[test]
+    fn invalid_uci() {
+        for text in &["", "e2", "e2e9", "e7e8k", "e7e8qq", "Nf3", "e2-e4", "é2e4"] {
+            assert_eq!(decode_uci(text).unwrap_err().reason, format!("Invalid notation: {}", text));
+        }
+    }
+
+    #[test]
+    fn resolves_uci() {
+        let initial = board(crate::game::fen::INITIAL_FEN);
+        assert_eq!(resolve_uci(&initial, "g1f3").unwrap(), Move::new(Square::G1, Square::F3));
+        assert_eq!(resolve_uci(&initial, "e2e5").unwrap_err().reason, "Illegal move: e2e5");
+        assert_eq!(resolve_uci(&initial, "0000").unwrap_err().reason, "Illegal move: 0000");
+        let promotion = board("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(resolve_uci(&promotion, "a7a8").unwrap_err().reason, "Illegal move: a7a8");
+        assert!(resolve_uci(&promotion, "a7a8r").is_ok());
+    }
+
+    #[test]
+    fn resolves_castling_either_way() {
+        let castling = board("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        assert_eq!(resolve_uci(&castling, "e8g8").unwrap(), Move::new(Square::E8, Square::G8));
+        assert_eq!(resolve_uci(&castling, "e8h8").unwrap(), Move::new(Square::E8, Square::G8));
+        assert_eq!(resolve_uci(&castling, "e8a8").unwrap(), Move::new(Square::E8, Square::C8));
+        // without the right to castle, taking the rook is as illegal as moving two squares
+        let moved = board("r3k2r/8/8/8/8/8/8/R3K2R b KQ - 0 1");
+        assert!(resolve_uci(&moved, "e8h8").is_err());
+        assert!(resolve_uci(&moved, "e8g8").is_err());
+    }
+
+    #[test]
+    fn encodes_uci() {
+        let castling = board("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let encoded = |text: &str, style| encode_uci(&castling, &resolve_uci(&castling, text).unwrap(), style);
+        assert_eq!(encoded("e1g1", UciCastling::KingMove), "e1g1");
+        assert_eq!(encoded("e1g1", UciCastling::KingTakesRook), "e1h1");
+        assert_eq!(encoded("e1a1", UciCastling::KingMove), "e1c1");
+        assert_eq!(encoded("e1c1", UciCastling::KingTakesRook), "e1a1");
+        // the king moving two squares is only castling from its own square
+        assert_eq!(encoded("a1d1", UciCastling::KingTakesRook), "a1d1");
+        assert_eq!(encoded("b7a8q", UciCastling::KingTakesRook), "b7a8q");
+        assert_eq!(encode_uci(&castling, &Move::NULL, UciCastling::KingMove), "0000");
+    }
+
+    #[test]
+    fn encodes_a_uci_line() {
+        let initial = board(crate::game::fen::INITIAL_FEN);
+        let moves: Vec<Move> = ["e2e4", "e7e5", "g1f3"].iter().map(|text| decode_uci(text).unwrap()).collect();
+        assert_eq!(encode_uci_line(&initial, &moves, UciCastling::KingMove), vec!["e2e4", "e7e5", "g1f3"]);
+    }
+
+    #[test]
+    fn converts_between_san_and_uci() {
+        let castling = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(san_to_uci(&castling, "O-O", UciCastling::KingMove).unwrap(), "e1g1");
+        assert_eq!(san_to_uci(&castling, "O-O-O", UciCastling::KingTakesRook).unwrap(), "e1a1");
+        assert_eq!(san_to_uci(&castling, "Rxa8+", UciCastling::KingMove).unwrap(), "a1a8");
+        assert_eq!(uci_to_san(&castling, "e1h1").unwrap(), "O-O");
+        assert_eq!(uci_to_san(&castling, "h1h8").unwrap(), "Rxh8+");
+        assert_eq!(san_to_uci(&castling, "Qd1", UciCastling::KingMove).unwrap_err().reason, "Illegal move: Qd1");
+        assert_eq!(uci_to_san(&castling, "e1e3").unwrap_err().reason, "Illegal move: e1e3");
+        let promotion = board("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(uci_to_san(&promotion, "a7b8n").unwrap(), "axb8=N");
+        assert_eq!(san_to_uci(&promotion, "axb8=N", UciCastling::KingMove).unwrap(), "a7b8n");
+    }
+}
@@ -0,0 +1,80 @@
+// Perft ("performance test") counts the leaf nodes of the tree of legal moves to a given depth. The counts
+// for well known positions are published, so comparing against them checks move generation exactly.
+
+use super::board::*;
+use super::chess_move::*;
+use super::fen;
+use super::fen::FenError;
+use super::piece_move::generate_moves;
+
+pub fn perft(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = generate_moves(board);
+    // the moves at the last ply don't need to be made to be counted
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for chess_move in moves {
+        board.make_move(chess_move);
+        nodes += perft(board, depth - 1);
+        board.unmake_move();
+    }
+    nodes
+}
+
+// the perft count below each root move, in the order the moves were generated
+pub fn divide(board: &mut Board, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+    generate_moves(board)
+        .into_iter()
+        .map(|chess_move| {
+            board.make_move(chess_move);
+            let nodes = perft(board, depth - 1);
+            board.unmake_move();
+            (chess_move, nodes)
+        })
+        .collect()
+}
+
+pub fn perft_fen(fen: &str, depth: u32) -> Result<u64, FenError> {
+    Ok(perft(&mut fen::decode(fen)?, depth))
+}
+
+pub fn divide_fen(fen: &str, depth: u32) -> Result<Vec<(Move, u64)>, FenError> {
+    Ok(divide(&mut fen::decode(fen)?, depth))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::square::Square;
+
+    #[test]
+    fn perft_of_depth_zero_is_one() {
+        assert_eq!(perft(&mut Board::new(), 0), 1);
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let divided = divide_fen(fen::INITIAL_FEN, 3).unwrap();
+        assert_eq!(divided.len(), 20);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        let (_, nodes) = divided.iter().find(|(m, _)| *m == Move::new(Square::E2, Square::E4)).unwrap();
+        assert_eq!(*nodes, 600);
+    }
+
+    #[test]
+    fn perft_leaves_the_board_unchanged() {
+        let mut board = Board::new();
+        let before = board.clone();
+        perft(&mut board, 3);
+        assert_eq!(board, before);
+        assert_eq!(board.hash(), before.hash());
+    }
+}
@@ -0,0 +1,354 @@
+// Portable Game Notation: games as tag pairs ([Event "..."]) followed by the moves in SAN, e.g.
+// "1. e4 e5 2. Nf3 Nc6 1-0". The text is split into games, each of tags and movetext tokens; a PgnGame keeps
+// only the main line's moves, skipping comments, variations and numeric annotation glyphs (a GameTree keeps
+// them).
+
+use super::board::*;
+use super::chess_move::*;
+use super::fen;
+use super::notation::*;
+
+#[derive(Debug, PartialEq)]
+pub struct PgnError {
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    // in standard algebraic notation, without move numbers
+    pub moves: Vec<String>,
+    // "1-0", "0-1", "1/2-1/2" or "*", as given after the moves
+    pub result: Option<String>,
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    // the result after the moves, or failing that the Result tag
+    pub fn outcome(&self) -> Option<&str> {
+        self.result.as_deref().or_else(|| self.tag("Result"))
+    }
+
+    // the position the game starts from: the FEN tag's if there is one, otherwise the initial position
+    pub fn start(&self) -> Result<Board, PgnError> {
+        match self.tag("FEN") {
+            Some(text) => fen::decode(text).map_err(|err| PgnError { reason: err.reason }),
+            None => Ok(Board::new()),
+        }
+    }
+
+    // the game's moves, checked for legality from its start position
+    pub fn replay(&self) -> Result<(Board, Vec<Move>), PgnError> {
+        let start = self.start()?;
+        let mut board = start.clone();
+        let mut moves = vec![];
+        for (ply, san) in self.moves.iter().enumerate() {
+            let chess_move = decode(san.clone())
+                .and_then(|notation| resolve(&board, &notation))
+                .map_err(|err| PgnError { reason: format!("{} at ply {}: {}", err.reason, ply + 1, san) })?;
+            board.make_move(chess_move);
+            moves.push(chess_move);
+        }
+        Ok((start, moves))
+    }
+}
+
+pub(crate) fn parse_tag(line: &str) -> Result<(String, String), PgnError> {
+    let invalid = || PgnError { reason: format!("Invalid tag: {}", line) };
+    let inner = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).ok_or_else(invalid)?;
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(invalid)?;
+    let value = value.trim();
+    let value = value.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')).ok_or_else(invalid)?;
+    Ok((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+// every game in the text, in order
+pub fn parse_games(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    parse_each_game(text).into_iter().collect()
+}
+
+// every game in the text, in order, each read or the reason it couldn't be
+pub fn parse_each_game(text: &str) -> Vec<Result<PgnGame, PgnError>> {
+    split_games(text)
+        .into_iter()
+        .map(|game| {
+            let game = game?;
+            let mut moves = vec![];
+            let mut result = None;
+            let mut depth = 0;
+            for token in game.tokens {
+                match token {
+                    Token::Move(san) if depth == 0 => moves.push(san),
+                    Token::Open => depth += 1,
+                    Token::Close => depth -= 1,
+                    Token::Result(text) => result = Some(text),
+                    _ => {}
+                }
+            }
+            Ok(PgnGame { tags: game.tags, moves, result })
+        })
+        .collect()
+}
+
+// a piece of a game's movetext
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    // in SAN, without its number ("12.", "12...") or suffix annotation ("!?", given as a Nag after it)
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    // a variation's start and end
+    Open,
+    Close,
+    Result(String),
+}
+
+// a game's tag pairs and the tokens of its movetext, ending with the result if one was given
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameText {
+    pub tags: Vec<(String, String)>,
+    pub tokens: Vec<Token>,
+}
+
+// the suffix annotations PGN allows after a move, and the NAGs they stand for
+const SUFFIXES: [(&str, u8); 6] = [("!!", 3), ("??", 4), ("!?", 5), ("?!", 6), ("!", 1), ("?", 2)];
+
+#[derive(Default)]
+struct Splitter {
+    games: Vec<Result<GameText, PgnError>>,
+    game: GameText,
+    // the text of a {} comment still open
+    comment: Option<String>,
+    depth: usize,
+}
+
+impl Splitter {
+    fn finish(&mut self) {
+        self.games.push(Ok(std::mem::take(&mut self.game)));
+    }
+
+    fn word(&mut self, word: &str) -> Result<(), PgnError> {
+        if RESULTS.contains(&word) {
+            if self.depth > 0 {
+                return Err(PgnError { reason: "Unterminated variation".to_string() });
+            }
+            self.game.tokens.push(Token::Result(word.to_string()));
+            self.finish();
+            return Ok(());
+        }
+        if let Some(nag) = word.strip_prefix('$') {
+            let nag = nag.parse().map_err(|_| PgnError { reason: format!("Invalid annotation: {}", word) })?;
+            self.game.tokens.push(Token::Nag(nag));
+            return Ok(());
+        }
+        // move numbers may run into the move
+        let word = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if word.is_empty() {
+            return Ok(());
+        }
+        match SUFFIXES.iter().find(|(suffix, _)| word.ends_with(suffix)) {
+            Some((suffix, nag)) => {
+                self.game.tokens.push(Token::Move(word[..word.len() - suffix.len()].to_string()));
+                self.game.tokens.push(Token::Nag(*nag));
+            }
+            None => self.game.tokens.push(Token::Move(word.to_string())),
+        }
+        Ok(())
+    }
+
+    fn movetext(&mut self, line: &str) -> Result<(), PgnError> {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if let Some(comment) = self.comment.as_mut() {
+                if c == '}' {
+                    let comment = self.comment.take().unwrap_or_default();
+                    self.game.tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" ")));
+                } else {
+                    comment.push(c);
+                }
+                continue;
+            }
+            match c {
+                '{' => self.comment = Some(String::new()),
+                // the rest of the line is a comment
+                ';' => {
+                    let comment: String = chars.by_ref().collect();
+                    self.game.tokens.push(Token::Comment(comment.trim().to_string()));
+                }
+                '(' => {
+                    self.depth += 1;
+                    self.game.tokens.push(Token::Open);
+                }
+                ')' if self.depth == 0 => return Err(PgnError { reason: "Unmatched ')'".to_string() }),
+                ')' => {
+                    self.depth -= 1;
+                    self.game.tokens.push(Token::Close);
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut word = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || "{};()".contains(c) {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    self.word(&word)?;
+                }
+            }
+        }
+        // a comment running onto the next line keeps the line break as a space
+        if let Some(comment) = self.comment.as_mut() {
+            comment.push(' ');
+        }
+        Ok(())
+    }
+}
+
+// The games in the text, in order, each split into tags and tokens or the reason it couldn't be. A game ends
+// with its result, or where tags follow its moves; after one which can't be read, splitting carries on from
+// the next game's tags.
+pub fn split_games(text: &str) -> Vec<Result<GameText, PgnError>> {
+    let mut splitter = Splitter::default();
+    let mut failure = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if failure.is_some() {
+            if !trimmed.starts_with('[') {
+                continue;
+            }
+            splitter.games.push(Err(failure.take().unwrap()));
+            splitter = Splitter { games: std::mem::take(&mut splitter.games), ..Splitter::default() };
+        }
+        let in_comment = splitter.comment.is_some();
+        // a line starting with % is an escape, ignored by readers
+        if !in_comment && trimmed.starts_with('%') {
+            continue;
+        }
+        if !in_comment && splitter.depth == 0 && trimmed.starts_with('[') {
+            // tags after moves begin the next game, even when the last one had no result
+            if !splitter.game.tokens.is_empty() {
+                splitter.finish();
+            }
+            match parse_tag(trimmed) {
+                Ok(tag) => splitter.game.tags.push(tag),
+                Err(err) => failure = Some(err),
+            }
+            continue;
+        }
+        if let Err(err) = splitter.movetext(line) {
+            failure = Some(err);
+        }
+    }
+    let reason = if failure.is_some() {
+        failure.map(|err| err.reason)
+    } else if splitter.comment.is_some() {
+        Some("Unterminated comment".to_string())
+    } else if splitter.depth > 0 {
+        Some("Unterminated variation".to_string())
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => splitter.games.push(Err(PgnError { reason })),
+        None if !splitter.game.tokens.is_empty() || !splitter.game.tags.is_empty() => splitter.finish(),
+        None => {}
+    }
+    splitter.games
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const GAMES: &str = r#"[Event "Casual"]
+[White "Anderssen"]
+[Result "1-0"]
+
+1. e4 e5 2. f4 {The King's Gambit} exf4 3. Bc4 Qh4+ (3... Nf6 $1) 4. Kf1 b5!? ; a wing gambit
+5. Bxb5 1-0
+
+[Event "Second"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1.e4 Kd7 2.e5 *
+"#;
+
+    #[test]
+    fn main_lines_are_read() {
+        let games = parse_games(GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("White"), Some("Anderssen"));
+        assert_eq!(games[0].moves, ["e4", "e5", "f4", "exf4", "Bc4", "Qh4+", "Kf1", "b5", "Bxb5"]);
+        assert_eq!(games[0].outcome(), Some("1-0"));
+        assert_eq!(games[1].moves, ["e4", "Kd7", "e5"]);
+        assert_eq!(games[1].outcome(), Some("*"));
+    }
+
+    #[test]
+    fn games_are_replayed_from_their_start() {
+        let games = parse_games(GAMES).unwrap();
+        let (start, moves) = games[1].replay().unwrap();
+        assert_eq!(fen::encode(&start), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(moves.len(), 3);
+        let (start, moves) = games[0].replay().unwrap();
+        assert_eq!(start, Board::new());
+        assert_eq!(moves.len(), 9);
+    }
+
+    #[test]
+    fn illegal_moves_are_reported() {
+        let games = parse_games("1. e4 e5 2. Ke3 1-0").unwrap();
+        let err = games[0].replay().unwrap_err();
+        assert!(err.reason.ends_with("at ply 3: Ke3"), "{}", err.reason);
+    }
+
+    #[test]
+    fn unterminated_text_is_an_error() {
+        assert!(parse_games("1. e4 { unfinished").is_err());
+        assert!(parse_games("1. e4 (1. d4").is_err());
+        assert!(parse_games("[Event \"Broken]").is_err());
+    }
+
+    #[test]
+    fn movetext_is_split_into_tokens() {
+        let games = split_games("[Event \"A\"]\n1. e4!? {best\nby test} e5 (1... c5 $14) ; done\n%escape\n2. Nf3 *");
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tags, [("Event".to_string(), "A".to_string())]);
+        assert_eq!(
+            game.tokens,
+            [
+                Token::Move("e4".to_string()),
+                Token::Nag(5),
+                Token::Comment("best by test".to_string()),
+                Token::Move("e5".to_string()),
+                Token::Open,
+                Token::Move("c5".to_string()),
+                Token::Nag(14),
+                Token::Close,
+                Token::Comment("done".to_string()),
+                Token::Move("Nf3".to_string()),
+                Token::Result("*".to_string()),
+            ]
+        );
+        // tags after moves begin a new game
+        assert_eq!(split_games("1. e4\n[Event \"B\"]\n1. d4").len(), 2);
+    }
+
+    #[test]
+    fn games_after_one_which_cant_be_read_still_are() {
+        let text = "[Event \"Broken]\n1. e4 1-0\n[Event \"Fine\"]\n1. d4 ) d5\n[Event \"Last\"]\n1. c4 *";
+        let games = parse_each_game(text);
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].as_ref().unwrap_err().reason, "Invalid tag: [Event \"Broken]");
+        assert_eq!(games[1].as_ref().unwrap_err().reason, "Unmatched ')'");
+        assert_eq!(games[2].as_ref().unwrap().moves, ["c4"]);
+    }
+}
@@ -1,50 +1,184 @@
+use super::attacks::*;
+use super::bitboard::*;
+use super::board::*;
+use super::chess_move::*;
+use super::color::*;
 use super::piece_type::*;
+use super::square::*;
 
-fn validate_moves(moves: Vec<(u8, u8)>) -> Vec<(u8, u8)> {
-    let mut valid_moves: Vec<(u8, u8)> = [].to_vec();
-    for m in moves.iter() {
-        let (m1, m2) = *m;
-        if m1 < 8 && m2 < 8 {
-            valid_moves.push((m1, m2))
+fn get_pawn_moves(start: Square, color: Color) -> Vec<Square> {
+    let forward = if color.is_white() { 1 } else { -1 };
+    let mut possible_moves = [].to_vec();
+    let relative_rank = start.rank().relative_to(color);
+    if relative_rank != Rank::ONE {
+        possible_moves.push(start.offset(0, forward));
+        possible_moves.push(start.offset(1, forward));
+        if relative_rank == Rank::TWO {
+            possible_moves.push(start.offset(0, 2 * forward));
         }
+        possible_moves.push(start.offset(-1, forward));
     }
-    return valid_moves;
+    // moves which would leave the board have no square
+    possible_moves.into_iter().flatten().collect()
 }
 
-fn get_pawn_moves(start: (u8, u8), is_white: bool) -> Vec<(u8, u8)> {
-    let (m1, m2) = start;
-    let mut possible_moves = [].to_vec();
-    if is_white {
-        if m1 > 0 {
-            possible_moves.push((m1 + 1, m2));
-            possible_moves.push((m1 + 1, m2 + 1));
-            if m1 == 1 {
-                possible_moves.push((m1 + 2, m2));
-            }
-            if m2 > 0 {
-                possible_moves.push((m1 + 1, m2 - 1));
-            }
-        }
+pub fn get_moves(color: Color, start: Square, piece_type: PieceType) -> Vec<Square> {
+    match piece_type {
+        PieceType::Pawn => get_pawn_moves(start, color),
+        _ => panic!("Not implemented yet"),
+    }
+}
+
+const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+fn push_pawn_move(moves: &mut Vec<Move>, from: Square, to: Square, color: Color) {
+    if to.rank().relative_to(color) == Rank::EIGHT {
+        moves.extend(PROMOTIONS.iter().map(|piece_type| Move::promoting(from, to, *piece_type)));
     } else {
-        if m1 < 7 {
-            possible_moves.push((m1 - 1, m2));
-            possible_moves.push((m1 - 1, m2 + 1));
-            if m1 == 6 {
-                possible_moves.push((m1 - 2, m2));
+        moves.push(Move::new(from, to));
+    }
+}
+
+// own pieces which can't leave the line between their king and an opponent's rook, bishop or queen
+fn pinned_pieces(board: &Board, king: Square, color: Color) -> Bitboard {
+    let them = !color;
+    let straight = board.pieces_of(them, PieceType::Rook) | board.pieces_of(them, PieceType::Queen);
+    let diagonal = board.pieces_of(them, PieceType::Bishop) | board.pieces_of(them, PieceType::Queen);
+    let snipers = (rook_attacks(king, Bitboard::EMPTY) & straight) | (bishop_attacks(king, Bitboard::EMPTY) & diagonal);
+    let mut pinned = Bitboard::EMPTY;
+    for sniper in snipers.squares() {
+        let blockers = between(king, sniper) & board.occupied();
+        if blockers.count() == 1 {
+            pinned |= blockers & board.color(color);
+        }
+    }
+    pinned
+}
+
+fn generate_castling(board: &Board, king: Square, color: Color, moves: &mut Vec<Move>) {
+    let rank = Rank::ONE.relative_to(color);
+    if king != Square::new(File::E, rank) {
+        return;
+    }
+    let rights = board.castling_rights();
+    let sides = [
+        (CastlingRights::king_side(color), File::H, File::G, File::F),
+        (CastlingRights::queen_side(color), File::A, File::C, File::D),
+    ];
+    for (right, rook_file, king_to, king_passes) in sides.iter() {
+        let rook = Square::new(*rook_file, rank);
+        let to = Square::new(*king_to, rank);
+        if rights.contains(*right)
+            && board.piece_at(rook) == Some((PieceType::Rook, color))
+            && (between(king, rook) & board.occupied()).is_empty()
+            && !board.is_attacked(Square::new(*king_passes, rank), !color)
+            && !board.is_attacked(to, !color)
+        {
+            moves.push(Move::new(king, to));
+        }
+    }
+}
+
+fn generate_en_passant(board: &Board, king: Option<Square>, color: Color, moves: &mut Vec<Move>) {
+    let to = match board.en_passant() {
+        Some(to) => to,
+        None => return,
+    };
+    let captured = Square::new(to.file(), to.rank().offset(if color.is_white() { -1 } else { 1 }).unwrap());
+    for from in (pawn_attacks(!color, to) & board.pieces_of(color, PieceType::Pawn)).squares() {
+        // two pawns leave the rank at once, so rather than reasoning about pins the king is checked directly
+        let occupied = (board.occupied().without(from).without(captured)).with(to);
+        let exposed = king.is_some_and(|king| {
+            (board.attackers_to(king, occupied) & board.color(!color)).without(captured).any()
+        });
+        if !exposed {
+            moves.push(Move::new(from, to));
+        }
+    }
+}
+
+// All the legal moves for the side to move. Moves which would leave the king in check are never generated:
+// pieces pinned to the king may only move along the pin, and when in check only moves which capture the
+// checking piece, block it or move the king are generated.
+pub fn generate_moves(board: &Board) -> Vec<Move> {
+    let mut moves = Vec::with_capacity(64);
+    let color = board.side_to_move();
+    let own = board.color(color);
+    let enemy = board.color(!color);
+    let occupied = board.occupied();
+    let king = board.king_square(color);
+
+    let (checkers, pinned) = match king {
+        Some(king) => (board.checkers(), pinned_pieces(board, king, color)),
+        None => (Bitboard::EMPTY, Bitboard::EMPTY),
+    };
+
+    if let Some(king) = king {
+        // the king is taken off the board to find the squares it would be attacked on, so that it can't
+        // step back along the line of a checking slider
+        let without_king = occupied.without(king);
+        for to in (king_attacks(king) & !own).squares() {
+            if (board.attackers_to(to, without_king) & enemy).is_empty() {
+                moves.push(Move::new(king, to));
             }
-            if m2 > 0 {
-                possible_moves.push((m1 - 1, m2 - 1));
+        }
+        if checkers.count() > 1 {
+            return moves;
+        }
+        if checkers.is_empty() {
+            generate_castling(board, king, color, &mut moves);
+        }
+    }
+
+    // when in check, other pieces must capture the checking piece or block it
+    let target = match (king, checkers.first()) {
+        (Some(king), Some(checker)) => between(king, checker).with(checker),
+        _ => !own,
+    };
+    let allowed = |from: Square, to: Square| match king {
+        Some(king) if pinned.contains(from) => line(king, from).contains(to),
+        _ => true,
+    };
+
+    for piece_type in [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen].iter() {
+        for from in board.pieces_of(color, *piece_type).squares() {
+            let attacks = match piece_type {
+                PieceType::Knight => knight_attacks(from),
+                PieceType::Bishop => bishop_attacks(from, occupied),
+                PieceType::Rook => rook_attacks(from, occupied),
+                _ => queen_attacks(from, occupied),
+            };
+            for to in (attacks & target & !own).squares() {
+                if allowed(from, to) {
+                    moves.push(Move::new(from, to));
+                }
             }
         }
     }
-    return validate_moves(possible_moves);
-}
 
-pub fn get_moves(is_white: bool, start: (u8, u8), piece_type: PieceType) -> Vec<(u8, u8)> {
-    match piece_type {
-        PieceType::Pawn => get_pawn_moves(start, is_white),
-        _ => panic!("Not implemented yet"),
+    let forward: i8 = if color.is_white() { 1 } else { -1 };
+    for from in board.pieces_of(color, PieceType::Pawn).squares() {
+        if let Some(to) = from.offset(0, forward) {
+            if !occupied.contains(to) {
+                if target.contains(to) && allowed(from, to) {
+                    push_pawn_move(&mut moves, from, to, color);
+                }
+                if from.rank().relative_to(color) == Rank::TWO {
+                    let double = to.offset(0, forward).unwrap();
+                    if !occupied.contains(double) && target.contains(double) && allowed(from, double) {
+                        moves.push(Move::new(from, double));
+                    }
+                }
+            }
+        }
+        for to in (pawn_attacks(color, from) & enemy & target).squares() {
+            if allowed(from, to) {
+                push_pawn_move(&mut moves, from, to, color);
+            }
+        }
     }
+    generate_en_passant(board, king, color, &mut moves);
+    moves
 }
 
 #[cfg(test)]
@@ -52,127 +186,127 @@ mod tests {
 
     use super::*;
 
-    fn assert_move(is_white: bool, piece_type: PieceType, start: (u8, u8), end: (u8, u8)) {
-        let moves = get_moves(is_white, start, piece_type);
-        let found = moves
-            .iter()
-            .position(|(rank, file)| *rank == end.0 && *file == end.1);
-        assert!(found.is_some());
+    fn assert_move(color: Color, piece_type: PieceType, start: Square, end: Square) {
+        let moves = get_moves(color, start, piece_type);
+        assert!(moves.contains(&end));
+    }
+
+    fn assert_can_not_move(color: Color, piece_type: PieceType, start: Square, end: Square) {
+        let moves = get_moves(color, start, piece_type);
+        assert!(!moves.contains(&end));
     }
 
-    fn assert_can_not_move(is_white: bool, piece_type: PieceType, start: (u8, u8), end: (u8, u8)) {
-        let moves = get_moves(is_white, start, piece_type);
-        let found = moves
-            .iter()
-            .position(|(rank, file)| *rank == end.0 && *file == end.1);
-        assert!(found.is_none());
+    // moves off the board can't be expressed as a Square, so instead check nothing leaves the expected files
+    fn assert_stays_within_files(color: Color, piece_type: PieceType, start: Square, files: &[File]) {
+        let moves = get_moves(color, start, piece_type);
+        assert!(moves.iter().all(|square| files.contains(&square.file())));
     }
 
     #[test]
     fn white_pawn_can_move_forward_one() {
-        assert_move(true, PieceType::Pawn, (1, 0), (2, 0));
+        assert_move(Color::White, PieceType::Pawn, Square::A2, Square::A3);
     }
 
     #[test]
     fn white_pawn_can_initially_move_forward_two() {
-        assert_move(true, PieceType::Pawn, (1, 0), (3, 0));
+        assert_move(Color::White, PieceType::Pawn, Square::A2, Square::A4);
     }
 
     #[test]
     fn white_pawn_can_capture_left() {
-        assert_move(true, PieceType::Pawn, (4, 4), (5, 3));
+        assert_move(Color::White, PieceType::Pawn, Square::E5, Square::D6);
     }
 
     #[test]
     fn white_pawn_can_capture_right() {
-        assert_move(true, PieceType::Pawn, (4, 4), (5, 5));
+        assert_move(Color::White, PieceType::Pawn, Square::E5, Square::F6);
     }
 
     #[test]
     fn white_pawn_move_forward_one_blocked_by_edge_of_board() {
-        assert_can_not_move(true, PieceType::Pawn, (7, 7), (8, 7));
+        assert!(get_moves(Color::White, Square::H8, PieceType::Pawn).is_empty());
     }
 
     #[test]
     fn white_pawn_move_forward_two_blocked_by_edge_of_board() {
-        assert_can_not_move(true, PieceType::Pawn, (7, 7), (9, 7));
+        assert!(get_moves(Color::White, Square::H8, PieceType::Pawn).is_empty());
     }
 
-    // white_pawn_capture_left_blocked_by_edge_of_board is already impossible because file is an unsigned integer (thus can't be -1)
+    // white_pawn_capture_left_blocked_by_edge_of_board is already impossible because a Square can't be off the board
 
     #[test]
     fn white_pawn_capture_right_blocked_by_edge_of_board() {
-        assert_can_not_move(true, PieceType::Pawn, (2, 7), (3, 8));
+        assert_stays_within_files(Color::White, PieceType::Pawn, Square::H3, &[File::G, File::H]);
     }
 
     #[test]
     fn white_pawn_cant_move_backwards() {
-        assert_can_not_move(true, PieceType::Pawn, (1, 0), (0, 0));
+        assert_can_not_move(Color::White, PieceType::Pawn, Square::A2, Square::A1);
     }
 
     #[test]
     fn white_pawn_cant_capture_backwards() {
-        assert_can_not_move(true, PieceType::Pawn, (1, 0), (0, 1));
+        assert_can_not_move(Color::White, PieceType::Pawn, Square::A2, Square::B1);
     }
 
     #[test]
     fn black_pawn_can_initially_move_forward_two() {
-        assert_move(false, PieceType::Pawn, (6, 0), (4, 0));
+        assert_move(Color::Black, PieceType::Pawn, Square::A7, Square::A5);
     }
 
     #[test]
     fn black_pawn_can_move_forward_one() {
-        assert_move(false, PieceType::Pawn, (6, 0), (5, 0));
+        assert_move(Color::Black, PieceType::Pawn, Square::A7, Square::A6);
     }
 
     #[test]
     fn black_pawn_can_capture_left() {
-        assert_move(false, PieceType::Pawn, (5, 5), (4, 4));
+        assert_move(Color::Black, PieceType::Pawn, Square::F6, Square::E5);
     }
 
     #[test]
     fn black_pawn_can_capture_right() {
-        assert_move(false, PieceType::Pawn, (5, 5), (4, 6));
+        assert_move(Color::Black, PieceType::Pawn, Square::F6, Square::G5);
     }
 
-    // black_pawn_move_forward_one_blocked_by_edge_of_board is already impossible because rank is an unsigned integer (thus can't be -1)
+    // black_pawn_move_forward_one_blocked_by_edge_of_board is already impossible because a Square can't be off the board
 
-    // black_pawn_move_forward_two_blocked_by_edge_of_board is already impossible because rank is an unsigned integer (thus can't be -1)
+    // black_pawn_move_forward_two_blocked_by_edge_of_board is already impossible because a Square can't be off the board
 
-    // black_pawn_capture_left_blocked_by_edge_of_board is already impossible because file is an unsigned integer (thus can't be -1)
+    // black_pawn_capture_left_blocked_by_edge_of_board is already impossible because a Square can't be off the board
 
     #[test]
     fn black_pawn_capture_right_blocked_by_edge_of_board() {
-        assert_can_not_move(false, PieceType::Pawn, (6, 7), (5, 8));
+        assert_stays_within_files(Color::Black, PieceType::Pawn, Square::H7, &[File::G, File::H]);
     }
 
     #[test]
     fn black_pawn_cant_move_backwards() {
-        assert_can_not_move(false, PieceType::Pawn, (1, 0), (2, 0));
+        assert_can_not_move(Color::Black, PieceType::Pawn, Square::A2, Square::A3);
     }
 
     #[test]
     fn black_pawn_cant_capture_backwards() {
-        assert_can_not_move(false, PieceType::Pawn, (1, 0), (2, 1));
+        assert_can_not_move(Color::Black, PieceType::Pawn, Square::A2, Square::B3);
     }
 
     #[test]
     fn pawn_can_move_to_top_of_board() {
-        assert_move(true, PieceType::Pawn, (6, 0), (7, 0));
+        assert_move(Color::White, PieceType::Pawn, Square::A7, Square::A8);
     }
 
     #[test]
     fn pawn_can_move_to_bottom_of_board() {
-        assert_move(false, PieceType::Pawn, (1, 0), (0, 0));
+        assert_move(Color::Black, PieceType::Pawn, Square::A2, Square::A1);
     }
 
     #[test]
     fn pawn_can_capture_to_left_edge_of_board() {
-        assert_move(true, PieceType::Pawn, (1, 1), (2, 0));
+        assert_move(Color::White, PieceType::Pawn, Square::B2, Square::A3);
     }
 
     #[test]
     fn pawn_can_capture_to_right_edge_of_board() {
-        assert_move(true, PieceType::Pawn, (1, 6), (2, 7));
+        assert_move(Color::White, PieceType::Pawn, Square::G2, Square::H3);
     }
 }
@@ -1,4 +1,4 @@
-#[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub enum PieceType {
     Empty = 0,
     King = 1,
@@ -7,4 +7,18 @@ pub enum PieceType {
     Bishop = 4,
     Knight = 5,
     Pawn = 6
-}
\ No newline at end of file
+}
+
+impl PieceType {
+    // the letter used for the piece in algebraic notation (pawns have none)
+    pub fn symbol(self) -> Option<char> {
+        match self {
+            PieceType::King => Some('K'),
+            PieceType::Queen => Some('Q'),
+            PieceType::Rook => Some('R'),
+            PieceType::Bishop => Some('B'),
+            PieceType::Knight => Some('N'),
+            PieceType::Pawn | PieceType::Empty => None,
+        }
+    }
+}
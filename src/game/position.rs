@@ -1,85 +1,270 @@
+use wasm_bindgen::prelude::*;
+
+use super::attacks::*;
+use super::bitboard::*;
+use super::board::*;
+use super::color::*;
+use super::notation::*;
 use super::piece_type::*;
-//use super::notation::*;
+use super::square::*;
 use std::vec::*;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
-    pub rank: u8,
-    pub file: u8,
+    pub square: Square,
     pub piece_type: PieceType,
-    pub is_white: bool
+    pub color: Color
 }
 
-const INITIAL_POSITIONS: [Position; 32] = [
-    Position { rank: 0, file: 0, piece_type: PieceType::Rook, is_white: true },
-    Position { rank: 0, file: 1, piece_type: PieceType::Knight, is_white: true },
-    Position { rank: 0, file: 2, piece_type: PieceType::Bishop, is_white: true },
-    Position { rank: 0, file: 3, piece_type: PieceType::Queen, is_white: true },
-    Position { rank: 0, file: 4, piece_type: PieceType::King, is_white: true },
-    Position { rank: 0, file: 5, piece_type: PieceType::Bishop, is_white: true },
-    Position { rank: 0, file: 6, piece_type: PieceType::Knight, is_white: true },
-    Position { rank: 0, file: 7, piece_type: PieceType::Rook, is_white: true },
-
-    Position { rank: 1, file: 0, piece_type: PieceType::Pawn, is_white: true },
-    Position { rank: 1, file: 1, piece_type: PieceType::Pawn, is_white: true },
-    Position { rank: 1, file: 2, piece_type: PieceType::Pawn, is_white: true },
-    Position { rank: 1, file: 3, piece_type: PieceType::Pawn, is_white: true },
-    Position { rank: 1, file: 4, piece_type: PieceType::Pawn, is_white: true },
-    Position { rank: 1, file: 5, piece_type: PieceType::Pawn, is_white: true },
-    Position { rank: 1, file: 6, piece_type: PieceType::Pawn, is_white: true },
-    Position { rank: 1, file: 7, piece_type: PieceType::Pawn, is_white: true },
-
-    Position { rank: 6, file: 0, piece_type: PieceType::Pawn, is_white: false },
-    Position { rank: 6, file: 1, piece_type: PieceType::Pawn, is_white: false },
-    Position { rank: 6, file: 2, piece_type: PieceType::Pawn, is_white: false },
-    Position { rank: 6, file: 3, piece_type: PieceType::Pawn, is_white: false },
-    Position { rank: 6, file: 4, piece_type: PieceType::Pawn, is_white: false },
-    Position { rank: 6, file: 5, piece_type: PieceType::Pawn, is_white: false },
-    Position { rank: 6, file: 6, piece_type: PieceType::Pawn, is_white: false },
-    Position { rank: 6, file: 7, piece_type: PieceType::Pawn, is_white: false },
-
-    Position { rank: 7, file: 0, piece_type: PieceType::Rook, is_white: false },
-    Position { rank: 7, file: 1, piece_type: PieceType::Knight, is_white: false },
-    Position { rank: 7, file: 2, piece_type: PieceType::Bishop, is_white: false },
-    Position { rank: 7, file: 3, piece_type: PieceType::Queen, is_white: false },
-    Position { rank: 7, file: 4, piece_type: PieceType::King, is_white: false },
-    Position { rank: 7, file: 5, piece_type: PieceType::Bishop, is_white: false },
-    Position { rank: 7, file: 6, piece_type: PieceType::Knight, is_white: false },
-    Position { rank: 7, file: 7, piece_type: PieceType::Rook, is_white: true },
+pub const INITIAL_POSITIONS: [Position; 32] = [
+    Position { square: Square::A1, piece_type: PieceType::Rook, color: Color::White },
+    Position { square: Square::B1, piece_type: PieceType::Knight, color: Color::White },
+    Position { square: Square::C1, piece_type: PieceType::Bishop, color: Color::White },
+    Position { square: Square::D1, piece_type: PieceType::Queen, color: Color::White },
+    Position { square: Square::E1, piece_type: PieceType::King, color: Color::White },
+    Position { square: Square::F1, piece_type: PieceType::Bishop, color: Color::White },
+    Position { square: Square::G1, piece_type: PieceType::Knight, color: Color::White },
+    Position { square: Square::H1, piece_type: PieceType::Rook, color: Color::White },
+
+    Position { square: Square::A2, piece_type: PieceType::Pawn, color: Color::White },
+    Position { square: Square::B2, piece_type: PieceType::Pawn, color: Color::White },
+    Position { square: Square::C2, piece_type: PieceType::Pawn, color: Color::White },
+    Position { square: Square::D2, piece_type: PieceType::Pawn, color: Color::White },
+    Position { square: Square::E2, piece_type: PieceType::Pawn, color: Color::White },
+    Position { square: Square::F2, piece_type: PieceType::Pawn, color: Color::White },
+    Position { square: Square::G2, piece_type: PieceType::Pawn, color: Color::White },
+    Position { square: Square::H2, piece_type: PieceType::Pawn, color: Color::White },
+
+    Position { square: Square::A7, piece_type: PieceType::Pawn, color: Color::Black },
+    Position { square: Square::B7, piece_type: PieceType::Pawn, color: Color::Black },
+    Position { square: Square::C7, piece_type: PieceType::Pawn, color: Color::Black },
+    Position { square: Square::D7, piece_type: PieceType::Pawn, color: Color::Black },
+    Position { square: Square::E7, piece_type: PieceType::Pawn, color: Color::Black },
+    Position { square: Square::F7, piece_type: PieceType::Pawn, color: Color::Black },
+    Position { square: Square::G7, piece_type: PieceType::Pawn, color: Color::Black },
+    Position { square: Square::H7, piece_type: PieceType::Pawn, color: Color::Black },
+
+    Position { square: Square::A8, piece_type: PieceType::Rook, color: Color::Black },
+    Position { square: Square::B8, piece_type: PieceType::Knight, color: Color::Black },
+    Position { square: Square::C8, piece_type: PieceType::Bishop, color: Color::Black },
+    Position { square: Square::D8, piece_type: PieceType::Queen, color: Color::Black },
+    Position { square: Square::E8, piece_type: PieceType::King, color: Color::Black },
+    Position { square: Square::F8, piece_type: PieceType::Bishop, color: Color::Black },
+    Position { square: Square::G8, piece_type: PieceType::Knight, color: Color::Black },
+    Position { square: Square::H8, piece_type: PieceType::Rook, color: Color::Black },
 ];
 
-pub fn calculate_next_positions(prior_positions: Vec<Position>, next_move: &String) -> Vec<Position> {
-    println!("{}", next_move);
-    prior_positions
+// Given an array of moves, calculates an array of positions to be rendered. The moves are played from the
+// initial position until one can't be followed (e.g. the end of game marker).
+pub fn calculate_positions(moves: Vec<String>) -> Vec<Position> {
+    let mut board = Board::new();
+    for next_move in moves {
+        match decode(next_move).and_then(|notation| resolve(&board, &notation)) {
+            Ok(chess_move) => board.make_move(chess_move),
+            Err(_) => break,
+        }
+    }
+    board.to_positions()
 }
 
-// Given an array of moves, calculates an array of positions to be rendered
-pub fn calculate_positions(moves: Vec<String>) -> Vec<Position> {
-    let mut positions = moves.iter().fold(INITIAL_POSITIONS.to_vec(), calculate_next_positions);
-    positions.sort();
-    positions
+// a piece which can't leave the line between its king and the opponent's rook, bishop or queen (the pinner)
+// without exposing the king to check
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pin {
+    pinned: Square,
+    pinner: Square,
+    king: Square,
+}
+
+impl Pin {
+    pub fn pinned(&self) -> Square {
+        self.pinned
+    }
+
+    pub fn pinner(&self) -> Square {
+        self.pinner
+    }
+
+    pub fn king(&self) -> Square {
+        self.king
+    }
+}
+
+#[wasm_bindgen]
+impl Pin {
+    // square names, e.g. "e2"
+    #[wasm_bindgen(getter, js_name = pinned)]
+    pub fn pinned_name(&self) -> String {
+        self.pinned.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = pinner)]
+    pub fn pinner_name(&self) -> String {
+        self.pinner.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = king)]
+    pub fn king_name(&self) -> String {
+        self.king.to_string()
+    }
+}
+
+// every square the side's pieces attack, whether empty or occupied by either side
+pub fn attacked_squares(board: &Board, color: Color) -> Bitboard {
+    board.color(color).squares().fold(Bitboard::EMPTY, |attacked, square| {
+        attacked | piece_attacks(board.piece_type_at(square), color, square, board.occupied())
+    })
+}
+
+// how many of the side's pieces attack each square, indexed by Square::index
+pub fn attack_counts(board: &Board, color: Color) -> [u8; 64] {
+    let mut counts = [0; 64];
+    for square in board.color(color).squares() {
+        for attacked in piece_attacks(board.piece_type_at(square), color, square, board.occupied()).squares() {
+            counts[attacked.index() as usize] += 1;
+        }
+    }
+    counts
+}
+
+// the side's pieces pinned to its king
+pub fn pins(board: &Board, color: Color) -> Vec<Pin> {
+    let king = match board.king_square(color) {
+        Some(king) => king,
+        None => return vec![],
+    };
+    let them = !color;
+    let straight = board.pieces_of(them, PieceType::Rook) | board.pieces_of(them, PieceType::Queen);
+    let diagonal = board.pieces_of(them, PieceType::Bishop) | board.pieces_of(them, PieceType::Queen);
+    let snipers = (rook_attacks(king, Bitboard::EMPTY) & straight) | (bishop_attacks(king, Bitboard::EMPTY) & diagonal);
+    snipers
+        .squares()
+        .filter_map(|pinner| {
+            let blockers = between(king, pinner) & board.occupied();
+            match (blockers.count(), blockers.first()) {
+                (1, Some(pinned)) if board.color(color).contains(pinned) => Some(Pin { pinned, pinner, king }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// the squares of the pieces giving check to the side to move
+pub fn checkers(board: &Board) -> Vec<Square> {
+    board.checkers().squares().collect()
+}
+
+// is the square attacked by at least one of the side's pieces? For an occupied square this says whether
+// its piece would be recaptured by that side.
+pub fn is_defended(board: &Board, square: Square, by: Color) -> bool {
+    board.is_attacked(square, by)
+}
+
+// the side's pieces (other than the king) which the opponent attacks and which no piece of the side defends
+pub fn hanging_pieces(board: &Board, color: Color) -> Vec<Square> {
+    let attacked = attacked_squares(board, !color);
+    (board.color(color) & !board.pieces(PieceType::King) & attacked)
+        .squares()
+        .filter(|square| !is_defended(board, *square, color))
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
 
-    //use super::*;
+    use super::*;
+
+    #[test]
+    fn initial_positions_are_evenly_split_between_the_colours() {
+        for color in Color::all() {
+            let own = INITIAL_POSITIONS.iter().filter(|position| position.color == color);
+            assert_eq!(own.clone().count(), 16);
+            assert!(own.clone().all(|position| {
+                let rank = position.square.rank().relative_to(color);
+                rank == Rank::ONE || rank == Rank::TWO
+            }));
+        }
+    }
 
-    // #[test]
-    // fn a_single_move() {
-    //     let moves = vec!["e4".to_string()];
+    #[test]
+    fn a_single_move() {
+        let moves = vec!["e4".to_string()];
 
-    //     let mut expected = INITIAL_POSITIONS.to_vec();
-    //     let mut moving_piece = expected
-    //         .iter_mut()
-    //         .find(|position| (**position).rank == 1 && (**position).file == 4)
-    //         .unwrap();
-    //     moving_piece.rank = 3;
-    //     expected.sort();
+        let mut expected = INITIAL_POSITIONS.to_vec();
+        let moving_piece = expected.iter_mut().find(|position| position.square == Square::E2).unwrap();
+        moving_piece.square = Square::E4;
+        expected.sort();
 
-    //     let actual = calculate_positions(moves);
+        let actual = calculate_positions(moves);
 
-    //     assert_eq!(expected, actual);
+        assert_eq!(expected, actual);
+    }
 
-    // }
-}
\ No newline at end of file
+    #[test]
+    fn moves_are_followed_until_the_end_of_game() {
+        let moves = ["e4", "e5", "Nf3", "1-0", "Nc6"].iter().map(|m| m.to_string()).collect();
+        let actual = calculate_positions(moves);
+        assert!(actual.contains(&Position { square: Square::F3, piece_type: PieceType::Knight, color: Color::White }));
+        assert!(actual.contains(&Position { square: Square::B8, piece_type: PieceType::Knight, color: Color::Black }));
+    }
+
+    fn board(fen: &str) -> Board {
+        crate::game::fen::decode(fen).unwrap_or_else(|err| panic!("{}", err.reason))
+    }
+
+    #[test]
+    fn attack_counts_include_every_attacker() {
+        let counts = attack_counts(&Board::new(), Color::White);
+        // f3 is attacked by the e2 and g2 pawns and the g1 knight
+        assert_eq!(counts[Square::F3.index() as usize], 3);
+        // the e2 pawn is defended by the king, queen, bishop and knight
+        assert_eq!(counts[Square::E2.index() as usize], 4);
+        assert_eq!(counts[Square::E4.index() as usize], 0);
+        assert_eq!(attacked_squares(&Board::new(), Color::Black) & Bitboard::rank(Rank::SIX), Bitboard::rank(Rank::SIX));
+        assert!((attacked_squares(&Board::new(), Color::Black) & Bitboard::rank(Rank::FIVE)).is_empty());
+    }
+
+    #[test]
+    fn pinned_pieces_are_reported_with_their_pinner() {
+        let board = board("4k3/4r3/8/b7/8/8/3NB3/4K3 w - - 0 1");
+        let mut actual = pins(&board, Color::White);
+        actual.sort_by_key(|pin| pin.pinned());
+        assert_eq!(
+            actual,
+            vec![
+                Pin { pinned: Square::D2, pinner: Square::A5, king: Square::E1 },
+                Pin { pinned: Square::E2, pinner: Square::E7, king: Square::E1 },
+            ]
+        );
+        assert!(pins(&board, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn two_pieces_in_between_are_not_pinned() {
+        let board = board("4k3/4r3/8/8/4P3/8/4B3/4K3 w - - 0 1");
+        assert!(pins(&board, Color::White).is_empty());
+    }
+
+    #[test]
+    fn checkers_are_the_pieces_giving_check() {
+        let board = board("4k3/8/8/8/1b6/8/3N4/r3K3 w - - 0 1");
+        assert_eq!(checkers(&board), vec![Square::A1]);
+        let double = board_with_double_check();
+        assert_eq!(checkers(&double), vec![Square::F3, Square::E8]);
+    }
+
+    fn board_with_double_check() -> Board {
+        board("4r1k1/8/8/8/8/5n2/8/4K3 w - - 0 1")
+    }
+
+    #[test]
+    fn undefended_attacked_pieces_are_hanging() {
+        // the knight on e5 is attacked by the d6 pawn and defended by nothing; the b3 bishop is defended
+        let board = board("4k3/8/3p4/4N3/8/1B6/8/1R2K3 b - - 0 1");
+        assert!(!is_defended(&board, Square::E5, Color::White));
+        assert!(is_defended(&board, Square::B3, Color::White));
+        assert_eq!(hanging_pieces(&board, Color::White), vec![Square::E5]);
+        assert!(hanging_pieces(&board, Color::Black).is_empty());
+    }
+}
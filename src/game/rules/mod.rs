@@ -2,19 +2,22 @@ use std::vec::Vec;
 
 use crate::game::rules::rule::Rule;
 
-mod rule;
+pub mod rule;
 mod rules_1_2_who_s_go_is_it;
 mod rules_1_3_has_a_move_been_made;
 mod rules_1_4_cant_move_after_checkmate;
 mod rules_1_5_stalemate;
 mod rules_2_1_the_board;
+mod rules_3_10_legal_moves;
+pub mod rules_4_touch_move;
 
-pub fn build_rules() -> Vec<Box<Rule>> {
+pub fn build_rules() -> Vec<Box<dyn Rule>> {
     vec![
         Box::new(rules_1_2_who_s_go_is_it::Implementation::new()),
         Box::new(rules_1_3_has_a_move_been_made::Implementation::new()),
         Box::new(rules_1_4_cant_move_after_checkmate::Implementation::new()),
         Box::new(rules_1_5_stalemate::Implementation::new()),
-        Box::new(rules_2_1_the_board::Implementation::new())
+        Box::new(rules_2_1_the_board::Implementation::new()),
+        Box::new(rules_3_10_legal_moves::Implementation::new())
     ]
 }
\ No newline at end of file
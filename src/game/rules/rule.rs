@@ -2,6 +2,11 @@ use crate::game::moving::MoveError;
 use crate::game::game::Game;
 
 pub trait Rule {
+    // the article of the FIDE Laws of Chess the rule implements, e.g. "1.4"
+    fn article(&self) -> &'static str;
+
+    fn title(&self) -> &'static str;
+
     fn validate(&self, _game: &Game, _next_move: String) -> Result<(), MoveError> {
         Ok(())
     }
@@ -13,4 +13,12 @@ impl Implementation {
     }
 }
 
-impl Rule for Implementation {}
+impl Rule for Implementation {
+    fn article(&self) -> &'static str {
+        "1.2"
+    }
+
+    fn title(&self) -> &'static str {
+        "White moves first, then the players move alternately"
+    }
+}
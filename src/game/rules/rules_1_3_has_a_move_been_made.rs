@@ -12,4 +12,12 @@ impl Implementation {
     }
 }
 
-impl Rule for Implementation {}
+impl Rule for Implementation {
+    fn article(&self) -> &'static str {
+        "1.3"
+    }
+
+    fn title(&self) -> &'static str {
+        "A player has the move once the opponent’s move has been made"
+    }
+}
@@ -27,6 +27,14 @@ impl Implementation {
 }
 
 impl Rule for Implementation {
+    fn article(&self) -> &'static str {
+        "1.4"
+    }
+
+    fn title(&self) -> &'static str {
+        "No move can be made once a king has been checkmated"
+    }
+
     fn validate(&self, game: &Game, _: String) -> Result<(), MoveError> {
         match game.state {
             GameState::BlackCheckmate | GameState::WhiteCheckmate => Err(MoveError {
@@ -46,8 +54,8 @@ mod tests {
     #[test]
     fn cant_move_after_white_checkmate() {
         let game = Game {
-            moves: [].to_vec(),
             state: GameState::WhiteCheckmate,
+            ..Game::new()
         };
         let actual = (Implementation {}).validate(&game, "".to_string());
         assert_eq!(
@@ -59,8 +67,8 @@ mod tests {
     #[test]
     fn cant_move_after_black_checkmate() {
         let game = Game {
-            moves: [].to_vec(),
             state: GameState::BlackCheckmate,
+            ..Game::new()
         };
         let actual = (Implementation {}).validate(&game, "".to_string());
         assert_eq!(
@@ -72,8 +80,8 @@ mod tests {
     #[test]
     fn does_not_prevent_move_after_other_states() {
         let game = Game {
-            moves: [].to_vec(),
             state: GameState::WhiteResigned,
+            ..Game::new()
         };
         let actual = (Implementation {}).validate(&game, "".to_string());
         assert!(actual.is_ok());
@@ -17,6 +17,14 @@ impl Implementation {
 }
 
 impl Rule for Implementation {
+    fn article(&self) -> &'static str {
+        "1.5"
+    }
+
+    fn title(&self) -> &'static str {
+        "No move can be made once the game is drawn"
+    }
+
     fn validate(&self, game: &Game, _: String) -> Result<(), MoveError> {
         match game.state {
             GameState::Started | GameState::NotStarted => Ok(()),
@@ -36,8 +44,8 @@ mod tests {
     #[test]
     fn move_test() {
         let game = Game {
-            moves: [].to_vec(),
             state: GameState::Stalemate,
+            ..Game::new()
         };
         let actual = (Implementation {}).validate(&game, "".to_string());
         assert_eq!(
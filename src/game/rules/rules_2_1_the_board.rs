@@ -19,6 +19,14 @@ impl Implementation {
 }
 
 impl Rule for Implementation {
+    fn article(&self) -> &'static str {
+        "2.1"
+    }
+
+    fn title(&self) -> &'static str {
+        "Moves must stay within the confines of the chess board"
+    }
+
     fn validate(&self, _: &Game, next_move: String) -> Result<(), MoveError> {
         match decode(next_move) {
             Ok(_) => Ok(()),
@@ -38,8 +46,8 @@ mod tests {
     #[test]
     fn a_move_must_be_valid_within_the_confines_of_the_board() {
         let game = Game {
-            moves: [].to_vec(),
             state: GameState::Stalemate,
+            ..Game::new()
         };
         let illegal_move = "i9".to_string(); // pawn to i9
         let actual = (Implementation {}).validate(&game, illegal_move);
@@ -0,0 +1,67 @@
+/*
+3.10.1
+A move is legal when all the relevant requirements of Articles 3.1 – 3.9 have been fulfilled.
+
+3.10.2
+A move is illegal when it fails to meet the relevant requirements of Articles 3.1 – 3.9.
+*/
+use super::rule::Rule;
+use crate::game::game::Game;
+use crate::game::moving::MoveError;
+use crate::game::notation::{decode, resolve};
+
+pub struct Implementation {}
+
+impl Implementation {
+    pub fn new() -> Implementation {
+        Implementation {}
+    }
+}
+
+impl Rule for Implementation {
+    fn article(&self) -> &'static str {
+        "3.10"
+    }
+
+    fn title(&self) -> &'static str {
+        "The move must be legal in the current position"
+    }
+
+    fn validate(&self, game: &Game, next_move: String) -> Result<(), MoveError> {
+        // notation which can't be read is reported by article 2.1
+        let notation = match decode(next_move) {
+            Ok(notation) if notation.end_of_game.is_none() => notation,
+            _ => return Ok(()),
+        };
+        let board = game.board()?;
+        match resolve(&board, &notation) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(MoveError { reason: err.reason }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn a_legal_move_is_accepted() {
+        assert!((Implementation {}).validate(&Game::new(), "Nf3".to_string()).is_ok());
+    }
+
+    #[test]
+    fn an_illegal_move_is_rejected() {
+        let actual = (Implementation {}).validate(&Game::new(), "e5".to_string());
+        assert_eq!(actual.err().unwrap().reason, "Illegal move: e5");
+    }
+
+    #[test]
+    fn legality_follows_the_moves_made() {
+        let game = Game::new().make_move("e4".to_string()).unwrap();
+        assert!((Implementation {}).validate(&game, "e5".to_string()).is_ok());
+        let actual = (Implementation {}).validate(&game, "e3".to_string());
+        assert_eq!(actual.err().unwrap().reason, "Illegal move: e3");
+    }
+}
@@ -0,0 +1,352 @@
+/*
+4.2.1
+Only the player having the move may adjust one or more pieces on their squares, provided that he first
+expresses his intention (for example by saying “j’adoube” or “I adjust”).
+
+4.3
+Except as provided in Article 4.2, if the player having the move touches on the chessboard, with the
+intention of moving or capturing:
+
+4.3.1
+one or more of his own pieces, he must move the first piece touched that can be moved
+
+4.3.2
+one or more of his opponent’s pieces, he must capture the first piece touched that can be captured
+
+4.3.3
+one or more pieces of each colour, he must capture the first touched opponent’s piece with his first
+touched piece or, if this is illegal, move or capture the first piece touched that can be moved or captured.
+
+4.5
+If none of the pieces touched in accordance with Article 4.3 can be moved or captured, the player may make
+any legal move.
+*/
+
+// Touch-move is optional (it only makes sense when simulating over-the-board play) so, unlike the other
+// rules, it is not part of build_rules(). Instead it is a layer wrapped around a Game which records what the
+// player having the move touches and holds them to it when the move is made.
+
+use crate::game::board::Board;
+use crate::game::chess_move::Move;
+use crate::game::game::Game;
+use crate::game::moving::MoveError;
+use crate::game::notation::{decode, resolve};
+use crate::game::piece_move::generate_moves;
+use crate::game::position::Position;
+use crate::game::square::Square;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TouchEvent {
+    Touched(Position),
+    Adjusted(Position),
+    Moved(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct TouchMove {
+    pub game: Game,
+    pub events: Vec<TouchEvent>,
+    // pieces touched since the last move which can be moved (own) or captured (opponent's), in touch order
+    obligations: Vec<Position>,
+}
+
+fn describe(position: &Position) -> String {
+    format!(
+        "{} on {}",
+        format!("{:?}", position.piece_type).to_lowercase(),
+        position.square
+    )
+}
+
+// does the legal move capture the piece on this square? an en passant capture takes the pawn beside it
+fn captures(board: &Board, chess_move: &Move, square: Square) -> bool {
+    if board.is_en_passant(chess_move) {
+        chess_move.to.file() == square.file() && chess_move.from.rank() == square.rank()
+    } else {
+        chess_move.to == square && board.is_capture(chess_move)
+    }
+}
+
+// only legal moves count: a pinned piece can't be moved, so touching it obliges nothing
+fn can_move(board: &Board, square: Square) -> bool {
+    generate_moves(board).iter().any(|chess_move| chess_move.from == square)
+}
+
+fn can_capture(board: &Board, by: Option<Square>, square: Square) -> bool {
+    generate_moves(board)
+        .iter()
+        .any(|chess_move| by.is_none_or(|by| chess_move.from == by) && captures(board, chess_move, square))
+}
+
+impl TouchMove {
+    pub fn new(game: Game) -> TouchMove {
+        TouchMove {
+            game,
+            events: vec![],
+            obligations: vec![],
+        }
+    }
+
+    fn piece_at(&self, positions: &[Position], square: Square) -> Result<Position, MoveError> {
+        match positions.iter().find(|p| p.square == square) {
+            Some(position) => Ok(position.clone()),
+            None => Err(MoveError {
+                reason: format!("There is no piece on {}", square),
+            }),
+        }
+    }
+
+    fn with_event(&self, event: TouchEvent, obligation: Option<Position>) -> TouchMove {
+        let mut next = self.clone();
+        next.events.push(event);
+        if let Some(position) = obligation {
+            next.obligations.push(position);
+        }
+        next
+    }
+
+    // the player having the move touches a piece with the intention of moving or capturing it
+    pub fn touch(&self, square: Square) -> Result<TouchMove, MoveError> {
+        let board = self.game.board()?;
+        let touched = self.piece_at(&board.to_positions(), square)?;
+        let is_own = touched.color == board.side_to_move();
+        let already_obliged = self
+            .obligations
+            .iter()
+            .any(|p| p.color == touched.color);
+        let obliges = !already_obliged
+            && if is_own {
+                can_move(&board, touched.square)
+            } else {
+                can_capture(&board, None, touched.square)
+            };
+        let obligation = if obliges { Some(touched.clone()) } else { None };
+        Ok(self.with_event(TouchEvent::Touched(touched), obligation))
+    }
+
+    // the player having the move adjusts a piece on its square, having first said "j'adoube"
+    pub fn adjust(&self, square: Square) -> Result<TouchMove, MoveError> {
+        let positions = self.game.board()?.to_positions();
+        let adjusted = self.piece_at(&positions, square)?;
+        Ok(self.with_event(TouchEvent::Adjusted(adjusted), None))
+    }
+
+    fn check_obligations(&self, board: &Board, chess_move: &Move) -> Result<(), MoveError> {
+        let color_to_move = board.side_to_move();
+        let own = self.obligations.iter().find(|p| p.color == color_to_move);
+        let opponent = self.obligations.iter().find(|p| p.color != color_to_move);
+        let required = match (own, opponent) {
+            (Some(piece), Some(target)) if can_capture(board, Some(piece.square), target.square) => {
+                if chess_move.from == piece.square && captures(board, chess_move, target.square) {
+                    return Ok(());
+                }
+                format!("The touched {} must be captured by the touched {}", describe(target), describe(piece))
+            }
+            _ => match self.obligations.first() {
+                Some(piece) if piece.color == color_to_move => {
+                    if chess_move.from == piece.square {
+                        return Ok(());
+                    }
+                    format!("The touched {} must be moved", describe(piece))
+                }
+                Some(target) => {
+                    if captures(board, chess_move, target.square) {
+                        return Ok(());
+                    }
+                    format!("The touched {} must be captured", describe(target))
+                }
+                None => return Ok(()),
+            },
+        };
+        Err(MoveError { reason: required })
+    }
+
+    pub fn make_move(&self, next_move: String) -> Result<TouchMove, MoveError> {
+        if !self.obligations.is_empty() {
+            let board = self.game.board()?;
+            let notation = decode(next_move.clone()).map_err(|err| MoveError { reason: err.reason })?;
+            // a move which can't be made is left to the game to reject
+            if let Ok(chess_move) = resolve(&board, &notation) {
+                self.check_obligations(&board, &chess_move)?;
+            }
+        }
+        let game = self.game.make_move(next_move.clone())?;
+        let mut events = self.events.clone();
+        events.push(TouchEvent::Moved(next_move));
+        Ok(TouchMove {
+            game,
+            events,
+            obligations: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::color::Color;
+    use crate::game::fen;
+    use crate::game::piece_type::PieceType;
+
+    fn piece(square: Square, piece_type: PieceType, color: Color) -> Position {
+        Position { square, piece_type, color }
+    }
+
+    fn obliged(obligations: Vec<Position>) -> TouchMove {
+        TouchMove {
+            game: Game::new(),
+            events: vec![],
+            obligations,
+        }
+    }
+
+    fn check(touch_move: &TouchMove, board: &Board, next_move: &str) -> Result<(), MoveError> {
+        let notation = decode(next_move.to_string()).unwrap_or_else(|err| panic!("{}", err.reason));
+        touch_move.check_obligations(board, &resolve(board, &notation).unwrap())
+    }
+
+    fn played(moves: &[&str]) -> TouchMove {
+        let game = moves.iter().try_fold(Game::new(), |game, next_move| game.make_move(next_move.to_string()));
+        TouchMove::new(game.unwrap())
+    }
+
+    #[test]
+    fn touching_an_own_piece_which_can_move_obliges_moving_it() {
+        let touched = TouchMove::new(Game::new()).touch(Square::G1).unwrap();
+        assert_eq!(
+            touched.make_move("e4".to_string()).err().unwrap().reason,
+            "The touched knight on g1 must be moved"
+        );
+        assert!(touched.make_move("Nf3".to_string()).is_ok());
+    }
+
+    #[test]
+    fn the_touched_piece_must_be_the_one_which_moves() {
+        let touched = TouchMove::new(Game::new()).touch(Square::B1).unwrap();
+        assert!(touched.make_move("Nf3".to_string()).is_err());
+        assert!(touched.make_move("Nc3".to_string()).is_ok());
+    }
+
+    #[test]
+    fn touching_an_own_piece_which_cannot_move_does_not_oblige() {
+        let touched = TouchMove::new(Game::new()).touch(Square::A1).unwrap();
+        assert!(touched.make_move("e4".to_string()).is_ok());
+    }
+
+    #[test]
+    fn the_first_touched_piece_which_can_move_must_be_moved() {
+        let touched = TouchMove::new(Game::new())
+            .touch(Square::A1)
+            .and_then(|t| t.touch(Square::D2))
+            .and_then(|t| t.touch(Square::G1))
+            .unwrap();
+        assert_eq!(
+            touched.make_move("Nf3".to_string()).err().unwrap().reason,
+            "The touched pawn on d2 must be moved"
+        );
+        assert!(touched.make_move("d4".to_string()).is_ok());
+    }
+
+    #[test]
+    fn touching_an_empty_square_fails() {
+        let actual = TouchMove::new(Game::new()).touch(Square::E4);
+        assert_eq!(actual.err().unwrap().reason, "There is no piece on e4");
+    }
+
+    #[test]
+    fn adjusting_a_piece_does_not_oblige() {
+        let adjusted = TouchMove::new(Game::new()).adjust(Square::G1).unwrap();
+        assert_eq!(adjusted.events, vec![TouchEvent::Adjusted(piece(Square::G1, PieceType::Knight, Color::White))]);
+        assert!(adjusted.make_move("e4".to_string()).is_ok());
+    }
+
+    #[test]
+    fn touching_an_opponents_piece_which_cannot_be_captured_does_not_oblige() {
+        let touched = TouchMove::new(Game::new()).touch(Square::E7).unwrap();
+        assert!(touched.make_move("e4".to_string()).is_ok());
+    }
+
+    #[test]
+    fn a_move_releases_the_obligation_and_is_recorded() {
+        let moved = TouchMove::new(Game::new())
+            .touch(Square::E2)
+            .and_then(|t| t.make_move("e4".to_string()))
+            .unwrap();
+        assert!(moved.make_move("a5".to_string()).is_ok());
+        assert_eq!(
+            moved.events,
+            vec![
+                TouchEvent::Touched(piece(Square::E2, PieceType::Pawn, Color::White)),
+                TouchEvent::Moved("e4".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn touching_an_opponents_piece_obliges_capturing_it() {
+        let board = fen::decode("4k3/8/8/3p4/4P3/2N5/8/4K3 w - - 0 1").unwrap();
+        let touch_move = obliged(vec![piece(Square::D5, PieceType::Pawn, Color::Black)]);
+        assert_eq!(
+            check(&touch_move, &board, "e5").err().unwrap().reason,
+            "The touched pawn on d5 must be captured"
+        );
+        assert!(check(&touch_move, &board, "exd5").is_ok());
+        assert!(check(&touch_move, &board, "Nxd5").is_ok());
+    }
+
+    #[test]
+    fn touching_pieces_of_each_colour_obliges_capturing_with_the_touched_piece() {
+        let board = fen::decode("4k3/8/8/3p4/4P3/2N5/8/4K3 w - - 0 1").unwrap();
+        let touch_move = obliged(vec![
+            piece(Square::D5, PieceType::Pawn, Color::Black),
+            piece(Square::C3, PieceType::Knight, Color::White),
+        ]);
+        assert_eq!(
+            check(&touch_move, &board, "exd5").err().unwrap().reason,
+            "The touched pawn on d5 must be captured by the touched knight on c3"
+        );
+        assert!(check(&touch_move, &board, "Nxd5").is_ok());
+    }
+
+    #[test]
+    fn when_the_touched_piece_cannot_capture_the_first_touched_piece_is_binding() {
+        let board = fen::decode("4k3/8/8/3p4/4P3/8/8/4K1N1 w - - 0 1").unwrap();
+        let touch_move = obliged(vec![
+            piece(Square::D5, PieceType::Pawn, Color::Black),
+            piece(Square::G1, PieceType::Knight, Color::White),
+        ]);
+        assert!(check(&touch_move, &board, "Nf3").is_err());
+        assert!(check(&touch_move, &board, "exd5").is_ok());
+    }
+
+    #[test]
+    fn touching_a_pinned_piece_does_not_oblige() {
+        let touch_move = played(&["e4", "e5", "Nc3", "Bb4", "d3", "a6"]);
+        let touched = touch_move.touch(Square::C3).unwrap();
+        assert!(touched.obligations.is_empty());
+        assert!(touched.make_move("a3".to_string()).is_ok());
+        // nor does touching an opponent's piece which could only be taken by a pinned piece
+        let touched = touch_move.touch(Square::B4).unwrap();
+        assert!(touched.obligations.is_empty());
+        let pinned = played(&["e4", "e5", "Nc3", "Bb4", "d3", "d5"]).touch(Square::D5).unwrap();
+        assert_eq!(pinned.make_move("a3".to_string()).err().unwrap().reason, "The touched pawn on d5 must be captured");
+        assert!(pinned.make_move("exd5".to_string()).is_ok());
+    }
+
+    #[test]
+    fn castling_and_en_passant_count_as_moving_and_capturing() {
+        let touch_move = played(&["e4", "a6", "Nf3", "a5", "Bc4", "a4", "b4"]);
+        let touched = touch_move.touch(Square::A4).unwrap();
+        assert_eq!(touched.make_move("Nc6".to_string()).err().unwrap().reason, "The touched pawn on a4 must be moved");
+        // the pawn on b4 can only be taken en passant
+        let touched = touch_move.touch(Square::B4).unwrap();
+        assert_eq!(touched.obligations.len(), 1);
+        assert!(touched.make_move("a3".to_string()).is_err());
+        assert!(touched.make_move("axb3".to_string()).is_ok());
+        let board = fen::decode("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let touch_move = obliged(vec![piece(Square::E1, PieceType::King, Color::White)]);
+        assert!(check(&touch_move, &board, "O-O").is_ok());
+        assert!(check(&touch_move, &board, "Rf1").is_err());
+    }
+}
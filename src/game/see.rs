@@ -0,0 +1,151 @@
+// Static exchange evaluation works out the material won or lost on a square when both sides keep capturing
+// there with their least valuable attacker, either side being free to stop when continuing would lose
+// material. Sliding pieces lined up behind an attacker (x-rays) join in once the piece in front has
+// captured. Pins and checks are ignored, which is the usual trade of accuracy for speed.
+
+use super::attacks::*;
+use super::bitboard::*;
+use super::board::*;
+use super::chess_move::*;
+use super::piece_type::*;
+use super::square::*;
+
+// in centipawns; the king is worth more than everything else put together so it's never traded
+pub fn exchange_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight | PieceType::Bishop => 300,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 20000,
+        PieceType::Empty => 0,
+    }
+}
+
+const LEAST_VALUABLE_FIRST: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+// the net material gained by the side making the move (which needn't be a capture) once the exchange on
+// its destination is over
+pub fn see(board: &Board, chess_move: &Move) -> i32 {
+    let to = chess_move.to;
+    let (mover, color) = match board.piece_at(chess_move.from) {
+        Some(piece) => piece,
+        None => return 0,
+    };
+    let mut occupied = board.occupied().without(chess_move.from);
+    let mut gains = [0; 32];
+    gains[0] = exchange_value(board.captured_piece_type(chess_move).unwrap_or(PieceType::Empty));
+    if board.is_en_passant(chess_move) {
+        occupied = occupied.without(to.offset(0, if color.is_white() { -1 } else { 1 }).unwrap());
+    }
+    // the value of the piece standing on the square, which the next capture wins
+    let mut at_risk = match chess_move.promotion {
+        Some(promotion) => {
+            gains[0] += exchange_value(promotion) - exchange_value(PieceType::Pawn);
+            exchange_value(promotion)
+        }
+        None => exchange_value(mover),
+    };
+    let diagonal = board.pieces(PieceType::Bishop) | board.pieces(PieceType::Queen);
+    let straight = board.pieces(PieceType::Rook) | board.pieces(PieceType::Queen);
+    let last_rank = Bitboard::rank(Rank::ONE) | Bitboard::rank(Rank::EIGHT);
+    let mut side = !color;
+    let mut depth = 0;
+    loop {
+        // recomputing the sliders through the remaining pieces uncovers any x-rays
+        let attackers = (board.attackers_to(to, occupied)
+            | (bishop_attacks(to, occupied) & diagonal)
+            | (rook_attacks(to, occupied) & straight))
+            & occupied;
+        let own = attackers & board.color(side);
+        let (piece_type, from) = match LEAST_VALUABLE_FIRST
+            .iter()
+            .find_map(|piece_type| (own & board.pieces(*piece_type)).first().map(|square| (*piece_type, square)))
+        {
+            Some(attacker) => attacker,
+            None => break,
+        };
+        // the king can't capture onto a square the opponent still attacks
+        if piece_type == PieceType::King && (attackers & board.color(!side)).any() {
+            break;
+        }
+        depth += 1;
+        gains[depth] = at_risk - gains[depth - 1];
+        at_risk = exchange_value(piece_type);
+        if piece_type == PieceType::Pawn && last_rank.contains(to) {
+            gains[depth] += exchange_value(PieceType::Queen) - exchange_value(PieceType::Pawn);
+            at_risk = exchange_value(PieceType::Queen);
+        }
+        occupied = occupied.without(from);
+        side = !side;
+    }
+    // each side only continues the exchange while it's worth it
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+    gains[0]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::fen::decode;
+    use crate::game::notation;
+
+    fn assert_see(fen: &str, san: &str, expected: i32) {
+        let board = decode(fen).unwrap_or_else(|err| panic!("{}", err.reason));
+        let decoded = notation::decode(san.to_string()).unwrap_or_else(|err| panic!("{}", err.reason));
+        let chess_move = notation::resolve(&board, &decoded).unwrap_or_else(|err| panic!("{}", err.reason));
+        assert_eq!(see(&board, &chess_move), expected, "{} in {}", san, fen);
+    }
+
+    #[test]
+    fn capturing_an_undefended_piece_wins_it() {
+        assert_see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "Rxe5", 100);
+    }
+
+    #[test]
+    fn capturing_a_defended_pawn_with_a_knight_loses_material() {
+        assert_see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "Nxe5", -200);
+    }
+
+    #[test]
+    fn x_ray_attackers_join_the_exchange() {
+        // the second rook backs up the first, so the pawn is won
+        assert_see("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "Rxe5", 100);
+        // without the second rook the defended pawn costs the rook
+        assert_see("4r1k1/8/8/4p3/8/8/8/4R1K1 w - - 0 1", "Rxe5", -400);
+    }
+
+    #[test]
+    fn the_exchange_stops_when_continuing_would_lose() {
+        // after Bxe5 black doesn't recapture with the queen because the rook would take it
+        assert_see("4q1k1/8/8/4n3/8/2B5/8/4R1K1 w - - 0 1", "Bxe5", 300);
+    }
+
+    #[test]
+    fn a_quiet_move_onto_an_attacked_square_loses_the_piece() {
+        assert_see("4k3/8/8/2p5/8/8/8/3QK3 w - - 0 1", "Qd4", -900);
+        assert_see("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", "Qd4", 0);
+    }
+
+    #[test]
+    fn the_king_only_recaptures_undefended_pieces() {
+        assert_see("8/8/8/8/8/8/2k1p3/3RK3 b - - 0 1", "exd1=Q", 500 + 800);
+        assert_see("4k3/8/8/8/8/8/4p3/3RK3 b - - 0 1", "exd1=Q", 500 + 800 - 900);
+    }
+
+    #[test]
+    fn en_passant_wins_the_pawn() {
+        assert_see("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 2", "dxe6", 100);
+    }
+}
@@ -0,0 +1,328 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::color::Color;
+use super::notation::NotationError;
+
+// a file (column) of the board, a..h
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct File(u8);
+
+// a rank (row) of the board, 1..8
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rank(u8);
+
+// one of the 64 squares of the board, numbered from a1 (0) along each rank to h8 (63)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8);
+
+impl File {
+    pub const A: File = File(0);
+    pub const B: File = File(1);
+    pub const C: File = File(2);
+    pub const D: File = File(3);
+    pub const E: File = File(4);
+    pub const F: File = File(5);
+    pub const G: File = File(6);
+    pub const H: File = File(7);
+
+    pub fn new(index: u8) -> Option<File> {
+        if index < 8 {
+            Some(File(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<File> {
+        match c {
+            'a'..='h' => Some(File(c as u8 - b'a')),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        (b'a' + self.0) as char
+    }
+
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn offset(self, delta: i8) -> Option<File> {
+        let index = self.0 as i8 + delta;
+        if (0..8).contains(&index) {
+            Some(File(index as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn all() -> impl Iterator<Item = File> {
+        (0..8).map(File)
+    }
+}
+
+impl Rank {
+    pub const ONE: Rank = Rank(0);
+    pub const TWO: Rank = Rank(1);
+    pub const THREE: Rank = Rank(2);
+    pub const FOUR: Rank = Rank(3);
+    pub const FIVE: Rank = Rank(4);
+    pub const SIX: Rank = Rank(5);
+    pub const SEVEN: Rank = Rank(6);
+    pub const EIGHT: Rank = Rank(7);
+
+    pub fn new(index: u8) -> Option<Rank> {
+        if index < 8 {
+            Some(Rank(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Rank> {
+        match c {
+            '1'..='8' => Some(Rank(c as u8 - b'1')),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        (b'1' + self.0) as char
+    }
+
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn offset(self, delta: i8) -> Option<Rank> {
+        let index = self.0 as i8 + delta;
+        if (0..8).contains(&index) {
+            Some(Rank(index as u8))
+        } else {
+            None
+        }
+    }
+
+    // the rank as seen from the given side, so that relative rank ONE is the side's back rank
+    pub fn relative_to(self, color: Color) -> Rank {
+        match color {
+            Color::White => self,
+            Color::Black => Rank(7 - self.0),
+        }
+    }
+
+    pub fn all() -> impl Iterator<Item = Rank> {
+        (0..8).map(Rank)
+    }
+}
+
+impl Square {
+    pub const A1: Square = Square(0);
+    pub const B1: Square = Square(1);
+    pub const C1: Square = Square(2);
+    pub const D1: Square = Square(3);
+    pub const E1: Square = Square(4);
+    pub const F1: Square = Square(5);
+    pub const G1: Square = Square(6);
+    pub const H1: Square = Square(7);
+    pub const A2: Square = Square(8);
+    pub const B2: Square = Square(9);
+    pub const C2: Square = Square(10);
+    pub const D2: Square = Square(11);
+    pub const E2: Square = Square(12);
+    pub const F2: Square = Square(13);
+    pub const G2: Square = Square(14);
+    pub const H2: Square = Square(15);
+    pub const A3: Square = Square(16);
+    pub const B3: Square = Square(17);
+    pub const C3: Square = Square(18);
+    pub const D3: Square = Square(19);
+    pub const E3: Square = Square(20);
+    pub const F3: Square = Square(21);
+    pub const G3: Square = Square(22);
+    pub const H3: Square = Square(23);
+    pub const A4: Square = Square(24);
+    pub const B4: Square = Square(25);
+    pub const C4: Square = Square(26);
+    pub const D4: Square = Square(27);
+    pub const E4: Square = Square(28);
+    pub const F4: Square = Square(29);
+    pub const G4: Square = Square(30);
+    pub const H4: Square = Square(31);
+    pub const A5: Square = Square(32);
+    pub const B5: Square = Square(33);
+    pub const C5: Square = Square(34);
+    pub const D5: Square = Square(35);
+    pub const E5: Square = Square(36);
+    pub const F5: Square = Square(37);
+    pub const G5: Square = Square(38);
+    pub const H5: Square = Square(39);
+    pub const A6: Square = Square(40);
+    pub const B6: Square = Square(41);
+    pub const C6: Square = Square(42);
+    pub const D6: Square = Square(43);
+    pub const E6: Square = Square(44);
+    pub const F6: Square = Square(45);
+    pub const G6: Square = Square(46);
+    pub const H6: Square = Square(47);
+    pub const A7: Square = Square(48);
+    pub const B7: Square = Square(49);
+    pub const C7: Square = Square(50);
+    pub const D7: Square = Square(51);
+    pub const E7: Square = Square(52);
+    pub const F7: Square = Square(53);
+    pub const G7: Square = Square(54);
+    pub const H7: Square = Square(55);
+    pub const A8: Square = Square(56);
+    pub const B8: Square = Square(57);
+    pub const C8: Square = Square(58);
+    pub const D8: Square = Square(59);
+    pub const E8: Square = Square(60);
+    pub const F8: Square = Square(61);
+    pub const G8: Square = Square(62);
+    pub const H8: Square = Square(63);
+
+    pub const fn new(file: File, rank: Rank) -> Square {
+        Square(rank.0 * 8 + file.0)
+    }
+
+    pub fn from_index(index: u8) -> Option<Square> {
+        if index < 64 {
+            Some(Square(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn file(self) -> File {
+        File(self.0 % 8)
+    }
+
+    pub fn rank(self) -> Rank {
+        Rank(self.0 / 8)
+    }
+
+    // a1 is a dark square
+    pub fn color(self) -> Color {
+        if (self.file().0 + self.rank().0).is_multiple_of(2) {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    // the square the given number of files and ranks away, if it is on the board
+    pub fn offset(self, file_delta: i8, rank_delta: i8) -> Option<Square> {
+        Some(Square::new(self.file().offset(file_delta)?, self.rank().offset(rank_delta)?))
+    }
+
+    // the same square as seen from the other side of the board (a1 <-> a8)
+    pub fn flip(self) -> Square {
+        Square(self.0 ^ 56)
+    }
+
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+}
+
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.file(), self.rank())
+    }
+}
+
+impl FromStr for Square {
+    type Err = NotationError;
+
+    fn from_str(name: &str) -> Result<Square, NotationError> {
+        let mut chars = name.chars();
+        match (chars.next().and_then(File::from_char), chars.next().and_then(Rank::from_char), chars.next()) {
+            (Some(file), Some(rank), None) => Ok(Square::new(file, rank)),
+            _ => Err(NotationError {
+                reason: format!("Invalid square: {}", name),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn squares_are_named_by_file_and_rank() {
+        assert_eq!(Square::A1.to_string(), "a1");
+        assert_eq!(Square::E4.to_string(), "e4");
+        assert_eq!(Square::H8.to_string(), "h8");
+    }
+
+    #[test]
+    fn squares_can_be_parsed_from_their_names() {
+        for square in Square::all() {
+            assert_eq!(square.to_string().parse::<Square>().ok(), Some(square));
+        }
+    }
+
+    #[test]
+    fn invalid_square_names_fail_to_parse() {
+        for name in ["", "e", "i4", "e9", "e0", "E4", "e44"].iter() {
+            assert_eq!(
+                name.parse::<Square>().err().unwrap().reason,
+                format!("Invalid square: {}", name)
+            );
+        }
+    }
+
+    #[test]
+    fn square_colours_alternate_with_a_dark_a1_corner() {
+        assert_eq!(Square::A1.color(), Color::Black);
+        assert_eq!(Square::H1.color(), Color::White);
+        assert_eq!(Square::D1.color(), Color::White);
+        assert_eq!(Square::E1.color(), Color::Black);
+        assert_eq!(Square::H8.color(), Color::Black);
+    }
+
+    #[test]
+    fn offsets_stay_on_the_board() {
+        assert_eq!(Square::E4.offset(1, 2), Some(Square::F6));
+        assert_eq!(Square::H4.offset(1, 0), None);
+        assert_eq!(Square::A1.offset(0, -1), None);
+    }
+
+    #[test]
+    fn iteration_covers_the_board_in_order() {
+        let squares: Vec<Square> = Square::all().collect();
+        assert_eq!(squares.len(), 64);
+        assert_eq!(squares[0], Square::A1);
+        assert_eq!(squares[9], Square::B2);
+        assert_eq!(squares[63], Square::H8);
+        assert_eq!(File::all().count(), 8);
+        assert_eq!(Rank::all().map(|rank| rank.to_char()).collect::<String>(), "12345678");
+    }
+
+    #[test]
+    fn ranks_can_be_seen_from_either_side() {
+        assert_eq!(Rank::TWO.relative_to(Color::White), Rank::TWO);
+        assert_eq!(Rank::TWO.relative_to(Color::Black), Rank::SEVEN);
+    }
+}
@@ -0,0 +1,149 @@
+// Taking moves back. A game keeps the board after each of its moves, so dropping the last of them restores
+// everything about the position before: castling rights, en passant, the halfmove clock and the positions
+// counted for repetition. Moves taken back are kept, most recent last, until they're replayed or a different
+// move is made; each keeps its record, with any timing and annotations, and is replayed as it was.
+//
+// In a game between two players a takeback needs the opponent's agreement: one player asks, and the other
+// accepts or declines. Asking takes back the requester's last move, along with the opponent's reply if there's
+// been one.
+
+use super::color::Color;
+use super::game::*;
+use super::moving::*;
+
+fn no_takeback(reason: &str) -> MoveError {
+    MoveError { reason: reason.to_string() }
+}
+
+// the game with its last plies taken back, ready to be replayed
+pub fn undo_internal(game: &Game, plies: usize) -> Result<Game, MoveError> {
+    if game.history.len() < plies {
+        return Err(no_takeback("Not enough moves to take back"));
+    }
+    let mut history = game.history.clone();
+    let mut undone = game.undone.clone();
+    for _ in 0..plies {
+        let (before, _) = history.pop().unwrap();
+        undone.push(history);
+        history = before;
+    }
+    Ok(Game { state: game.state, history, undone, takeback: None })
+}
+
+pub fn redo_internal(game: &Game) -> Result<Game, MoveError> {
+    let mut undone = game.undone.clone();
+    let history = undone.pop().ok_or_else(|| no_takeback("No moves to replay"))?;
+    Ok(Game { state: game.state, history, undone, takeback: None })
+}
+
+// how many plies the player's takeback would undo: their last move, and the reply to it if it's their turn
+fn takeback_plies(game: &Game, by: Color) -> Result<usize, MoveError> {
+    let plies = if game.board()?.side_to_move() == by { 2 } else { 1 };
+    if game.history.len() < plies {
+        return Err(no_takeback("No move to take back"));
+    }
+    Ok(plies)
+}
+
+pub fn request_takeback_internal(game: &Game, by: Color) -> Result<Game, MoveError> {
+    if game.takeback.is_some() {
+        return Err(no_takeback("A takeback has already been asked for"));
+    }
+    takeback_plies(game, by)?;
+    Ok(Game { takeback: Some(by), ..game.clone() })
+}
+
+// the opponent's answer to the takeback asked for
+pub fn answer_takeback_internal(game: &Game, by: Color, accept: bool) -> Result<Game, MoveError> {
+    match game.takeback {
+        None => Err(no_takeback("No takeback has been asked for")),
+        Some(requester) if requester == by => Err(no_takeback("Only the opponent can answer a takeback")),
+        Some(requester) if accept => undo_internal(game, takeback_plies(game, requester)?),
+        Some(_) => Ok(Game { takeback: None, ..game.clone() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::game::fen;
+
+    fn played(moves: &[&str]) -> Game {
+        moves.iter().try_fold(Game::new(), |game, next_move| game.make_move(next_move.to_string())).unwrap()
+    }
+
+    #[test]
+    fn undo_restores_the_position_exactly() {
+        let game = played(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O"]);
+        let before = played(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6"]);
+        let undone = game.undo().unwrap();
+        assert_eq!(undone.moves(), before.moves());
+        assert_eq!(undone.board().unwrap(), before.board().unwrap());
+        assert_eq!(
+            fen::encode(&undone.board().unwrap()),
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+        );
+        let undone = game.undo_move_pair().unwrap();
+        assert_eq!(undone.moves().len(), 5);
+        assert_eq!(undone.undone(), ["O-O", "Nf6"]);
+        assert!(Game::new().undo().is_err());
+        assert!(played(&["e4"]).undo_move_pair().is_err());
+    }
+
+    #[test]
+    fn undone_moves_can_be_replayed_until_another_is_made() {
+        let game = played(&["d4", "d5", "c4"]).undo_move_pair().unwrap();
+        let redone = game.redo().unwrap();
+        assert_eq!(redone.moves(), ["d4", "d5"]);
+        let redone = redone.redo().unwrap();
+        assert_eq!(redone.moves(), ["d4", "d5", "c4"]);
+        assert!(redone.redo().is_err());
+        // the repetition history comes back with the moves
+        assert_eq!(redone.board().unwrap(), played(&["d4", "d5", "c4"]).board().unwrap());
+        let branched = game.redo().unwrap().make_move("Nf3".to_string()).unwrap();
+        assert!(branched.undone().is_empty());
+        assert!(branched.redo().is_err());
+    }
+
+    #[test]
+    fn replayed_moves_keep_their_timing_and_annotations() {
+        let game = played(&["e4"])
+            .make_timed_move("e5".to_string(), Some(2_000.0), Some(295_000))
+            .and_then(|game| game.annotate_last_move(vec![5], Some("solid".to_string())))
+            .unwrap();
+        let redone = game.undo().and_then(|game| game.undo()).and_then(|game| game.redo()).unwrap();
+        assert_eq!(redone.records().len(), 1);
+        let redone = redone.redo().unwrap();
+        assert_eq!(redone.records(), game.records());
+        let record = redone.last_record().unwrap();
+        assert_eq!((record.timestamp_ms, record.clock_ms), (Some(2_000.0), Some(295_000)));
+        assert_eq!((record.nags.clone(), record.comment.clone()), (vec![5], Some("solid".to_string())));
+    }
+
+    #[test]
+    fn takebacks_need_the_opponents_agreement() {
+        let game = played(&["e4", "e5", "Qh5"]);
+        // Black asks on their own move, so White's reply goes too
+        let asked = game.request_takeback(Color::Black).unwrap();
+        assert_eq!(asked.takeback, Some(Color::Black));
+        assert!(asked.request_takeback(Color::White).is_err());
+        assert!(asked.accept_takeback(Color::Black).is_err());
+        let accepted = asked.accept_takeback(Color::White).unwrap();
+        assert_eq!(accepted.moves(), ["e4"]);
+        assert_eq!(accepted.takeback, None);
+
+        // White asks with Black to move: Qh5 only
+        let asked = game.request_takeback(Color::White).unwrap();
+        assert_eq!(asked.accept_takeback(Color::Black).unwrap().moves(), ["e4", "e5"]);
+        let declined = asked.decline_takeback(Color::Black).unwrap();
+        assert_eq!(declined.moves(), game.moves());
+        assert_eq!(declined.takeback, None);
+        assert!(declined.accept_takeback(Color::Black).is_err());
+
+        // a move made meanwhile ends the request
+        let moved = game.request_takeback(Color::White).unwrap().make_move("Nc6".to_string()).unwrap();
+        assert_eq!(moved.takeback, None);
+        assert!(played(&["e4"]).request_takeback(Color::Black).is_err());
+    }
+}
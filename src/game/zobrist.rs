@@ -0,0 +1,97 @@
+// Random keys for Zobrist hashing: a position's hash is the XOR of the keys for each piece on its square,
+// the castling rights, the en passant file and the side to move, so it can be updated incrementally as
+// pieces move. The keys are generated at compile time from a fixed seed so hashes are stable between runs.
+
+use super::color::*;
+use super::piece_type::*;
+use super::square::*;
+
+pub struct ZobristKeys {
+    pieces: [[[u64; 64]; 7]; 2],
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+    black_to_move: u64,
+}
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let next = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = next;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (next, z ^ (z >> 31))
+}
+
+const fn generate() -> ZobristKeys {
+    let mut keys = ZobristKeys {
+        pieces: [[[0; 64]; 7]; 2],
+        castling: [0; 16],
+        en_passant: [0; 8],
+        black_to_move: 0,
+    };
+    let mut state = 0x6e61_726d_6963_6865; // "narmiche"
+    let mut color = 0;
+    while color < 2 {
+        // piece type 0 (Empty) is left as zero so that empty squares don't affect the hash
+        let mut piece_type = 1;
+        while piece_type < 7 {
+            let mut square = 0;
+            while square < 64 {
+                let (next, key) = splitmix64(state);
+                state = next;
+                keys.pieces[color][piece_type][square] = key;
+                square += 1;
+            }
+            piece_type += 1;
+        }
+        color += 1;
+    }
+    // castling keys combine one key per right so that any combination of rights has its own key
+    let mut rights = [0; 4];
+    let mut right = 0;
+    while right < 4 {
+        let (next, key) = splitmix64(state);
+        state = next;
+        rights[right] = key;
+        right += 1;
+    }
+    let mut combination = 0;
+    while combination < 16 {
+        let mut right = 0;
+        while right < 4 {
+            if combination & (1 << right) != 0 {
+                keys.castling[combination] ^= rights[right];
+            }
+            right += 1;
+        }
+        combination += 1;
+    }
+    let mut file = 0;
+    while file < 8 {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys.en_passant[file] = key;
+        file += 1;
+    }
+    keys.black_to_move = splitmix64(state).1;
+    keys
+}
+
+pub const ZOBRIST: ZobristKeys = generate();
+
+impl ZobristKeys {
+    pub fn piece(&self, color: Color, piece_type: PieceType, square: Square) -> u64 {
+        self.pieces[color.index()][piece_type as usize][square.index() as usize]
+    }
+
+    pub fn castling(&self, rights: u8) -> u64 {
+        self.castling[(rights & 15) as usize]
+    }
+
+    pub fn en_passant(&self, file: File) -> u64 {
+        self.en_passant[file.index() as usize]
+    }
+
+    pub fn black_to_move(&self) -> u64 {
+        self.black_to_move
+    }
+}
@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate serde_derive;
 
+pub mod engine;
 pub mod game;
 
 use wasm_bindgen::prelude::*;
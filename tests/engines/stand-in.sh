@@ -0,0 +1,38 @@
+#!/bin/sh
+# A stand-in UCI engine for the external engine tests. It answers every search with the same canned
+# analysis, except that "go depth 13" gets an illegal move and a search by time is never answered until
+# it's stopped. Positions are echoed back as info strings so the tests can see what was sent.
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Stand-in 1.0"
+            echo "id author narmi tests"
+            echo "option name Hash type spin default 1 min 1 max 4"
+            echo "uciok"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        position*)
+            echo "info string $line"
+            ;;
+        "go depth 13")
+            echo "bestmove e2e5"
+            ;;
+        "go movetime"*)
+            ;;
+        go*)
+            echo "info depth 1 seldepth 1 multipv 1 score cp 13 nodes 20 nps 2000 time 10 pv d7d5"
+            echo "info depth 2 seldepth 4 multipv 1 score mate 3 nodes 400 nps 4000 hashfull 1 time 100 pv d7d5 e4d5 d8d5"
+            echo "info depth 2 multipv 2 score cp -50 pv a7a6"
+            echo "info currmove d7d5 currmovenumber 1"
+            echo "bestmove d7d5 ponder e4d5"
+            ;;
+        stop)
+            echo "bestmove g8f6"
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done
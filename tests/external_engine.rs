@@ -0,0 +1,100 @@
+//! Driving engines over UCI as child processes: a scripted stand-in engine (tests/engines/stand-in.sh) for
+//! the protocol's corners, and narmi-uci itself for a real search.
+
+extern crate narmi_chess;
+
+use std::time::{Duration, Instant};
+
+use narmi_chess::engine::external::*;
+use narmi_chess::engine::search::SearchLimits;
+use narmi_chess::game::fen;
+use narmi_chess::game::game::Game;
+
+const STAND_IN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/engines/stand-in.sh");
+
+fn stand_in() -> ExternalEngine {
+    ExternalEngine::spawn("sh", &[STAND_IN]).unwrap_or_else(|err| panic!("{}", err.reason))
+}
+
+fn after_e4() -> Game {
+    Game::new().make_move("e4".to_string()).unwrap()
+}
+
+#[test]
+#[cfg(unix)]
+fn the_handshake_names_the_engine() {
+    let engine = stand_in();
+    assert_eq!(engine.name(), Some("Stand-in 1.0"));
+    assert_eq!(engine.author(), Some("narmi tests"));
+    assert_eq!(engine.options(), ["Hash type spin default 1 min 1 max 4"]);
+    engine.quit();
+}
+
+#[test]
+#[cfg(unix)]
+fn analysis_is_typed() {
+    let mut engine = stand_in();
+    engine.new_game().unwrap();
+    let analysis = engine.analyse_game(&after_e4(), &SearchLimits::depth(4)).unwrap();
+    assert_eq!(analysis.strings(), ["position startpos moves e2e4"]);
+    assert_eq!(analysis.best_move.map(|m| m.to_string()), Some("d7-d5".to_string()));
+    assert_eq!(analysis.ponder.map(|m| m.to_string()), Some("e4-d5".to_string()));
+    // the second line of a multi-line analysis doesn't replace the first
+    assert_eq!(analysis.score, Some(Score::Mate(3)));
+    assert_eq!(analysis.mate_in(), Some(3));
+    assert_eq!(analysis.depth, Some(2));
+    assert_eq!(analysis.nodes, Some(400));
+    assert_eq!(analysis.san_pv, ["d5", "exd5", "Qxd5"]);
+    // every info line is kept, including the one only saying which move is being searched
+    assert_eq!(analysis.infos.len(), 5);
+}
+
+#[test]
+#[cfg(unix)]
+fn an_illegal_best_move_is_an_error() {
+    let mut engine = stand_in();
+    let err = engine.analyse_game(&Game::new(), &SearchLimits::depth(13)).unwrap_err();
+    assert_eq!(err.reason, "The engine chose an illegal move: e2e5");
+    // the engine is still usable afterwards
+    assert!(engine.analyse_game(&after_e4(), &SearchLimits::depth(1)).is_ok());
+}
+
+#[test]
+#[cfg(unix)]
+fn a_slow_engine_is_stopped() {
+    let mut engine = stand_in();
+    engine.set_grace(Duration::from_millis(100));
+    let started = Instant::now();
+    let analysis = engine.analyse_game(&after_e4(), &SearchLimits::movetime(100)).unwrap();
+    assert_eq!(analysis.best_move.map(|m| m.to_string()), Some("g8-f6".to_string()));
+    assert!(analysis.score.is_none());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn a_search_needs_a_limit() {
+    let mut engine = ExternalEngine::spawn(env!("CARGO_BIN_EXE_narmi-uci"), &[]).unwrap();
+    let err = engine.analyse_game(&Game::new(), &SearchLimits::default()).unwrap_err();
+    assert_eq!(err.reason, "A search needs a depth, node or time limit");
+}
+
+#[test]
+fn a_missing_engine_is_an_error() {
+    let err = ExternalEngine::spawn("./no-such-engine", &[]).err().unwrap();
+    assert!(err.reason.starts_with("Couldn't start ./no-such-engine"));
+}
+
+#[test]
+fn narmi_analyses_itself() {
+    let mut engine = ExternalEngine::spawn(env!("CARGO_BIN_EXE_narmi-uci"), &[]).unwrap();
+    assert!(engine.name().unwrap().starts_with("narmi"));
+    engine.set_option("Hash", Some("4")).unwrap();
+    let board = fen::decode("r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1").unwrap();
+    let analysis = engine.analyse(&board, &SearchLimits::depth(5)).unwrap();
+    assert_eq!(analysis.mate_in(), Some(2));
+    assert_eq!(analysis.san_pv, ["Re8+", "Rxe8", "Rxe8#"]);
+    let mated = fen::decode("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    let analysis = engine.analyse(&mated, &SearchLimits::depth(2)).unwrap();
+    assert_eq!(analysis.best_move, None);
+    assert_eq!(analysis.score, Some(Score::Mate(0)));
+}
@@ -0,0 +1,185 @@
+//! Perft regression suite: leaf node counts for well known positions, checking move generation (including
+//! castling, en passant and promotion) exactly.
+//!
+//! The deeper counts take a while in a debug build, so they are ignored by default. Run them with
+//! `cargo test --release --test perft -- --include-ignored`.
+
+extern crate narmi_chess;
+
+use narmi_chess::game::perft::perft_fen;
+
+// checks the counts for depths 1, 2, 3...
+fn assert_perft(fen: &str, expected: &[u64]) {
+    for (depth, nodes) in expected.iter().enumerate() {
+        assert_perft_at(fen, depth as u32 + 1, *nodes);
+    }
+}
+
+fn assert_perft_at(fen: &str, depth: u32, expected: u64) {
+    assert_eq!(perft_fen(fen, depth).unwrap(), expected, "depth {} of {}", depth, fen);
+}
+
+const INITIAL: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+
+#[test]
+fn initial_position() {
+    assert_perft(INITIAL, &[20, 400, 8902, 197_281]);
+}
+
+#[test]
+fn kiwipete() {
+    assert_perft(KIWIPETE, &[48, 2039, 97_862]);
+}
+
+#[test]
+fn position_3() {
+    assert_perft(POSITION_3, &[14, 191, 2812, 43_238]);
+}
+
+#[test]
+fn position_4() {
+    assert_perft(POSITION_4, &[6, 264, 9467]);
+    assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+}
+
+#[test]
+fn position_5() {
+    assert_perft(POSITION_5, &[44, 1486, 62_379]);
+}
+
+// en passant
+
+#[test]
+fn en_passant_capture_which_would_expose_the_king_along_the_rank() {
+    assert_perft("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", &[18, 92, 1670, 10_138]);
+}
+
+#[test]
+fn en_passant_capture_which_gives_discovered_check() {
+    assert_perft("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", &[15, 126, 1928, 13_931]);
+}
+
+#[test]
+fn en_passant_capture_of_a_checking_pawn() {
+    assert_perft("8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1", &[6]);
+}
+
+#[test]
+fn avoid_illegal_en_passant_capture() {
+    assert_perft("8/5bk1/8/2Pp4/8/1K6/8/8 w - d6 0 1", &[8, 104, 736, 9287]);
+}
+
+// castling
+
+#[test]
+fn short_castling_gives_check() {
+    assert_perft("5k2/8/8/8/8/8/8/4K2R w K - 0 1", &[15, 66, 1198, 6399]);
+}
+
+#[test]
+fn long_castling_gives_check() {
+    assert_perft("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", &[16, 71, 1286, 7418]);
+}
+
+#[test]
+fn castling_rights_are_lost_when_rooks_are_captured() {
+    assert_perft("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", &[26, 1141, 27_826]);
+}
+
+#[test]
+fn castling_is_prevented_by_attacked_squares() {
+    assert_perft("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", &[44, 1494, 50_509]);
+}
+
+// promotion
+
+#[test]
+fn promotion_out_of_check() {
+    assert_perft("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", &[11, 133, 1442, 19_174]);
+}
+
+#[test]
+fn promotion_to_give_check() {
+    assert_perft("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", &[9, 40, 472, 2661]);
+}
+
+#[test]
+fn under_promotion_to_give_check() {
+    assert_perft("8/P1k5/K7/8/8/8/8/8 w - - 0 1", &[6, 27, 273, 1329]);
+}
+
+// checks and stalemates
+
+#[test]
+fn discovered_check() {
+    assert_perft("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", &[29, 165, 5160]);
+}
+
+#[test]
+fn self_stalemate() {
+    assert_perft("K1k5/8/P7/8/8/8/8/8 w - - 0 1", &[2, 6, 13, 63, 382, 2217]);
+}
+
+#[test]
+fn stalemate_and_checkmate() {
+    assert_perft("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", &[10, 25, 268, 926, 10_857]);
+}
+
+#[test]
+fn double_check() {
+    assert_perft_at("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4, 23_527);
+}
+
+// deeper counts
+
+#[test]
+#[ignore]
+fn initial_position_deep() {
+    assert_perft(INITIAL, &[20, 400, 8902, 197_281, 4_865_609]);
+}
+
+#[test]
+#[ignore]
+fn kiwipete_deep() {
+    assert_perft(KIWIPETE, &[48, 2039, 97_862, 4_085_603]);
+}
+
+#[test]
+#[ignore]
+fn position_3_deep() {
+    assert_perft(POSITION_3, &[14, 191, 2812, 43_238, 674_624, 11_030_083]);
+}
+
+#[test]
+#[ignore]
+fn position_4_deep() {
+    assert_perft(POSITION_4, &[6, 264, 9467, 422_333, 15_833_292]);
+}
+
+#[test]
+#[ignore]
+fn position_5_deep() {
+    assert_perft(POSITION_5, &[44, 1486, 62_379, 2_103_487]);
+}
+
+#[test]
+#[ignore]
+fn edge_cases_deep() {
+    assert_perft("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", &[18, 92, 1670, 10_138, 185_429, 1_134_888]);
+    assert_perft_at("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", 6, 1_015_133);
+    assert_perft("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", &[15, 126, 1928, 13_931, 206_379, 1_440_467]);
+    assert_perft("5k2/8/8/8/8/8/8/4K2R w K - 0 1", &[15, 66, 1198, 6399, 120_330, 661_072]);
+    assert_perft("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", &[16, 71, 1286, 7418, 141_077, 803_711]);
+    assert_perft("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", &[26, 1141, 27_826, 1_274_206]);
+    assert_perft("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", &[44, 1494, 50_509, 1_720_476]);
+    assert_perft("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", &[11, 133, 1442, 19_174, 266_199, 3_821_001]);
+    assert_perft("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", &[29, 165, 5160, 31_961, 1_004_658]);
+    assert_perft("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", &[9, 40, 472, 2661, 38_983, 217_342]);
+    assert_perft("8/P1k5/K7/8/8/8/8/8 w - - 0 1", &[6, 27, 273, 1329, 18_135, 92_683]);
+    assert_perft("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", &[10, 25, 268, 926, 10_857, 43_261, 567_584]);
+}
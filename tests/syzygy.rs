@@ -0,0 +1,71 @@
+//! Probing real Syzygy tables: the few small 3 piece files in tests/syzygy_tables, and optionally a larger set
+//! (with the 4 piece files, from https://tablebase.lichess.ovh) found through the SYZYGY_PATH environment
+//! variable, whose test is ignored unless asked for:
+//!
+//!     SYZYGY_PATH=/path/to/syzygy cargo test --test syzygy -- --ignored
+
+extern crate narmi_chess;
+
+use std::env;
+
+use narmi_chess::engine::syzygy::*;
+use narmi_chess::game::fen;
+use narmi_chess::game::notation::encode;
+
+fn tablebases() -> Tablebases {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy_tables");
+    let tablebases = Tablebases::open(path).unwrap_or_else(|err| panic!("{}", err.reason));
+    assert_eq!(tablebases.max_pieces(), 3);
+    tablebases
+}
+
+fn probe(tablebases: &Tablebases, fen: &str) -> (Wdl, i32) {
+    let board = fen::decode(fen).unwrap();
+    let wdl = tablebases.probe_wdl(&board).unwrap_or_else(|err| panic!("{}: {}", fen, err.reason));
+    let dtz = tablebases.probe_dtz(&board).unwrap_or_else(|err| panic!("{}: {}", fen, err.reason));
+    (wdl, dtz)
+}
+
+#[test]
+fn results_are_probed() {
+    let tablebases = tablebases();
+    assert_eq!(probe(&tablebases, "4k3/8/8/8/8/8/8/4KQ2 w - - 0 1").0, Wdl::Win);
+    assert_eq!(probe(&tablebases, "4k3/8/8/8/8/8/8/4KQ2 b - - 0 1").0, Wdl::Loss);
+    // the king in front of the pawn holds
+    assert_eq!(probe(&tablebases, "8/8/8/8/8/4k3/4P3/4K3 w - - 0 1"), (Wdl::Draw, 0));
+    // mate in one
+    assert_eq!(probe(&tablebases, "k7/8/1K6/8/8/8/8/7R w - - 0 1"), (Wdl::Win, 1));
+    assert_eq!(probe(&tablebases, "k7/8/1K6/8/8/8/8/7R b - - 0 1").0, Wdl::Loss);
+    // colours the other way round to the table's name
+    assert_eq!(probe(&tablebases, "4K3/8/8/8/8/8/8/4kq2 b - - 0 1").0, Wdl::Win);
+    // a pawn about to queen with the defending king too far away
+    assert_eq!(probe(&tablebases, "8/4P3/8/8/8/8/k7/4K3 w - - 0 1"), (Wdl::Win, 1));
+}
+
+#[test]
+fn the_best_moves_keep_the_win() {
+    let tablebases = tablebases();
+    let board = fen::decode("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+    let verdict = tablebases.verdict(&board).unwrap();
+    assert_eq!(verdict.best_move.map(|chess_move| encode(&board, &chess_move)), Some("Rh8#".to_string()));
+    assert_eq!(verdict.to_string(), "win in 1 ply to a capture or pawn move");
+    let board = fen::decode("8/8/3k4/8/8/8/8/3QK3 w - - 0 1").unwrap();
+    let root_moves = tablebases.root_moves(&board).unwrap();
+    let best = tablebases.best_moves(&board).unwrap();
+    assert!(!best.is_empty() && best.len() < root_moves.len());
+    for root_move in root_moves.iter().filter(|root_move| best.contains(&root_move.chess_move)) {
+        assert_eq!(root_move.wdl, Wdl::Win);
+    }
+    // leaving the queen where the king can take it (as Qd5+ does) draws
+    assert!(root_moves.iter().any(|root_move| root_move.wdl == Wdl::Draw));
+}
+
+#[test]
+#[ignore = "needs the Syzygy tables named by SYZYGY_PATH"]
+fn larger_tables_are_probed() {
+    let path = env::var("SYZYGY_PATH").expect("SYZYGY_PATH should name the directory holding the tables");
+    let tablebases = Tablebases::open(&path).unwrap_or_else(|err| panic!("{}", err.reason));
+    assert!(tablebases.max_pieces() >= 4, "the 4 piece tables are needed in {}", path);
+    // bishop and knight mate, a long way off
+    assert_eq!(probe(&tablebases, "8/8/8/8/B7/N7/K2k4/8 b - - 0 1"), (Wdl::Loss, -59));
+}